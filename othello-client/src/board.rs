@@ -5,15 +5,45 @@ use std::f64::consts::PI;
 use stdweb::traits::*;
 use stdweb::unstable::TryInto;
 use stdweb::web::html_element::CanvasElement;
-use stdweb::web::{document, CanvasRenderingContext2d, FillRule};
+use stdweb::web::{document, CanvasRenderingContext2d, FillRule, TextAlign};
 use stdweb::web::window;
 
 use stdweb::web::event::ClickEvent;
 
 use super::context::Context;
+use yew::services::storage::StorageService;
 
-use model::{BoardModel, Cell, BOARD_SIZE};
-use wscommand::Color;
+use clock::LocalClocks;
+use clock_view::ClockView;
+use engine;
+use engine::model::{BoardModel, Cell, BOARD_SIZE};
+use puzzle;
+use puzzle_board;
+use review::{self, Annotation, MoveReview};
+use wscommand::{Color, GameOverReason, WsBoardState, WsGameResult, WsRating};
+
+/// Number of hints a player can ask during a game
+const HINTS_PER_GAME: usize = 3;
+/// Depth of the midgame search used for hints
+const HINT_DEPTH: usize = 4;
+
+pub const HINT_COLOR: &str = "#fd3";
+pub const GOOD_MOVE_COLOR: &str = "#3cf";
+pub const BAD_MOVE_COLOR: &str = "#f43";
+
+/// Width of a square, depending on the window size
+pub fn cell_width() -> u32 {
+    let cell_width = ((window().inner_width() as u32) / (BOARD_SIZE as u32)) - 2;
+    let cell_width = ::std::cmp::min(60, cell_width);
+    ::std::cmp::max(32, cell_width)
+}
+
+/// A square highlighted on the board with its engine score
+pub struct Marker {
+    pub pos: usize,
+    pub score: i32,
+    pub color: &'static str,
+}
 
 pub struct BoardUI {
     board: BoardModel,
@@ -63,7 +93,37 @@ impl BoardUI {
         context.stroke();
     }
 
-    pub fn paint(&self, player: Cell, context: &CanvasRenderingContext2d) {
+    /// Highlight a square and display its engine score
+    fn paint_marker(&self, marker: &Marker, context: &CanvasRenderingContext2d) {
+        let width = self.cell_width - self.margin_width * 2.;
+        let posx = (marker.pos % BOARD_SIZE) as f64 * self.cell_width;
+        let posy = (marker.pos / BOARD_SIZE) as f64 * self.cell_width;
+        context.set_line_width(3.);
+        context.set_stroke_style_color(marker.color);
+        context.stroke_rect(
+            posx + self.margin_width + 1.5,
+            posy + self.margin_width + 1.5,
+            width - 3.,
+            width - 3.,
+        );
+        context.set_line_width(1.);
+        context.set_fill_style_color(marker.color);
+        context.set_font(&format!("{}px sans-serif", (width / 4.).floor()));
+        context.set_text_align(TextAlign::Center);
+        context.fill_text(
+            &format!("{:+}", marker.score),
+            posx + width / 2.,
+            posy + width * 0.85,
+            None,
+        );
+    }
+
+    pub fn paint(
+        &self,
+        player: Cell,
+        markers: &[Marker],
+        context: &CanvasRenderingContext2d,
+    ) {
         let width = self.cell_width - self.margin_width * 2.;
 
         for x in 0..BOARD_SIZE {
@@ -103,6 +163,9 @@ impl BoardUI {
                 width,
             );
         }
+        for marker in markers {
+            self.paint_marker(marker, context);
+        }
     }
     fn can_play(&self, player: Cell) -> bool {
         self.board.get_possibilities(player).len() > 0
@@ -118,10 +181,19 @@ pub struct Store {
     local_player: Cell,
     game_over: bool,
     cell_width: u32,
+    /// the engine suggestion for the current move
+    hint: Option<(usize, i32)>,
+    hints_left: usize,
+    /// the engine assessment of the position, from the black side
+    evaluation: Option<i32>,
+    /// moves played since the beginning of the game
+    moves: Vec<(Cell, usize)>,
+    /// the result sent by the server
+    result: Option<WsGameResult>,
 }
 
 impl Store {
-    fn new(cell_width: u32) -> Self {
+    pub fn new(cell_width: u32) -> Self {
         let board = BoardModel::new();
         let board = BoardUI::new(board, cell_width, 1);
         Store {
@@ -130,25 +202,54 @@ impl Store {
             game_over: false,
             current_player: Cell::Black,
             local_player: Cell::Empty, // will be ellected
+            hint: None,
+            hints_left: HINTS_PER_GAME,
+            evaluation: None,
+            moves: Vec::new(),
+            result: None,
         }
     }
 
-    fn cell_width(&self) -> u32 {
+    pub fn cell_width(&self) -> u32 {
         self.cell_width
     }
 
-    fn paint(&self, context: &CanvasRenderingContext2d) {
+    pub fn current_player(&self) -> Cell {
+        self.current_player
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.game_over
+    }
+
+    pub fn evaluation(&self) -> Option<i32> {
+        self.evaluation
+    }
+
+    pub fn result(&self) -> Option<&WsGameResult> {
+        self.result.as_ref()
+    }
+
+    pub fn paint(&self, context: &CanvasRenderingContext2d) {
         let player = if self.local_player == self.current_player {
             self.local_player
         } else {
             Cell::Empty
         };
-        self.board.paint(player, context);
+        let markers: Vec<Marker> = self.hint
+            .iter()
+            .map(|&(pos, score)| Marker {
+                pos,
+                score,
+                color: HINT_COLOR,
+            })
+            .collect();
+        self.board.paint(player, &markers, context);
         let score = self.board.score();
         info!("Black: {} - White: {}", score.0, score.1);
     }
 
-    fn play(&mut self, x: usize, y: usize) -> Result<(), ()> {
+    pub fn play(&mut self, x: usize, y: usize) -> Result<(), ()> {
         if self.game_over {
             // you cannot play after the game is over
             return Err(());
@@ -158,6 +259,8 @@ impl Store {
             return Err(());
         }
         if let Ok(_) = self.board.board.set_cell(x, y, self.current_player) {
+            self.hint = None;
+            self.moves.push((self.current_player, x + y * BOARD_SIZE));
             if self.board.can_play(self.current_player.opposite()) {
                 self.current_player = self.current_player.opposite();
                 info!("Player {:?} play", self.current_player);
@@ -169,18 +272,101 @@ impl Store {
         Ok(())
     }
 
-    fn score(&self) -> (usize, usize) {
+    pub fn score(&self) -> (usize, usize) {
         self.board.score()
     }
 
+    /// The game can be aborted until both players have moved
+    pub fn can_abort(&self) -> bool {
+        let moved = |player| self.moves.iter().any(|&(cell, _)| cell == player);
+        !(moved(Cell::Black) && moved(Cell::White))
+    }
+
+    /// Replace the board by the one of the server, after a rejected move
+    fn resync(&mut self, state: &WsBoardState) {
+        self.board.board = BoardModel::from_string(&state.position);
+        self.current_player = match state.current_player {
+            Color::Black => Cell::Black,
+            Color::White => Cell::White,
+        };
+        self.game_over = false;
+        self.hint = None;
+        self.evaluation = None;
+        // the rejected move is not part of the game
+        if self.moves.last().map(|&(player, _)| player) == Some(self.local_player) {
+            self.moves.pop();
+        }
+    }
+
+    /// Replay the moves of a resumed game
+    pub fn restore(&mut self, moves: &[usize]) {
+        for &pos in moves {
+            let _ = self.play(pos % BOARD_SIZE, pos / BOARD_SIZE);
+        }
+    }
+
+    /// The server ended the game, it may be before the end of the moves
+    pub fn finish(&mut self, result: &WsGameResult) {
+        self.game_over = true;
+        self.hint = None;
+        self.result = Some(result.clone());
+    }
+
+    /// Ask the engine the best move for the local player
+    fn hint(&mut self) {
+        if self.hints_left == 0 || self.hint.is_some() {
+            return;
+        }
+        if let Some(hint) =
+            engine::best_move(&self.board.board, self.local_player, HINT_DEPTH)
+        {
+            self.hints_left -= 1;
+            self.hint = Some(hint);
+        }
+    }
+
+    /// Ask the engine the expected final disc difference
+    pub fn evaluate(&mut self) {
+        let score = engine::assess(&self.board.board, self.current_player, HINT_DEPTH);
+        self.evaluation = Some(if self.current_player == Cell::Black {
+            score
+        } else {
+            -score
+        });
+    }
+
+    /// Paint the position before the reviewed move, with the played move
+    /// and the best alternative
+    fn paint_review(&self, review: &MoveReview, ply: usize, context: &CanvasRenderingContext2d) {
+        let position = review::position_before(&self.moves, ply);
+        let board = BoardUI::new(position, self.cell_width, 1);
+        let mut markers = vec![];
+        if review.best.0 != review.pos {
+            markers.push(Marker {
+                pos: review.best.0,
+                score: review.best.1,
+                color: GOOD_MOVE_COLOR,
+            });
+        }
+        markers.push(Marker {
+            pos: review.pos,
+            score: review.score,
+            color: if review.annotation == Annotation::Good {
+                GOOD_MOVE_COLOR
+            } else {
+                BAD_MOVE_COLOR
+            },
+        });
+        board.paint(Cell::Empty, &markers, context);
+    }
 }
 
-struct Canvas {
+pub struct Canvas {
     canvas: CanvasElement,
 }
 
 impl Canvas {
-    fn new(selector: &str, store: &Store) -> Canvas {
+    pub fn new(selector: &str, cell_width: u32) -> Canvas {
         let canvas: CanvasElement = document()
             .query_selector(selector)
             .unwrap()
@@ -188,7 +374,7 @@ impl Canvas {
             .try_into()
             .unwrap();
 
-        let canvas_width = store.cell_width() as u32 * BOARD_SIZE as u32;
+        let canvas_width = cell_width * BOARD_SIZE as u32;
 
         canvas.set_width(canvas_width);
         canvas.set_height(canvas_width);
@@ -196,16 +382,31 @@ impl Canvas {
         Canvas { canvas }
     }
 
-    fn context(&self) -> CanvasRenderingContext2d {
+    pub fn context(&self) -> CanvasRenderingContext2d {
         self.canvas.get_context().unwrap()
     }
 }
 
+/// Where the rematch of the finished game stands
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Rematch {
+    /// the user can offer to play again
+    Available,
+    /// the user offered a rematch, the opponent has not answered
+    Offered,
+    /// the opponent offers a rematch
+    Received,
+    /// declined, or the opponent left
+    Declined,
+}
+
 #[derive(PartialEq)]
 enum Status {
     BeingCreated,
     WaitingOpponent,
     Playing,
+    /// Replaying a finished game with the engine annotations
+    Reviewing,
 }
 
 pub struct Board {
@@ -213,39 +414,115 @@ pub struct Board {
     store: Store,
     cell_width: u32,
     status: Status,
+    /// the engine review of the game, once it is over
+    review: Vec<MoveReview>,
+    /// the move displayed in the review
+    review_ply: usize,
+    /// the board joined on the server, kept once the game is over
+    board_id: String,
     nickname: String,
     opponent: Option<String>,
+    rating: Option<WsRating>,
+    opponent_rating: Option<WsRating>,
+    /// the opponent lost the connection and may come back
+    opponent_away: bool,
+    /// the number of sessions watching the game
+    spectators: usize,
+    clocks: Option<LocalClocks>,
+    rematch: Rematch,
+    /// the game has been restored from a resumed session
+    resuming: bool,
+    hints: bool,
+    evaluation: bool,
+    rated: bool,
     onstart: Option<Callback<()>>,
     onclick: Option<Callback<(usize, usize)>>,
-    ongameover: Option<Callback<(usize, usize)>>,
+    onresign: Option<Callback<()>>,
+    onabort: Option<Callback<()>>,
+    onrematch: Option<Callback<bool>>,
 }
 
 #[derive(PartialEq, Clone)]
 pub struct Props {
+    pub board_id: String,
     pub color: Option<Color>,
     pub nickname: String,
     pub opponent: Option<String>,
+    /// the ratings of the players, `None` on a server that does not rate them
+    pub rating: Option<WsRating>,
+    pub opponent_rating: Option<WsRating>,
     pub opponent_move: Option<(usize, usize)>,
+    /// the board of the server, when it refused a move
+    pub board_state: Option<WsBoardState>,
+    /// the final result of the game, sent by the server
+    pub game_result: Option<WsGameResult>,
+    /// the moves of the game to restore, when the session is resumed
+    pub resumed_moves: Option<Vec<usize>>,
+    pub opponent_away: bool,
+    pub spectators: usize,
+    /// `None` for a game without clock
+    pub clocks: Option<LocalClocks>,
+    /// once the game is over
+    pub rematch: Rematch,
+    /// the players can ask the engine for a hint
+    pub hints: bool,
+    /// display the engine evaluation of the position
+    pub evaluation: bool,
+    /// the game is rated, the engine does not help the players
+    pub rated: bool,
     pub onstart: Option<Callback<()>>,
     pub onclick: Option<Callback<(usize, usize)>>,
-    pub ongameover: Option<Callback<(usize, usize)>>,
+    pub onresign: Option<Callback<()>>,
+    pub onabort: Option<Callback<()>>,
+    /// offer or accept a rematch, `false` to decline it
+    pub onrematch: Option<Callback<bool>>,
 }
 
 impl Default for Props {
     fn default() -> Self {
         Props {
+            board_id: "".to_string(),
             nickname: "".to_string(),
             opponent: None,
+            rating: None,
+            opponent_rating: None,
             opponent_move: None,
+            board_state: None,
+            game_result: None,
+            resumed_moves: None,
+            opponent_away: false,
+            spectators: 0,
+            clocks: None,
+            rematch: Rematch::Declined,
             color: None,
+            hints: false,
+            evaluation: false,
+            rated: false,
             onstart: None,
             onclick: None,
-            ongameover: None,
+            onresign: None,
+            onabort: None,
+            onrematch: None,
         }
     }
 }
 
+/// The rating after a player name, if the server rates the players
+fn view_rating(rating: &Option<WsRating>) -> String {
+    match *rating {
+        Some(ref rating) => format!(" ({})", rating),
+        None => "".to_string(),
+    }
+}
+
 impl Board {
+    /// Bind the canvas, once it is in the page
+    fn attach(&mut self) {
+        if self.canvas.is_none() {
+            self.canvas = Some(Canvas::new("#game", self.cell_width));
+        }
+    }
+
     fn paint(&mut self) {
         if let Some(ref canvas) = self.canvas {
             let context = canvas.context();
@@ -257,13 +534,54 @@ impl Board {
         self.canvas.as_ref().unwrap().context()
     }
 
+    /// Keep the puzzles found in the finished game
+    fn collect_puzzles(&self, env: &mut Env<Context, Self>) {
+        let found = puzzle::find_puzzles(&self.store.moves);
+        info!("{} puzzle(s) found in the game", found.len());
+        if found.is_empty() {
+            return;
+        }
+        let storage: &mut StorageService = env.as_mut();
+        let mut puzzles = puzzle_board::load_puzzles(storage);
+        for p in found {
+            if !puzzles.iter().any(|known| known.position == p.position) {
+                puzzles.push(p);
+            }
+        }
+        puzzle_board::save_puzzles(storage, &puzzles);
+    }
+
     fn view_start_button(&self) -> Html<Context, Self> {
-        if self.status == Status::BeingCreated {
+        if self.status == Status::BeingCreated && self.resuming {
             html!{
                 <div>
                     <button
                         onclick=|_|Msg::AttachEvent,
-                        >{"Join a board"}
+                        >{ "Resume the game" }
+                    </button>
+                </div>
+            }
+        } else {
+            html! {
+                <>
+                </>
+            }
+        }
+    }
+    fn view_hint_button(&self) -> Html<Context, Self> {
+        if self.hints
+            && !self.rated
+            && self.status == Status::Playing
+            && self.opponent.is_some()
+            && !self.store.game_over
+            && self.store.current_player == self.store.local_player
+        {
+            html!{
+                <div>
+                    <button
+                        disabled=self.store.hints_left == 0,
+                        onclick=|_|Msg::Hint,
+                        >{ format!("Hint ({} left)", self.store.hints_left) }
                     </button>
                 </div>
             }
@@ -274,6 +592,104 @@ impl Board {
             }
         }
     }
+
+    /// Abort the game until both players have moved, resign after
+    fn view_game_buttons(&self) -> Html<Context, Self> {
+        if self.status != Status::Playing || self.opponent.is_none() || self.store.game_over {
+            html! {
+                <>
+                </>
+            }
+        } else if self.store.can_abort() {
+            html! {
+                <button
+                    onclick=|_|Msg::Abort,
+                    >{"Abort"}
+                </button>
+            }
+        } else {
+            html! {
+                <button
+                    onclick=|_|Msg::Resign,
+                    >{"Resign"}
+                </button>
+            }
+        }
+    }
+
+    fn view_rematch(&self) -> Html<Context, Self> {
+        let opponent = self.opponent.as_ref().map_or("the opponent", |name| name.as_str());
+        match self.rematch {
+            Rematch::Available => html! {
+                <button
+                    onclick=|_|Msg::Rematch(true),
+                    >{"Rematch"}
+                </button>
+            },
+            Rematch::Offered => html! {
+                <>
+                    { format!("Waiting for {} to accept the rematch ", opponent) }
+                    <button
+                        onclick=|_|Msg::Rematch(false),
+                        >{"Cancel the rematch"}
+                    </button>
+                </>
+            },
+            Rematch::Received => html! {
+                <>
+                    { format!("{} offers a rematch ", opponent) }
+                    <button
+                        onclick=|_|Msg::Rematch(true),
+                        >{"Accept"}
+                    </button>
+                    <button
+                        onclick=|_|Msg::Rematch(false),
+                        >{"Decline"}
+                    </button>
+                </>
+            },
+            Rematch::Declined => html! {
+                <>
+                    {"No rematch"}
+                </>
+            },
+        }
+    }
+
+    fn view_opponent_away(&self) -> Html<Context, Self> {
+        if self.opponent_away && !self.store.game_over {
+            html! {
+                <>
+                {" (reconnecting…)"}
+                </>
+            }
+        } else {
+            html! {
+                <>
+                </>
+            }
+        }
+    }
+
+    fn view_spectators(&self) -> Html<Context, Self> {
+        if self.spectators > 0 && self.status == Status::Playing && !self.store.game_over {
+            html! {
+                <p>{ format!("{} spectator(s)", self.spectators) }</p>
+            }
+        } else {
+            html! {
+                <>
+                </>
+            }
+        }
+    }
+
+    fn view_clock(&self, color: Color) -> Html<Context, Self> {
+        html! {
+            <ClockView: clocks=&self.clocks, color=color, />
+        }
+    }
+
     fn view_playing(&self, cell: Cell) -> Html<Context, Self> {
         if self.store.current_player == cell {
             html! {
@@ -294,16 +710,8 @@ impl Board {
         let score = self.store.score();
 
         if self.store.game_over {
-            let result = if score.0 == score.1 {
-                "draw".to_string()
-            }
-            else if (self.store.local_player == Cell::Black && score.0 > score.1) ||
-               (self.store.local_player == Cell::White && score.0 < score.1) {
-                format!("{} win!", self.nickname)
-            }
-            else {
-                format!("{} win!", self.opponent.as_ref().unwrap())
-            };
+            let score = self.store.result.as_ref().map_or(score, |result| result.score);
+            let result = self.view_result(score);
 
             html! {
                 <div>
@@ -314,9 +722,10 @@ impl Board {
                     { result }
                     <br/>
                     <button
-                        onclick=|_|Msg::RespawnBoard,
-                        >{"Play again"}
+                        onclick=|_|Msg::Review,
+                        >{"Review the game"}
                     </button>
+                    { self.view_rematch() }
                 </div>
             }
         }
@@ -330,13 +739,154 @@ impl Board {
                         <div style={ format!("float left; background: black; height: 100%; width: {}%", percent)},>
                         </div>
                     </div>
+                    { self.view_evaluation() }
+                    { self.view_game_buttons() }
                 </div>
             }
         }
     }
 
+    /// The result line, the one of the server once received
+    fn view_result(&self, score: (usize, usize)) -> String {
+        let (winner, reason) = match self.store.result {
+            Some(ref result) => (
+                match result.winner {
+                    Some(Color::Black) => Cell::Black,
+                    Some(Color::White) => Cell::White,
+                    None => Cell::Empty,
+                },
+                result.reason.clone(),
+            ),
+            None if score.0 > score.1 => (Cell::Black, GameOverReason::Normal),
+            None if score.0 < score.1 => (Cell::White, GameOverReason::Normal),
+            None => (Cell::Empty, GameOverReason::Normal),
+        };
+        if reason == GameOverReason::Aborted {
+            return "the game has been aborted".to_string();
+        }
+        if winner == Cell::Empty {
+            return "draw".to_string();
+        }
+        let name = if winner == self.store.local_player {
+            self.nickname.as_str()
+        } else {
+            self.opponent.as_ref().map_or("opponent", |name| name.as_str())
+        };
+        match reason {
+            GameOverReason::Normal => format!("{} win!", name),
+            GameOverReason::Resignation => format!("{} win by resignation!", name),
+            GameOverReason::Timeout => format!("{} win on time!", name),
+            GameOverReason::Disconnect => format!("{} win, the opponent left the game", name),
+            GameOverReason::Aborted => "the game has been aborted".to_string(),
+        }
+    }
+
+    fn view_review(&self) -> Html<Context, Self> {
+        let review = &self.review[self.review_ply];
+        let player = match review.player {
+            Cell::Black => "Black",
+            _ => "White",
+        };
+        let comment = if review.best.0 == review.pos {
+            "best move".to_string()
+        } else {
+            format!(
+                "{}, best was {} ({:+})",
+                review.annotation.label(),
+                review::square_name(review.best.0),
+                review.best.1
+            )
+        };
+        html!{
+            <div>
+                <p>
+                    { format!("Move {}/{}: {} {} ({:+}) - {}",
+                              self.review_ply + 1,
+                              self.review.len(),
+                              player,
+                              review::square_name(review.pos),
+                              review.score,
+                              comment) }
+                </p>
+                <button
+                    disabled=self.review_ply == 0,
+                    onclick=|_|Msg::ReviewMove(-1),
+                    >{"◀ Previous"}
+                </button>
+                <button
+                    disabled=self.review_ply + 1 >= self.review.len(),
+                    onclick=|_|Msg::ReviewMove(1),
+                    >{"Next ▶"}
+                </button>
+                <button
+                    onclick=|_|Msg::CloseReview,
+                    >{"Close the review"}
+                </button>
+                <ul>
+                    <li>{ format!("Black: {} inaccuracies, {} mistakes, {} blunders",
+                                  self.count_annotations(Cell::Black, Annotation::Inaccuracy),
+                                  self.count_annotations(Cell::Black, Annotation::Mistake),
+                                  self.count_annotations(Cell::Black, Annotation::Blunder)) }</li>
+                    <li>{ format!("White: {} inaccuracies, {} mistakes, {} blunders",
+                                  self.count_annotations(Cell::White, Annotation::Inaccuracy),
+                                  self.count_annotations(Cell::White, Annotation::Mistake),
+                                  self.count_annotations(Cell::White, Annotation::Blunder)) }</li>
+                </ul>
+            </div>
+        }
+    }
+
+    fn count_annotations(&self, player: Cell, annotation: Annotation) -> usize {
+        self.review
+            .iter()
+            .filter(|r| r.player == player && r.annotation == annotation)
+            .count()
+    }
+
+    fn paint_review(&self) {
+        if let Some(ref canvas) = self.canvas {
+            let context = canvas.context();
+            self.store
+                .paint_review(&self.review[self.review_ply], self.review_ply, &context);
+        }
+    }
+
+    /// The evaluation bar is off for the players of a rated game
+    fn shows_evaluation(&self) -> bool {
+        self.evaluation && !self.rated
+    }
+
+    fn view_evaluation(&self) -> Html<Context, Self> {
+        match self.store.evaluation {
+            Some(score) if self.shows_evaluation() => {
+                let width = self.store.cell_width() as usize * BOARD_SIZE;
+                let probability = engine::win_probability(score);
+                let leader = if score >= 0 { "Black" } else { "White" };
+                html! {
+                    <div style={ format!("max-width: {}px", width)},>
+                        <div style="height:12px; border:1px solid black; background: white;",>
+                            <div style={ format!("float left; background: black; height: 100%; width: {}%", probability * 100.)},>
+                            </div>
+                        </div>
+                        <small>
+                            { format!("Evaluation: {} {:+} ({:.0}% for black)",
+                                      leader, score.abs(), probability * 100.) }
+                        </small>
+                    </div>
+                }
+            }
+            _ => {
+                html! {
+                    <>
+                    </>
+                }
+            }
+        }
+    }
+
     fn view_player_score(&self) -> Html<Context, Self> {
         match self.status {
+            Status::Reviewing => self.view_review(),
             Status::BeingCreated => {
                 html!{
                     <>
@@ -348,7 +898,7 @@ impl Board {
                     Cell::Black => {
                         html!{
                             <ul>
-                                <li>{"Black: "}{ self.nickname.as_str() }</li>
+                                <li>{"Black: "}{ self.nickname.as_str() }{ view_rating(&self.rating) }</li>
                                 <li>{"White: Waiting for another player"}</li>
                             </ul>
                         }
@@ -357,7 +907,7 @@ impl Board {
                         html!{
                             <ul>
                                 <li>{"Black: Waiting for another player"}</li>
-                                <li>{"White: "}{ self.nickname.as_str() }</li>
+                                <li>{"White: "}{ self.nickname.as_str() }{ view_rating(&self.rating) }</li>
                             </ul>
                         }
                     }
@@ -378,8 +928,8 @@ impl Board {
                             html!{
                                 <>
                                     <ul>
-                                        <li>{"Black: "}{ self.nickname.as_str() }{ self.view_playing(Cell::Black) }</li>
-                                        <li>{"White: "}{ opponent }{ self.view_playing(Cell::White) }</li>
+                                        <li>{"Black: "}{ self.nickname.as_str() }{ view_rating(&self.rating) }{ self.view_clock(Color::Black) }{ self.view_playing(Cell::Black) }</li>
+                                        <li>{"White: "}{ opponent }{ view_rating(&self.opponent_rating) }{ self.view_opponent_away() }{ self.view_clock(Color::White) }{ self.view_playing(Cell::White) }</li>
                                     </ul>
                                     { self.view_game_advancement() }
                                 </>
@@ -389,8 +939,8 @@ impl Board {
                             html!{
                                 <>
                                     <ul>
-                                        <li>{"Black: "}{ opponent } { self.view_playing(Cell::Black) }</li>
-                                        <li>{"White: "}{ self.nickname.as_str() } { self.view_playing(Cell::White) }</li>
+                                        <li>{"Black: "}{ opponent }{ view_rating(&self.opponent_rating) } { self.view_opponent_away() }{ self.view_clock(Color::Black) }{ self.view_playing(Cell::Black) }</li>
+                                        <li>{"White: "}{ self.nickname.as_str() }{ view_rating(&self.rating) } { self.view_clock(Color::White) }{ self.view_playing(Cell::White) }</li>
                                     </ul>
                                     { self.view_game_advancement() }
                                 </>
@@ -425,8 +975,19 @@ impl Board {
 pub enum Msg {
     AttachEvent,
     Clicked(ClickEvent),
+    /// Show the engine suggestion
+    Hint,
+    /// Analyse the finished game
+    Review,
+    /// Move forward or backward in the review
+    ReviewMove(isize),
+    CloseReview,
     /// Restart the game
     RespawnBoard,
+    Resign,
+    Abort,
+    /// Offer or accept a rematch, or decline it
+    Rematch(bool),
 }
 
 impl Component<Context> for Board {
@@ -435,32 +996,61 @@ impl Component<Context> for Board {
 
     fn create(props: Self::Properties, _env: &mut Env<Context, Self>) -> Self {
         info!("Creating the board");
-        let cell_width = ((window().inner_width() as u32) / (BOARD_SIZE as u32)) - 2;
-        let cell_width = ::std::cmp::min(60, cell_width);
-        let cell_width = ::std::cmp::max(32, cell_width);
+        let cell_width = cell_width();
+        let mut store = Store::new(cell_width);
+        let resuming = match (props.resumed_moves, props.color) {
+            (Some(moves), Some(color)) => {
+                store.local_player = match color {
+                    Color::Black => Cell::Black,
+                    Color::White => Cell::White,
+                };
+                store.restore(&moves);
+                true
+            }
+            _ => false,
+        };
         Board {
             canvas: None,
             cell_width: cell_width,
-            store: Store::new(cell_width),
+            store,
+            board_id: props.board_id,
             nickname: props.nickname,
             opponent: props.opponent,
+            rating: props.rating,
+            opponent_rating: props.opponent_rating,
+            opponent_away: props.opponent_away,
+            spectators: props.spectators,
+            clocks: props.clocks,
+            rematch: props.rematch,
+            resuming,
+            hints: props.hints,
+            evaluation: props.evaluation,
+            rated: props.rated,
             onstart: props.onstart,
             onclick: props.onclick,
-            ongameover: props.ongameover,
+            onresign: props.onresign,
+            onabort: props.onabort,
+            onrematch: props.onrematch,
             status: Status::BeingCreated,
+            review: Vec::new(),
+            review_ply: 0,
         }
     }
 
-    fn update(&mut self, msg: Self::Message, _: &mut Env<Context, Self>) -> ShouldRender {
+    fn update(&mut self, msg: Self::Message, env: &mut Env<Context, Self>) -> ShouldRender {
         match msg {
             Msg::AttachEvent => {
-                let canvas = {
-                    Canvas::new("#game", &self.store)
-                };
-                self.canvas = Some(canvas);
+                // the resumed game is painted once the canvas is in the page
+                self.attach();
                 self.paint();
-                if let Some(ref onstart) = self.onstart {
-                    onstart.emit(());
+                self.resuming = false;
+                self.status = if self.opponent.is_some() {
+                    Status::Playing
+                } else {
+                    Status::WaitingOpponent
+                };
+                if self.shows_evaluation() {
+                    self.store.evaluate();
                 }
             }
             Msg::Clicked(ref event) => {
@@ -480,35 +1070,138 @@ impl Component<Context> for Board {
                 if let Ok(_) = self.store.play(x, y) {
                     let context = self.canvas_context();
                     self.store.paint(&context);
+                    if self.shows_evaluation() {
+                        self.store.evaluate();
+                    }
                     if let Some(ref onclick) = self.onclick {
                         onclick.emit((x, y));
                     }
                     if self.store.game_over {
-                        if let Some(ref ongameover) = self.ongameover {
-                            ongameover.emit((x, y));
-                        }
+                        self.collect_puzzles(env);
                     }
                 }
             }
+            Msg::Hint => {
+                if !self.hints
+                    || self.rated
+                    || self.store.current_player != self.store.local_player
+                {
+                    return false;
+                }
+                self.store.hint();
+                self.paint();
+            }
+            Msg::Review => {
+                if !self.store.game_over {
+                    return false;
+                }
+                if self.review.is_empty() {
+                    self.review = review::review_game(&self.store.moves, review::REVIEW_DEPTH);
+                }
+                if self.review.is_empty() {
+                    return false;
+                }
+                self.review_ply = 0;
+                self.status = Status::Reviewing;
+                self.paint_review();
+            }
+            Msg::ReviewMove(step) => {
+                let ply = self.review_ply as isize + step;
+                if ply < 0 || ply as usize >= self.review.len() {
+                    return false;
+                }
+                self.review_ply = ply as usize;
+                self.paint_review();
+            }
+            Msg::CloseReview => {
+                self.status = Status::Playing;
+                self.paint();
+            }
             Msg::RespawnBoard => {
                 self.store = Store::new(self.cell_width);
-                let canvas = Canvas::new("#game", &self.store);
+                let canvas = Canvas::new("#game", self.cell_width);
                 self.canvas = Some(canvas);
                 self.opponent = None;
                 self.status = Status::BeingCreated;
+                self.review = Vec::new();
                 if let Some(ref onstart) = self.onstart {
                     onstart.emit(());
                 }
                 let context = self.canvas_context();
                 self.store.paint(&context);
             }
+            Msg::Resign => {
+                if let Some(ref onresign) = self.onresign {
+                    onresign.emit(());
+                }
+                return false;
+            }
+            Msg::Abort => {
+                if let Some(ref onabort) = self.onabort {
+                    onabort.emit(());
+                }
+                return false;
+            }
+            Msg::Rematch(accepted) => {
+                if let Some(ref onrematch) = self.onrematch {
+                    onrematch.emit(accepted);
+                }
+                return false;
+            }
         }
         true
     }
 
-    fn change(&mut self, props: Self::Properties, _: &mut Env<Context, Self>) -> ShouldRender {
+    fn change(&mut self, props: Self::Properties, env: &mut Env<Context, Self>) -> ShouldRender {
         self.opponent = props.opponent;
+        self.rating = props.rating;
+        self.opponent_rating = props.opponent_rating;
         self.nickname = props.nickname;
+        self.hints = props.hints;
+        self.evaluation = props.evaluation;
+        self.rated = props.rated;
+        self.opponent_away = props.opponent_away;
+        self.spectators = props.spectators;
+        self.clocks = props.clocks;
+        self.rematch = props.rematch;
+        self.onresign = props.onresign;
+        self.onabort = props.onabort;
+        self.onrematch = props.onrematch;
+
+        if self.resuming {
+            // nothing to paint until the canvas is attached
+            if let Some((x, y)) = props.opponent_move {
+                let _ = self.store.play(x, y);
+            }
+            if let Some(ref result) = props.game_result {
+                self.store.finish(result);
+            }
+            return true;
+        }
+
+        if !props.board_id.is_empty() && props.board_id != self.board_id {
+            // a new board has been joined from the lobby
+            self.board_id = props.board_id.clone();
+            self.store = Store::new(self.cell_width);
+            self.status = Status::BeingCreated;
+            self.review = Vec::new();
+            self.attach();
+        } else if props.color.is_none() && self.status == Status::WaitingOpponent {
+            // the board has been cancelled before an opponent joined it
+            self.board_id = "".to_string();
+            self.store = Store::new(self.cell_width);
+            self.status = Status::BeingCreated;
+            self.paint();
+        }
+
+        if self.shows_evaluation() && self.store.evaluation.is_none() {
+            self.store.evaluate();
+        }
+
+        if self.status == Status::Reviewing {
+            // the game is over, keep the reviewed position on the canvas
+            return true;
+        }
 
         if let Some(color) = props.color {
             self.status = Status::WaitingOpponent;
@@ -520,18 +1213,39 @@ impl Component<Context> for Board {
                     self.store.local_player = Cell::Black;
                 }
             }
-            let context = self.canvas_context();
-            self.store.paint(&context);
+            self.paint();
         }
 
         if self.opponent.is_some() {
             self.status = Status::Playing;
         }
 
+        if let Some(ref state) = props.board_state {
+            self.store.resync(state);
+            self.paint();
+            if self.shows_evaluation() {
+                self.store.evaluate();
+            }
+        }
+
         if let Some((x, y)) = props.opponent_move {
             if let Ok(_) = self.store.play(x, y) {
-                let context = self.canvas_context();
-                self.store.paint(&context);
+                self.paint();
+                if self.shows_evaluation() {
+                    self.store.evaluate();
+                }
+                if self.store.game_over {
+                    self.collect_puzzles(env);
+                }
+            }
+        }
+
+        if let Some(ref result) = props.game_result {
+            let finished = self.store.game_over;
+            self.store.finish(result);
+            self.paint();
+            if !finished {
+                self.collect_puzzles(env);
             }
         }
         true
@@ -547,7 +1261,9 @@ impl Renderable<Context, Board> for Board {
                     id="game",
                     onclick=|event|Msg::Clicked(event),
                     ></canvas>
+                { self.view_hint_button() }
                 { self.view_player_score() }
+                { self.view_spectators() }
             </div>
         }
     }
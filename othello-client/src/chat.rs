@@ -0,0 +1,170 @@
+use yew::prelude::*;
+
+use super::context::Context;
+
+use wscommand::WsChatMessage;
+
+/// The longest message accepted by the server
+const MAX_MESSAGE_LEN: usize = 300;
+
+/// The messages of the board or of the lobby, and a field to talk
+pub struct Chat {
+    messages: Vec<WsChatMessage>,
+    error: Option<String>,
+    /// the user plays against an opponent who can be muted
+    can_mute: bool,
+    muted: bool,
+    /// the message being typed
+    text: String,
+    onsend: Option<Callback<String>>,
+    onmute: Option<Callback<bool>>,
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Props {
+    /// the oldest first
+    pub messages: Vec<WsChatMessage>,
+    /// why the server refused the last message
+    pub error: Option<String>,
+    pub can_mute: bool,
+    pub muted: bool,
+    pub onsend: Option<Callback<String>>,
+    pub onmute: Option<Callback<bool>>,
+}
+
+impl Default for Props {
+    fn default() -> Self {
+        Props {
+            messages: Vec::new(),
+            error: None,
+            can_mute: false,
+            muted: false,
+            onsend: None,
+            onmute: None,
+        }
+    }
+}
+
+pub enum Msg {
+    GotText(String),
+    Send,
+    ToggleMute,
+    Ignore,
+}
+
+impl Chat {
+    fn view_message(&self, message: &WsChatMessage) -> Html<Context, Self> {
+        html!{
+            <li>
+                <span class="author",>{ format!("{}: ", message.author) }</span>
+                { &message.text }
+            </li>
+        }
+    }
+
+    fn view_mute(&self) -> Html<Context, Self> {
+        if self.can_mute {
+            html!{
+                <label>
+                    <input type="checkbox",
+                        checked=self.muted,
+                        onclick=|_| Msg::ToggleMute,
+                        />
+                    { "Mute the opponent" }
+                </label>
+            }
+        } else {
+            html!{
+                <>
+                </>
+            }
+        }
+    }
+
+    fn view_error(&self) -> Html<Context, Self> {
+        match self.error {
+            Some(ref reason) => html!{
+                <p class="error",>{ reason }</p>
+            },
+            None => html!{
+                <>
+                </>
+            },
+        }
+    }
+}
+
+impl Component<Context> for Chat {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _: &mut Env<Context, Self>) -> Self {
+        Chat {
+            messages: props.messages,
+            error: props.error,
+            can_mute: props.can_mute,
+            muted: props.muted,
+            text: "".to_string(),
+            onsend: props.onsend,
+            onmute: props.onmute,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message, _: &mut Env<Context, Self>) -> ShouldRender {
+        match msg {
+            Msg::GotText(text) => {
+                self.text = text;
+            }
+            Msg::Send => {
+                let text = self.text.trim().to_string();
+                if text.is_empty() || text.chars().count() > MAX_MESSAGE_LEN {
+                    return false;
+                }
+                if let Some(ref onsend) = self.onsend {
+                    onsend.emit(text);
+                }
+                self.text = "".to_string();
+            }
+            Msg::ToggleMute => {
+                if let Some(ref onmute) = self.onmute {
+                    onmute.emit(!self.muted);
+                }
+                return false;
+            }
+            Msg::Ignore => return false,
+        }
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties, _: &mut Env<Context, Self>) -> ShouldRender {
+        self.messages = props.messages;
+        self.error = props.error;
+        self.can_mute = props.can_mute;
+        self.muted = props.muted;
+        self.onsend = props.onsend;
+        self.onmute = props.onmute;
+        true
+    }
+}
+
+impl Renderable<Context, Chat> for Chat {
+    fn view(&self) -> Html<Context, Self> {
+        html!{
+            <div class="chat",>
+                <ul>
+                    { for self.messages.iter().map(|message| self.view_message(message)) }
+                </ul>
+                <input class="edit",
+                    type="text",
+                    placeholder="Say something",
+                    value=&self.text,
+                    oninput=|e| Msg::GotText(e.value),
+                    onkeypress=|e| if e.key() == "Enter" { Msg::Send } else { Msg::Ignore },
+                    />
+                <button onclick=|_| Msg::Send,>{ "Send" }</button>
+                { self.view_mute() }
+                { self.view_error() }
+            </div>
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! The clocks of a game, kept by the server and counting down locally
+//! between its messages.
+
+use wscommand::{Color, WsClocks};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalClocks {
+    clocks: WsClocks,
+    /// when the clocks have been received, in ms
+    received_at: f64,
+}
+
+impl LocalClocks {
+    pub fn new(clocks: WsClocks, received_at: f64) -> Self {
+        LocalClocks {
+            clocks,
+            received_at,
+        }
+    }
+
+    pub fn running(&self) -> Option<&Color> {
+        self.clocks.running.as_ref()
+    }
+
+    /// The milliseconds left to the player at `now`
+    pub fn remaining(&self, color: &Color, now: f64) -> u64 {
+        let left = match *color {
+            Color::Black => self.clocks.black,
+            Color::White => self.clocks.white,
+        };
+        if self.running() == Some(color) {
+            let elapsed = (now - self.received_at).max(0.) as u64;
+            left.saturating_sub(elapsed)
+        } else {
+            left
+        }
+    }
+
+    /// The clocks as they are at `now`, not running anymore
+    pub fn stop(&self, now: f64) -> LocalClocks {
+        LocalClocks {
+            clocks: WsClocks {
+                black: self.remaining(&Color::Black, now),
+                white: self.remaining(&Color::White, now),
+                running: None,
+            },
+            received_at: now,
+        }
+    }
+}
+
+/// `m:ss`, with the tenths of a second under ten seconds
+pub fn format_clock(ms: u64) -> String {
+    let secs = ms / 1000;
+    if secs < 10 {
+        format!("0:{:02}.{}", secs, ms % 1000 / 100)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clocks(running: Option<Color>) -> LocalClocks {
+        LocalClocks::new(
+            WsClocks {
+                black: 60_000,
+                white: 30_000,
+                running,
+            },
+            1000.,
+        )
+    }
+
+    #[test]
+    fn test_remaining() {
+        let clocks = clocks(Some(Color::White));
+        assert_eq!(clocks.remaining(&Color::White, 1000.), 30_000);
+        assert_eq!(clocks.remaining(&Color::White, 6000.), 25_000);
+        assert_eq!(clocks.remaining(&Color::White, 100_000.), 0);
+        assert_eq!(clocks.remaining(&Color::Black, 6000.), 60_000);
+
+        let stopped = clocks.stop(6000.);
+        assert_eq!(stopped.running(), None);
+        assert_eq!(stopped.remaining(&Color::White, 20_000.), 25_000);
+    }
+
+    #[test]
+    fn test_format_clock() {
+        assert_eq!(format_clock(0), "0:00.0");
+        assert_eq!(format_clock(9_470), "0:09.4");
+        assert_eq!(format_clock(10_000), "0:10");
+        assert_eq!(format_clock(185_999), "3:05");
+        assert_eq!(format_clock(3_600_000), "60:00");
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use stdweb::web::Date;
+use yew::prelude::*;
+use yew::services::interval::{IntervalService, IntervalTask};
+
+use super::context::Context;
+
+use clock::{self, LocalClocks};
+use wscommand::Color;
+
+/// How often a running clock is drawn again
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The clock of a player, counting down while it runs
+pub struct ClockView {
+    clocks: Option<LocalClocks>,
+    color: Color,
+    _tick: IntervalTask,
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Props {
+    /// `None` for a game without clock
+    pub clocks: Option<LocalClocks>,
+    pub color: Color,
+}
+
+impl Default for Props {
+    fn default() -> Self {
+        Props {
+            clocks: None,
+            color: Color::Black,
+        }
+    }
+}
+
+pub enum Msg {
+    Tick,
+}
+
+impl Component<Context> for ClockView {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, env: &mut Env<Context, Self>) -> Self {
+        let callback = env.send_back(|_| Msg::Tick);
+        let interval: &mut IntervalService = env.as_mut();
+        ClockView {
+            clocks: props.clocks,
+            color: props.color,
+            _tick: interval.spawn(TICK_INTERVAL, callback),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message, _: &mut Env<Context, Self>) -> ShouldRender {
+        match msg {
+            Msg::Tick => match self.clocks {
+                Some(ref clocks) => clocks.running() == Some(&self.color),
+                None => false,
+            },
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties, _: &mut Env<Context, Self>) -> ShouldRender {
+        self.clocks = props.clocks;
+        self.color = props.color;
+        true
+    }
+}
+
+impl Renderable<Context, ClockView> for ClockView {
+    fn view(&self) -> Html<Context, Self> {
+        match self.clocks {
+            Some(ref clocks) => {
+                let running = clocks.running() == Some(&self.color);
+                let remaining = clocks.remaining(&self.color, Date::now());
+                html!{
+                    <span class=if running { "clock running" } else { "clock" },>
+                        { clock::format_clock(remaining) }
+                    </span>
+                }
+            }
+            None => html!{
+                <>
+                </>
+            },
+        }
+    }
+}
@@ -1,9 +1,17 @@
 use yew::services::websocket::WebSocketService;
 use yew::services::interval::IntervalService;
+use yew::services::storage::{Area, StorageService};
 
 pub struct Context {
     ws: WebSocketService,
     interval: IntervalService,
+    storage: StorageService,
+}
+
+impl AsMut<StorageService> for Context {
+    fn as_mut(&mut self) -> &mut StorageService {
+        &mut self.storage
+    }
 }
 
 impl AsMut<IntervalService> for Context {
@@ -23,6 +31,7 @@ impl Context {
         Context {
             interval: IntervalService::new(),
             ws: WebSocketService::new(),
+            storage: StorageService::new(Area::Local),
         }
     }
 }
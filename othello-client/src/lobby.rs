@@ -0,0 +1,280 @@
+use yew::prelude::*;
+
+use super::context::Context;
+
+use wscommand::{ClockMode, Color, ColorPreference, TimeControl, WsBoardSettings, WsOpenBoard};
+
+/// The time controls offered when creating a board, `(minutes, increment)`
+const TIME_CONTROLS: &[(u32, u32)] = &[(1, 0), (3, 2), (5, 0), (10, 5)];
+
+fn time_control_label(time_control: &Option<TimeControl>) -> String {
+    match *time_control {
+        Some(ref tc) => match tc.mode {
+            ClockMode::Fischer => format!("{}+{}", tc.minutes, tc.increment),
+            ClockMode::Bronstein => format!("{}d{}", tc.minutes, tc.increment),
+        },
+        None => "no clock".to_string(),
+    }
+}
+
+/// Create a board with some settings, or join one waiting for an opponent
+pub struct Lobby {
+    boards: Vec<WsOpenBoard>,
+    color: ColorPreference,
+    time_control: Option<TimeControl>,
+    /// how the increment of the time controls is given
+    clock_mode: ClockMode,
+    rated: bool,
+    private: bool,
+    /// the invite code typed by the user
+    invite_code: String,
+    onquickjoin: Option<Callback<Option<TimeControl>>>,
+    oncreate: Option<Callback<WsBoardSettings>>,
+    onjoin: Option<Callback<String>>,
+    oninvite: Option<Callback<String>>,
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Props {
+    /// the boards waiting for an opponent, the oldest first
+    pub boards: Vec<WsOpenBoard>,
+    pub onquickjoin: Option<Callback<Option<TimeControl>>>,
+    pub oncreate: Option<Callback<WsBoardSettings>>,
+    pub onjoin: Option<Callback<String>>,
+    /// join a private board with its code
+    pub oninvite: Option<Callback<String>>,
+}
+
+impl Default for Props {
+    fn default() -> Self {
+        Props {
+            boards: Vec::new(),
+            onquickjoin: None,
+            oncreate: None,
+            onjoin: None,
+            oninvite: None,
+        }
+    }
+}
+
+pub enum Msg {
+    QuickJoin,
+    SetColor(ColorPreference),
+    SetTimeControl(Option<TimeControl>),
+    SetClockMode(ClockMode),
+    ToggleRated,
+    TogglePrivate,
+    Create,
+    Join(String),
+    GotInviteCode(String),
+    JoinInvite,
+}
+
+impl Lobby {
+    fn view_color(&self, color: ColorPreference, label: &str) -> Html<Context, Self> {
+        html!{
+            <label>
+                <input type="radio",
+                    name="color",
+                    checked=self.color == color,
+                    onclick=|_| Msg::SetColor(color),
+                    />
+                { label }
+            </label>
+        }
+    }
+
+    fn view_time_control(&self, time_control: Option<TimeControl>) -> Html<Context, Self> {
+        html!{
+            <label>
+                <input type="radio",
+                    name="time_control",
+                    checked=self.time_control == time_control,
+                    onclick=|_| Msg::SetTimeControl(time_control),
+                    />
+                { time_control_label(&time_control) }
+            </label>
+        }
+    }
+
+    fn view_clock_mode(&self, mode: ClockMode, label: &str) -> Html<Context, Self> {
+        html!{
+            <label>
+                <input type="radio",
+                    name="clock_mode",
+                    checked=self.clock_mode == mode,
+                    onclick=|_| Msg::SetClockMode(mode),
+                    />
+                { label }
+            </label>
+        }
+    }
+
+    fn view_open_board(&self, open: &WsOpenBoard) -> Html<Context, Self> {
+        let board_id = open.board_id.clone();
+        let color = match open.color {
+            Color::Black => "black",
+            Color::White => "white",
+        };
+        html!{
+            <li>
+                { format!("{}: you play {}, {}, {} ",
+                          open.creator,
+                          color,
+                          time_control_label(&open.settings.time_control),
+                          if open.settings.rated { "rated" } else { "casual" }) }
+                <button onclick=|_| Msg::Join(board_id.clone()),>{ "Join" }</button>
+            </li>
+        }
+    }
+}
+
+impl Component<Context> for Lobby {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _: &mut Env<Context, Self>) -> Self {
+        Lobby {
+            boards: props.boards,
+            color: ColorPreference::Random,
+            time_control: None,
+            clock_mode: ClockMode::Fischer,
+            rated: false,
+            private: false,
+            invite_code: "".to_string(),
+            onquickjoin: props.onquickjoin,
+            oncreate: props.oncreate,
+            onjoin: props.onjoin,
+            oninvite: props.oninvite,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message, _: &mut Env<Context, Self>) -> ShouldRender {
+        match msg {
+            Msg::QuickJoin => {
+                if let Some(ref onquickjoin) = self.onquickjoin {
+                    onquickjoin.emit(self.time_control);
+                }
+                return false;
+            }
+            Msg::SetColor(color) => {
+                self.color = color;
+            }
+            Msg::SetTimeControl(time_control) => {
+                self.time_control = time_control;
+            }
+            Msg::SetClockMode(mode) => {
+                self.clock_mode = mode;
+                if let Some(ref mut time_control) = self.time_control {
+                    time_control.mode = mode;
+                }
+            }
+            Msg::ToggleRated => {
+                self.rated = !self.rated;
+            }
+            Msg::TogglePrivate => {
+                self.private = !self.private;
+            }
+            Msg::Create => {
+                let settings = WsBoardSettings {
+                    color: self.color,
+                    time_control: self.time_control,
+                    variant: "standard".to_string(),
+                    rated: self.rated,
+                    private: self.private,
+                };
+                if let Some(ref oncreate) = self.oncreate {
+                    oncreate.emit(settings);
+                }
+                return false;
+            }
+            Msg::Join(board_id) => {
+                if let Some(ref onjoin) = self.onjoin {
+                    onjoin.emit(board_id);
+                }
+                return false;
+            }
+            Msg::GotInviteCode(code) => {
+                self.invite_code = code;
+            }
+            Msg::JoinInvite => {
+                if self.invite_code.trim().is_empty() {
+                    return false;
+                }
+                if let Some(ref oninvite) = self.oninvite {
+                    oninvite.emit(self.invite_code.trim().to_string());
+                }
+            }
+        }
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties, _: &mut Env<Context, Self>) -> ShouldRender {
+        self.boards = props.boards;
+        self.onquickjoin = props.onquickjoin;
+        self.oncreate = props.oncreate;
+        self.onjoin = props.onjoin;
+        self.oninvite = props.oninvite;
+        true
+    }
+}
+
+impl Renderable<Context, Lobby> for Lobby {
+    fn view(&self) -> Html<Context, Self> {
+        html!{
+            <div class="lobby",>
+                <button onclick=|_| Msg::QuickJoin,>{ "Quick game" }</button>
+                <fieldset>
+                    <legend>{ "Create a board" }</legend>
+                    <div>
+                        { self.view_color(ColorPreference::Black, "Black") }
+                        { self.view_color(ColorPreference::White, "White") }
+                        { self.view_color(ColorPreference::Random, "Random") }
+                    </div>
+                    <div>
+                        { self.view_time_control(None) }
+                        { for TIME_CONTROLS.iter().map(|&(minutes, increment)| {
+                            self.view_time_control(Some(TimeControl {
+                                minutes,
+                                increment,
+                                mode: self.clock_mode,
+                            }))
+                        }) }
+                    </div>
+                    <div>
+                        { self.view_clock_mode(ClockMode::Fischer, "Increment") }
+                        { self.view_clock_mode(ClockMode::Bronstein, "Delay") }
+                    </div>
+                    <label>
+                        <input type="checkbox",
+                            checked=self.rated,
+                            onclick=|_| Msg::ToggleRated,
+                            />
+                        { "Rated" }
+                    </label>
+                    <label>
+                        <input type="checkbox",
+                            checked=self.private,
+                            onclick=|_| Msg::TogglePrivate,
+                            />
+                        { "Private, with an invite code" }
+                    </label>
+                    <button onclick=|_| Msg::Create,>{ "Create" }</button>
+                </fieldset>
+                <div>
+                    <input class="edit",
+                        type="text",
+                        placeholder="Invite code",
+                        value=&self.invite_code,
+                        oninput=|e| Msg::GotInviteCode(e.value),
+                        />
+                    <button onclick=|_| Msg::JoinInvite,>{ "Join with the code" }</button>
+                </div>
+                <p>{ if self.boards.is_empty() { "No board is waiting for an opponent" } else { "Open boards" } }</p>
+                <ul>
+                    { for self.boards.iter().map(|open| self.view_open_board(open)) }
+                </ul>
+            </div>
+        }
+    }
+}
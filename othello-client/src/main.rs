@@ -8,33 +8,66 @@ extern crate serde_derive;
 extern crate serde_json;
 
 extern crate failure;
+extern crate othello_engine as engine;
 
 extern crate stdweb;
 #[macro_use]
 extern crate yew;
 
+use std::time::Duration;
+
 use failure::Error;
 
-use stdweb::web::window;
+use stdweb::web::{window, Date};
 use yew::prelude::*;
 use yew::services::Task;
+use yew::services::interval::{IntervalService, IntervalTask};
+use yew::services::storage::StorageService;
 use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask};
-use yew::format::Json;
+use yew::format::{Json, Text};
 
 mod context;
 mod board;
-mod model;
+mod chat;
+mod clock;
+mod clock_view;
+mod lobby;
+mod puzzle;
+mod puzzle_board;
+mod review;
+mod watch_board;
 mod wscommand;
 
 use context::Context;
-use board::Board;
+use board::{Board, Rematch};
+use chat::Chat;
+use clock::LocalClocks;
+use lobby::Lobby;
+use puzzle_board::PuzzleBoard;
+use watch_board::WatchBoard;
+
+use wscommand::{Color, TimeControl, WsBoardSettings, WsBoardState, WsCancelBoard, WsChatMessage,
+                WsConnectingParam, WsCreateBoard, WsGameAction, WsGameResult, WsJoinBoard, WsLiveBoard,
+                WsMuteOpponent, WsOpenBoard, WsPlayBoard, WsRating, WsRequest, WsResponse, WsResume,
+                WsSendChat, WsWatchBoard, WsWatchedBoard};
+
+/// Where the token to resume the session after a reload is kept
+const RESUME_KEY: &str = "othello.resume";
+
+/// How often the client tells the server it is still there
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The connection is considered lost when the server is silent longer, in ms
+const SERVER_TIMEOUT: f64 = 15000.;
 
-use wscommand::{Color, WsConnectingParam, WsJoinBoard, WsPlayBoard, WsGameOver, WsRequest, WsResponse};
+/// The chat messages kept, the oldest ones are dropped
+const CHAT_LEN: usize = 100;
 
 pub enum WsAction {
     SendUser,
     Disconnect,
     Lost,
+    KeepAlive,
 }
 
 impl From<WsAction> for Msg {
@@ -50,7 +83,68 @@ struct Session {
     users_count: usize,
     nickname: String,
     opponent: Option<String>,
+    /// the ratings of the players, if the server rates them
+    rating: Option<WsRating>,
+    opponent_rating: Option<WsRating>,
     color: Option<Color>,
+    /// the opponent lost the connection and may come back
+    opponent_away: bool,
+    /// the moves of the game in progress when the session has been resumed
+    resumed_moves: Option<Vec<usize>>,
+    /// the server refused to join or to watch a board
+    join_error: Option<String>,
+    /// the boards waiting for an opponent, updated while not seated
+    open_boards: Vec<WsOpenBoard>,
+    /// the code to share, while waiting on a private board
+    invite_code: Option<String>,
+    /// the number of sessions watching the game of the user, or the watched one
+    spectators: usize,
+    /// the games in progress, while the user chooses one to watch
+    live_boards: Option<Vec<WsLiveBoard>>,
+    /// the game the user is watching
+    watched: Option<WsWatchedBoard>,
+    /// the player of the watched game who lost the connection
+    watched_away: Option<Color>,
+    /// the clock of the last quick game asked for, kept for the next one
+    quick_time_control: Option<TimeControl>,
+    /// the messages of the board or of the lobby
+    chat: Vec<WsChatMessage>,
+    /// the board of the messages, `None` for the lobby
+    chat_board: Option<String>,
+    /// why the server refused the last message
+    chat_error: Option<String>,
+    /// the messages of the opponent are not received
+    opponent_muted: bool,
+    /// the clocks of the game of the user, or of the watched one
+    clocks: Option<LocalClocks>,
+    /// the last game of the user, once it is over
+    finished_board: Option<String>,
+    rematch: Rematch,
+    /// the game of the user is rated
+    rated: bool,
+}
+
+impl Session {
+    fn is_watching(&self, board_id: &str) -> bool {
+        self.watched
+            .as_ref()
+            .map_or(false, |watched| watched.board_id == board_id)
+    }
+
+    /// Where the messages of the user go: the board where they play or the
+    /// one they watch, else the lobby
+    fn chat_scope(&self) -> Option<&str> {
+        if !self.board_id.is_empty() {
+            Some(self.board_id.as_str())
+        } else {
+            self.watched.as_ref().map(|watched| watched.board_id.as_str())
+        }
+    }
+
+    /// The user plays a rated game, the engine must not help them
+    fn in_rated_game(&self) -> bool {
+        self.rated && self.color.is_some() && self.finished_board.is_none()
+    }
 }
 
 /// User connection status
@@ -71,12 +165,41 @@ pub struct AppModel {
     /// Contains session information in case user is connected
     connected: ConnectionStatus,
 
-    /// Received by the websocket, send back to the board via a property
+    /// Received by the websocket, send back to the board via a property,
+    /// or to the watched board
     opponent_move: Option<(usize, usize)>,
 
+    /// Received when the server refused a move, replace the board of the game
+    board_state: Option<WsBoardState>,
+
+    /// Received when the server ends the game, send back to the board
+    game_result: Option<WsGameResult>,
+
     /// the web socket to communicate with the server
     ws: Option<WebSocketTask>,
 
+    /// send the keep alive messages while connected
+    keepalive: Option<IntervalTask>,
+
+    /// when the last message of the server has been received, in ms
+    last_seen: f64,
+
+    /// resume the previous session instead of creating a new one
+    resume_token: Option<String>,
+
+    /// the invite code of the page address, to join a private board once
+    /// connected
+    invite: Option<String>,
+
+    /// the player accept to get hints from the engine
+    hints: bool,
+
+    /// display the engine evaluation next to the score
+    evaluation: bool,
+
+    /// the player is solving puzzles instead of playing online
+    puzzles: bool,
+
     // inputs
     /// store the value of the nickname input
     nickname_input: String,
@@ -87,58 +210,92 @@ pub enum Msg {
     Connecting,
     Disconnecting,
     GotInput(String),
+    ToggleHints,
+    ToggleEvaluation,
+    TogglePuzzles,
     WsAction(WsAction),
     WsReady(Result<WsResponse, Error>),
 
+    /// Ask for a quick game with a clock, or without
+    QuickJoin(Option<TimeControl>),
+    /// Ask for a quick game with the clock of the previous one
     JoinBoard(()),
+    CreateBoard(WsBoardSettings),
+    JoinOpenBoard(String),
+    /// Join a private board with its invite code
+    JoinInvite(String),
+    /// Leave the board before an opponent joins it
+    CancelBoard(()),
     BoardCellClicked((usize, usize)),
-    BoardGameOver((usize, usize))
+    Resign(()),
+    /// End the game before both players have moved
+    Abort(()),
+    /// Offer or accept a rematch of the finished game, or decline it
+    Rematch(bool),
+
+    /// Show the games in progress, or refresh them
+    ListLiveBoards(()),
+    WatchBoard(String),
+    /// Back to the games in progress
+    StopWatching(()),
+    /// Back to the board of the user
+    CloseLiveBoards(()),
+
+    SendChat(String),
+    MuteOpponent(bool),
 }
 
 impl Component<Context> for AppModel {
     type Message = Msg;
     type Properties = ();
 
-    fn create(_: Self::Properties, _: &mut Env<Context, Self>) -> Self {
-        AppModel {
+    fn create(_: Self::Properties, env: &mut Env<Context, Self>) -> Self {
+        let resume_token = {
+            let storage: &mut StorageService = env.as_mut();
+            let token: Text = storage.restore(RESUME_KEY);
+            token.ok()
+        };
+        let mut app = AppModel {
             connected: ConnectionStatus::Disconnected,
             opponent_move: None,
+            board_state: None,
+            game_result: None,
             nickname_input: "".to_string(),
+            hints: true,
+            evaluation: false,
+            puzzles: false,
             ws: None,
+            keepalive: None,
+            last_seen: 0.,
+            resume_token,
+            invite: window()
+                .location()
+                .and_then(|location| location.search().ok())
+                .and_then(|search| invite_from_query(&search)),
+        };
+        if app.resume_token.is_some() {
+            // the page has been reloaded, try to get the game back
+            app.connect(env);
         }
+        app
     }
 
     fn update(&mut self, msg: Self::Message, env: &mut Env<Context, Self>) -> ShouldRender {
         self.opponent_move = None; // always reset the move
+        self.board_state = None;
+        self.game_result = None;
 
         match msg {
             Msg::Connecting => {
-                if self.nickname_input.len() == 0 {
+                if self.nickname_input.len() == 0 && self.resume_token.is_none() {
                     return false;
                 }
-                let callback = env.send_back(|Json(data)| Msg::WsReady(data));
-                let notification = env.send_back(|status| match status {
-                    WebSocketStatus::Opened => WsAction::SendUser.into(),
-                    WebSocketStatus::Closed | WebSocketStatus::Error => WsAction::Lost.into(),
-                });
-                let ws_service: &mut WebSocketService = env.as_mut();
-
-                let endpoint = {
-                    let location = window().location().unwrap();
-                    format!("{}://{}:{}/ws/",
-                        if location.protocol().unwrap() == "https:" { "wss" } else { "ws" },
-                        location.hostname().unwrap(),
-                        location.port().unwrap(),
-                    )
-                };
-                let task = ws_service.connect(endpoint.as_str(), callback, notification);
-                self.ws = Some(task);
-
-                self.connected = ConnectionStatus::Connecting(self.nickname_input.clone());
-                info!("connecting {}", self.nickname_input);
+                self.connect(env);
             }
             Msg::Disconnecting => {
                 self.connected = ConnectionStatus::Disconnected;
+                self.keepalive = None;
+                self.forget_session(env);
                 self.ws.take().unwrap().cancel();
                 info!("disconnected");
             }
@@ -147,20 +304,64 @@ impl Component<Context> for AppModel {
                 self.nickname_input = value;
             }
 
+            Msg::ToggleHints => {
+                if self.in_rated_game() {
+                    return false;
+                }
+                self.hints = !self.hints;
+            }
+
+            Msg::ToggleEvaluation => {
+                if self.in_rated_game() {
+                    return false;
+                }
+                self.evaluation = !self.evaluation;
+            }
+
+            Msg::TogglePuzzles => {
+                self.puzzles = !self.puzzles;
+            }
+
             Msg::WsAction(action) => match action {
                 WsAction::SendUser => {
-                    let payload = WsConnectingParam {
-                        nickname: self.nickname_input.as_str(),
+                    let command = match self.resume_token {
+                        Some(ref token) => WsRequest::Resume(WsResume {
+                            resume_token: token.as_str(),
+                        }),
+                        None => WsRequest::ConnectingParam(WsConnectingParam {
+                            nickname: self.nickname_input.as_str(),
+                        }),
                     };
-                    let command = WsRequest::ConnectingParam(payload);
                     self.ws.as_mut().unwrap().send(Json(&command));
+
+                    self.last_seen = Date::now();
+                    let callback = env.send_back(|_| WsAction::KeepAlive.into());
+                    let interval: &mut IntervalService = env.as_mut();
+                    self.keepalive = Some(interval.spawn(KEEPALIVE_INTERVAL, callback));
                 }
                 WsAction::Disconnect => {
                     self.connected = ConnectionStatus::Disconnected;
+                    self.keepalive = None;
                     self.ws.take().unwrap().cancel();
                 }
+                WsAction::KeepAlive => {
+                    if Date::now() - self.last_seen > SERVER_TIMEOUT {
+                        error!("No message from the server, the connection is lost");
+                        self.keepalive = None;
+                        if let Some(mut ws) = self.ws.take() {
+                            ws.cancel();
+                        }
+                        self.connected = ConnectionStatus::ConnectionError(
+                            "Connection lost with the server".to_string(),
+                        );
+                    } else if let Some(ref mut ws) = self.ws {
+                        ws.send(Json(&WsRequest::KeepAlive));
+                        return false;
+                    }
+                }
                 WsAction::Lost => {
                     self.ws = None;
+                    self.keepalive = None;
                     if self.connected != ConnectionStatus::Disconnected {
                         error!("Connection Closed from the server");
                         self.connected = ConnectionStatus::ConnectionError(
@@ -176,8 +377,12 @@ impl Component<Context> for AppModel {
                     return false;
                 }
                 let response = response.unwrap();
+                self.last_seen = Date::now();
                 info!("{:?}", response);
                 match response {
+                    WsResponse::KeepAlive => {
+                        return false;
+                    }
                     WsResponse::ConnectedParam(ref params) => {
                         let new_status =
                             if let ConnectionStatus::Connecting(ref nickname) = self.connected {
@@ -191,11 +396,148 @@ impl Component<Context> for AppModel {
                                     board_id: "".to_string(),
                                     color: None,
                                     opponent: None,
+                                    rating: None,
+                                    opponent_rating: None,
+                                    opponent_away: false,
+                                    resumed_moves: None,
+                                    join_error: None,
+                                    open_boards: Vec::new(),
+                                    invite_code: None,
+                                    spectators: 0,
+                                    live_boards: None,
+                                    watched: None,
+                                    watched_away: None,
+                                    quick_time_control: None,
+                                    chat: Vec::new(),
+                                    chat_board: None,
+                                    chat_error: None,
+                                    opponent_muted: false,
+                                    clocks: None,
+                                    finished_board: None,
+                                    rematch: Rematch::Declined,
+                                    rated: false,
                                 })
                             } else {
                                 ConnectionStatus::Disconnected
                             };
                         self.connected = new_status;
+                        self.ws
+                            .as_mut()
+                            .unwrap()
+                            .send(Json(&WsRequest::ListOpenBoards));
+                        self.join_invite();
+                        let token: Text = Ok(params.resume_token.clone());
+                        let storage: &mut StorageService = env.as_mut();
+                        storage.store(RESUME_KEY, token);
+                        self.resume_token = Some(params.resume_token.clone());
+                    }
+                    WsResponse::Resumed(ref params) => {
+                        if let ConnectionStatus::Connecting(_) = self.connected {
+                            info!("Session resumed");
+                            if let Some(ref nickname) = params.nickname {
+                                self.nickname_input = nickname.clone();
+                            }
+                            let board = params.board.clone();
+                            let joining = board.is_none();
+                            if joining {
+                                self.ws
+                                    .as_mut()
+                                    .unwrap()
+                                    .send(Json(&WsRequest::ListOpenBoards));
+                            }
+                            self.connected = ConnectionStatus::Connected(Session {
+                                session_id: params.session_id.clone(),
+                                users_count: params.users_count,
+                                nickname: self.nickname_input.clone(),
+                                board_id: board
+                                    .as_ref()
+                                    .map_or("".to_string(), |b| b.board_id.clone()),
+                                color: board.as_ref().map(|b| b.color.clone()),
+                                clocks: board
+                                    .as_ref()
+                                    .and_then(|b| b.clocks.clone())
+                                    .map(|clocks| LocalClocks::new(clocks, Date::now())),
+                                finished_board: None,
+                                rematch: Rematch::Declined,
+                                rated: board.as_ref().map_or(false, |b| b.rated),
+                                opponent: board.as_ref().and_then(|b| b.opponent.clone()),
+                                rating: None,
+                                opponent_rating: None,
+                                opponent_away: false,
+                                resumed_moves: board.map(|b| b.moves),
+                                join_error: None,
+                                open_boards: Vec::new(),
+                                invite_code: None,
+                                spectators: 0,
+                                live_boards: None,
+                                watched: None,
+                                watched_away: None,
+                                quick_time_control: None,
+                                chat: Vec::new(),
+                                chat_board: None,
+                                chat_error: None,
+                                opponent_muted: false,
+                            });
+                            if joining {
+                                self.join_invite();
+                            }
+                        }
+                    }
+                    WsResponse::ResumeFailed(ref params) => {
+                        info!("Cannot resume the session: {}", params.reason);
+                        self.forget_session(env);
+                        if self.nickname_input.len() > 0 {
+                            let command = WsRequest::ConnectingParam(WsConnectingParam {
+                                nickname: self.nickname_input.as_str(),
+                            });
+                            self.ws.as_mut().unwrap().send(Json(&command));
+                        } else {
+                            self.connected = ConnectionStatus::Disconnected;
+                            self.keepalive = None;
+                            self.ws.take().unwrap().cancel();
+                        }
+                    }
+                    WsResponse::LoginFailed(ref param) => {
+                        if let ConnectionStatus::Connecting(_) = self.connected {
+                            info!("Cannot connect: {}", param.reason);
+                            self.keepalive = None;
+                            self.ws.take().unwrap().cancel();
+                            self.connected =
+                                ConnectionStatus::ConnectionError(param.reason.clone());
+                        }
+                    }
+                    WsResponse::Error(ref param) => {
+                        error!("Request {:?} refused: {}", param.request, param.message);
+                        match self.connected {
+                            ConnectionStatus::Connecting(_) => {
+                                self.keepalive = None;
+                                self.ws.take().unwrap().cancel();
+                                self.connected =
+                                    ConnectionStatus::ConnectionError(param.message.clone());
+                            }
+                            ConnectionStatus::Connected(ref mut session) => {
+                                session.join_error = Some(param.message.clone());
+                            }
+                            _ => {}
+                        }
+                    }
+                    WsResponse::OpponentReconnecting(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.board_id == session.board_id {
+                                session.opponent_away = true;
+                            } else if session.is_watching(&param.board_id) {
+                                session.watched_away = Some(param.color.clone());
+                            }
+                        }
+                    }
+                    WsResponse::OpponentReconnected(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.board_id == session.board_id {
+                                session.opponent_away = false;
+                            } else if session.is_watching(&param.board_id) {
+                                session.watched_away = None;
+                            }
+                        }
                     }
                     WsResponse::JoinedBoard(ref param) => {
                         if let ConnectionStatus::Connected(ref mut session) = self.connected {
@@ -203,6 +545,14 @@ impl Component<Context> for AppModel {
                                 session.board_id = param.board_id.clone();
                                 session.color = Some(param.color.clone());
                                 session.opponent = param.opponent.clone();
+                                session.rating = param.rating;
+                                session.opponent_rating = param.opponent_rating;
+                                session.invite_code = param.invite_code.clone();
+                                session.spectators = 0;
+                                session.opponent_muted = false;
+                                session.clocks = None;
+                                session.finished_board = None;
+                                session.rated = param.rated;
                             } else {
                                 error!(
                                     "Session id does not match {} != {}",
@@ -211,11 +561,33 @@ impl Component<Context> for AppModel {
                             }
                         }
                     }
+                    WsResponse::BoardCancelled(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.board_id == session.board_id {
+                                session.board_id = "".to_string();
+                                session.color = None;
+                                session.invite_code = None;
+                                session.rated = false;
+                                self.ws
+                                    .as_mut()
+                                    .unwrap()
+                                    .send(Json(&WsRequest::ListOpenBoards));
+                            }
+                        }
+                    }
+                    WsResponse::JoinFailed(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.session_id == session.session_id {
+                                session.join_error = Some(param.reason.clone());
+                            }
+                        }
+                    }
                     WsResponse::OpponentJoinedBoard(ref param) => {
                         if let ConnectionStatus::Connected(ref mut session) = self.connected {
                             if param.session_id == session.session_id {
                                 if param.board_id == session.board_id {
                                     session.opponent = Some(param.opponent.clone());
+                                    session.opponent_rating = param.opponent_rating;
                                 } else {
                                     error!(
                                         "Board id does not match {} != {}",
@@ -231,38 +603,221 @@ impl Component<Context> for AppModel {
                         }
                     }
                     WsResponse::PlayedBoard(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            let mine = param.board_id == session.board_id
+                                && session.session_id == param.session_id;
+                            if mine || session.is_watching(&param.board_id) {
+                                self.opponent_move = Some(param.pos.clone());
+                                if let Some(ref clocks) = param.clocks {
+                                    session.clocks =
+                                        Some(LocalClocks::new(clocks.clone(), Date::now()));
+                                }
+                            }
+                        }
+                    }
+                    WsResponse::Clocks(ref param) => {
                         if let ConnectionStatus::Connected(ref mut session) = self.connected {
                             if param.board_id == session.board_id
                                 && session.session_id == param.session_id
                             {
-                                self.opponent_move = Some(param.pos.clone());
+                                session.clocks =
+                                    Some(LocalClocks::new(param.clocks.clone(), Date::now()));
                             }
                         }
                     }
-                    WsResponse::OpponentDisconnected(ref param) => {
+                    WsResponse::GameResult(ref param) => {
                         if let ConnectionStatus::Connected(ref mut session) = self.connected {
-                            if param.session_id == session.session_id {
+                            session.clocks = session.clocks.as_ref().map(|clocks| clocks.stop(Date::now()));
+                            if param.board_id == session.board_id
+                                && session.session_id == param.session_id
+                            {
+                                // the board is closed on the server, back to the lobby
                                 session.board_id = "".to_string();
-                                session.color = None;
-                                session.opponent = None;
+                                session.finished_board = Some(param.board_id.clone());
+                                session.rematch = Rematch::Available;
+                                self.game_result = Some(param.clone());
+                                self.ws
+                                    .as_mut()
+                                    .unwrap()
+                                    .send(Json(&WsRequest::ListOpenBoards));
+                            } else if session.is_watching(&param.board_id) {
+                                // the final position stays until the user stops watching
+                                self.game_result = Some(param.clone());
                             } else {
-                                error!("OpponentDisconnect reveived with an invalid session_id");
+                                error!("GameResult reveived for another board");
+                            }
+                        }
+                    }
+                    WsResponse::RejectedMove(ref param) => {
+                        if let ConnectionStatus::Connected(ref session) = self.connected {
+                            if param.board_id == session.board_id
+                                && session.session_id == param.session_id
+                            {
+                                error!("Move {:?} rejected: {}", param.pos, param.reason);
+                                self.board_state = param.board.clone();
+                            }
+                        }
+                    }
+                    WsResponse::OpenBoards(ref boards) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            session.open_boards = boards.clone();
+                        }
+                    }
+                    WsResponse::LiveBoards(ref boards) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if session.live_boards.is_some() {
+                                session.live_boards = Some(boards.clone());
+                            }
+                        }
+                    }
+                    WsResponse::WatchedBoard(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.session_id == session.session_id {
+                                session.spectators = param.spectators;
+                                session.watched_away = None;
+                                session.watched = Some(param.clone());
+                                session.clocks = param
+                                    .clocks
+                                    .clone()
+                                    .map(|clocks| LocalClocks::new(clocks, Date::now()));
+                            }
+                        }
+                    }
+                    WsResponse::WatchFailed(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.session_id == session.session_id {
+                                session.join_error = Some(param.reason.clone());
+                            }
+                        }
+                    }
+                    WsResponse::Spectators(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.board_id == session.board_id
+                                || session.is_watching(&param.board_id)
+                            {
+                                session.spectators = param.count;
+                            }
+                        }
+                    }
+                    WsResponse::Chat(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.board_id == session.chat_board {
+                                session.chat.push(param.message.clone());
+                                if session.chat.len() > CHAT_LEN {
+                                    let extra = session.chat.len() - CHAT_LEN;
+                                    session.chat.drain(..extra);
+                                }
+                            }
+                        }
+                    }
+                    WsResponse::ChatHistory(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.session_id == session.session_id {
+                                session.chat = param.messages.clone();
+                                session.chat_board = param.board_id.clone();
+                                session.chat_error = None;
+                            }
+                        }
+                    }
+                    WsResponse::ChatFailed(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.session_id == session.session_id {
+                                session.chat_error = Some(param.reason.clone());
+                            }
+                        }
+                    }
+                    WsResponse::RematchOffered(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if session.finished_board.as_ref() == Some(&param.board_id) {
+                                session.rematch = Rematch::Received;
+                            }
+                        }
+                    }
+                    WsResponse::RematchDeclined(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if session.finished_board.as_ref() == Some(&param.board_id) {
+                                session.rematch = Rematch::Declined;
+                            }
+                        }
+                    }
+                    WsResponse::GameActionFailed(ref param) => {
+                        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                            if param.session_id == session.session_id {
+                                session.join_error = Some(param.reason.clone());
+                                if session.finished_board.as_ref() == Some(&param.board_id) {
+                                    session.rematch = Rematch::Declined;
+                                }
                             }
                         }
                     }
                 }
             }
 
+            Msg::QuickJoin(time_control) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    session.quick_time_control = time_control;
+                }
+                return self.update(Msg::JoinBoard(()), env);
+            }
             Msg::JoinBoard(()) => {
                 info!("Join board");
-                if let ConnectionStatus::Connected(ref session) = self.connected {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    if session.resumed_moves.is_some() && session.board_id.len() > 0 {
+                        // back to the resumed board, not a new one
+                        return false;
+                    }
+                    session.resumed_moves = None;
+                    session.join_error = None;
+                    let payload = WsJoinBoard {
+                        session_id: session.session_id.as_str(),
+                        board_id: None,
+                        invite_code: None,
+                        time_control: session.quick_time_control,
+                    };
+                    let command = WsRequest::JoinBoard(payload);
+                    self.ws.as_mut().unwrap().send(Json(&command));
+                }
+            }
+            Msg::CreateBoard(settings) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    session.resumed_moves = None;
+                    session.join_error = None;
+                    let payload = WsCreateBoard {
+                        session_id: session.session_id.as_str(),
+                        settings: &settings,
+                    };
+                    let command = WsRequest::CreateBoard(payload);
+                    self.ws.as_mut().unwrap().send(Json(&command));
+                }
+            }
+            Msg::JoinOpenBoard(board_id) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    session.resumed_moves = None;
+                    session.join_error = None;
                     let payload = WsJoinBoard {
                         session_id: session.session_id.as_str(),
+                        board_id: Some(board_id.as_str()),
+                        invite_code: None,
+                        time_control: None,
                     };
                     let command = WsRequest::JoinBoard(payload);
                     self.ws.as_mut().unwrap().send(Json(&command));
                 }
             }
+            Msg::JoinInvite(code) => {
+                self.invite = Some(code);
+                self.join_invite();
+            }
+            Msg::CancelBoard(()) => {
+                if let ConnectionStatus::Connected(ref session) = self.connected {
+                    let payload = WsCancelBoard {
+                        session_id: session.session_id.as_str(),
+                        board_id: session.board_id.as_str(),
+                    };
+                    let command = WsRequest::CancelBoard(payload);
+                    self.ws.as_mut().unwrap().send(Json(&command));
+                }
+            }
             Msg::BoardCellClicked((x, y)) => {
                 info!("User play {} {}", x, y);
                 if let ConnectionStatus::Connected(ref session) = self.connected {
@@ -276,14 +831,109 @@ impl Component<Context> for AppModel {
                 }
             }
 
-            Msg::BoardGameOver((black_score, white_score)) => {
-                if let ConnectionStatus::Connected(ref session) = self.connected {
-                    let payload = WsGameOver {
+            Msg::Resign(()) | Msg::Abort(()) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    session.join_error = None;
+                    let payload = WsGameAction {
                         session_id: session.session_id.as_str(),
                         board_id: session.board_id.as_str(),
-                        score: (black_score, white_score),
                     };
-                    let command = WsRequest::GameOver(payload);
+                    let command = match msg {
+                        Msg::Abort(()) => WsRequest::Abort(payload),
+                        _ => WsRequest::Resign(payload),
+                    };
+                    self.ws.as_mut().unwrap().send(Json(&command));
+                }
+            }
+            Msg::Rematch(accepted) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    if let Some(ref board_id) = session.finished_board {
+                        session.join_error = None;
+                        let payload = WsGameAction {
+                            session_id: session.session_id.as_str(),
+                            board_id: board_id.as_str(),
+                        };
+                        let command = if accepted {
+                            WsRequest::OfferRematch(payload)
+                        } else {
+                            WsRequest::DeclineRematch(payload)
+                        };
+                        self.ws.as_mut().unwrap().send(Json(&command));
+                    }
+                    session.rematch = if accepted {
+                        Rematch::Offered
+                    } else {
+                        Rematch::Declined
+                    };
+                }
+            }
+
+            Msg::ListLiveBoards(()) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    let ws = self.ws.as_mut().unwrap();
+                    if session.live_boards.is_none() {
+                        session.live_boards = Some(Vec::new());
+                        // the finished game is not displayed anymore
+                        session.color = None;
+                        session.opponent = None;
+                        session.opponent_rating = None;
+                        session.clocks = None;
+                        ws.send(Json(&WsRequest::LeaveLobby));
+                    }
+                    session.join_error = None;
+                    ws.send(Json(&WsRequest::ListLiveBoards));
+                }
+            }
+            Msg::WatchBoard(board_id) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    session.join_error = None;
+                    let payload = WsWatchBoard {
+                        session_id: session.session_id.as_str(),
+                        board_id: board_id.as_str(),
+                    };
+                    let command = WsRequest::WatchBoard(payload);
+                    self.ws.as_mut().unwrap().send(Json(&command));
+                }
+            }
+            Msg::StopWatching(()) => {
+                self.stop_watching();
+                self.ws
+                    .as_mut()
+                    .unwrap()
+                    .send(Json(&WsRequest::ListLiveBoards));
+            }
+            Msg::CloseLiveBoards(()) => {
+                self.stop_watching();
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    session.live_boards = None;
+                    self.ws
+                        .as_mut()
+                        .unwrap()
+                        .send(Json(&WsRequest::ListOpenBoards));
+                }
+            }
+
+            Msg::SendChat(text) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    session.chat_error = None;
+                    let payload = WsSendChat {
+                        session_id: session.session_id.as_str(),
+                        board_id: session.chat_scope(),
+                        text: text.as_str(),
+                    };
+                    let command = WsRequest::SendChat(payload);
+                    self.ws.as_mut().unwrap().send(Json(&command));
+                }
+            }
+            Msg::MuteOpponent(muted) => {
+                if let ConnectionStatus::Connected(ref mut session) = self.connected {
+                    session.opponent_muted = muted;
+                    let payload = WsMuteOpponent {
+                        session_id: session.session_id.as_str(),
+                        board_id: session.board_id.as_str(),
+                        muted,
+                    };
+                    let command = WsRequest::MuteOpponent(payload);
                     self.ws.as_mut().unwrap().send(Json(&command));
                 }
             }
@@ -308,6 +958,81 @@ impl Renderable<Context, AppModel> for AppModel {
 }
 
 impl AppModel {
+    /// Open the websocket, the session is created or resumed once opened
+    fn connect(&mut self, env: &mut Env<Context, Self>) {
+        let callback = env.send_back(|Json(data)| Msg::WsReady(data));
+        let notification = env.send_back(|status| match status {
+            WebSocketStatus::Opened => WsAction::SendUser.into(),
+            WebSocketStatus::Closed | WebSocketStatus::Error => WsAction::Lost.into(),
+        });
+        let ws_service: &mut WebSocketService = env.as_mut();
+
+        let endpoint = {
+            let location = window().location().unwrap();
+            format!("{}://{}:{}/ws/",
+                if location.protocol().unwrap() == "https:" { "wss" } else { "ws" },
+                location.hostname().unwrap(),
+                location.port().unwrap(),
+            )
+        };
+        let task = ws_service.connect(endpoint.as_str(), callback, notification);
+        self.ws = Some(task);
+        self.puzzles = false;
+
+        self.connected = ConnectionStatus::Connecting(self.nickname_input.clone());
+        info!("connecting {}", self.nickname_input);
+    }
+
+    /// Join the private board of the invite code, if any
+    fn join_invite(&mut self) {
+        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+            if let Some(code) = self.invite.take() {
+                session.join_error = None;
+                let payload = WsJoinBoard {
+                    session_id: session.session_id.as_str(),
+                    board_id: None,
+                    invite_code: Some(code.as_str()),
+                    time_control: None,
+                };
+                let command = WsRequest::JoinBoard(payload);
+                self.ws.as_mut().unwrap().send(Json(&command));
+            }
+        }
+    }
+
+    /// Leave the watched game, if any
+    fn stop_watching(&mut self) {
+        if let ConnectionStatus::Connected(ref mut session) = self.connected {
+            if let Some(watched) = session.watched.take() {
+                let payload = WsWatchBoard {
+                    session_id: session.session_id.as_str(),
+                    board_id: watched.board_id.as_str(),
+                };
+                let command = WsRequest::StopWatching(payload);
+                self.ws.as_mut().unwrap().send(Json(&command));
+            }
+            session.spectators = 0;
+            session.watched_away = None;
+            session.clocks = None;
+        }
+    }
+
+    /// The user plays a rated game, the hints and the evaluation stay off
+    /// until it is over
+    fn in_rated_game(&self) -> bool {
+        match self.connected {
+            ConnectionStatus::Connected(ref session) => session.in_rated_game(),
+            _ => false,
+        }
+    }
+
+    /// The session cannot be resumed anymore
+    fn forget_session(&mut self, env: &mut Env<Context, Self>) {
+        self.resume_token = None;
+        let storage: &mut StorageService = env.as_mut();
+        storage.remove(RESUME_KEY);
+    }
+
     fn view_status_line(&self) -> Html<Context, Self> {
         match self.connected {
             ConnectionStatus::Connected(ref session) => {
@@ -316,9 +1041,27 @@ impl AppModel {
                         <span>
                             { format!("{} user(s) online", session.users_count) }
                         </span>
+                        <label>
+                            <input type="checkbox",
+                                checked=self.hints && !session.in_rated_game(),
+                                disabled=session.in_rated_game(),
+                                onclick=|_| Msg::ToggleHints,
+                                />
+                            { "Hints" }
+                        </label>
+                        <label>
+                            <input type="checkbox",
+                                checked=self.evaluation && !session.in_rated_game(),
+                                disabled=session.in_rated_game(),
+                                onclick=|_| Msg::ToggleEvaluation,
+                                />
+                            { "Evaluation" }
+                        </label>
+                        { self.view_watch_button(session) }
                         <button onclick=|_| Msg::Disconnecting.into(),>
                             { format!("Disconnect {}", session.nickname) }
                         </button>
+                        { self.view_join_error(session) }
                     </>
                 }
             }
@@ -338,6 +1081,7 @@ impl AppModel {
                         oninput=|e| Msg::GotInput(e.value),
                         />
                     <button onclick=|_| Msg::Connecting.into(),>{ "Connect" }</button>
+                    { self.view_puzzles_button() }
                     <p class="error",>{ message }</p>
                     </>
                 }
@@ -351,23 +1095,175 @@ impl AppModel {
                         oninput=|e| Msg::GotInput(e.value),
                         />
                     <button onclick=|_| Msg::Connecting.into(),>{ "Connect" }</button>
+                    { self.view_puzzles_button() }
                     </>
                 }
             }
         }
     }
 
+    fn view_join_error(&self, session: &Session) -> Html<Context, Self> {
+        match session.join_error {
+            Some(ref reason) => html!{
+                <p class="error",>{ reason }</p>
+            },
+            None => html!{
+                <>
+                </>
+            },
+        }
+    }
+
+    fn view_watch_button(&self, session: &Session) -> Html<Context, Self> {
+        if session.board_id.is_empty() && session.live_boards.is_none() {
+            html!{
+                <button onclick=|_| Msg::ListLiveBoards(()),>{ "Watch a game" }</button>
+            }
+        } else {
+            html!{
+                <>
+                </>
+            }
+        }
+    }
+
+    fn view_puzzles_button(&self) -> Html<Context, Self> {
+        html!{
+            <button onclick=|_| Msg::TogglePuzzles,>
+                { if self.puzzles { "Close the puzzles" } else { "Puzzles" } }
+            </button>
+        }
+    }
+
+    fn view_lobby(&self, session: &Session) -> Html<Context, Self> {
+        if session.board_id.is_empty() {
+            html!{
+                <Lobby: boards=&session.open_boards,
+                    onquickjoin=Msg::QuickJoin,
+                    oncreate=Msg::CreateBoard,
+                    onjoin=Msg::JoinOpenBoard,
+                    oninvite=Msg::JoinInvite, />
+            }
+        } else if session.opponent.is_none() && session.color.is_some() {
+            self.view_waiting(session)
+        } else {
+            html!{
+                <>
+                </>
+            }
+        }
+    }
+
+    /// The invite to share and the way out, while waiting for an opponent
+    fn view_waiting(&self, session: &Session) -> Html<Context, Self> {
+        let invite = match session.invite_code {
+            Some(ref code) => {
+                let url = window()
+                    .location()
+                    .map(|location| {
+                        format!(
+                            "{}{}?invite={}",
+                            location.origin().unwrap_or_default(),
+                            location.pathname().unwrap_or_default(),
+                            code
+                        )
+                    })
+                    .unwrap_or_default();
+                html!{
+                    <p>
+                        { format!("Invite code: {} ", code) }
+                        <a href=url.clone(),>{ &url }</a>
+                    </p>
+                }
+            }
+            None => html!{
+                <>
+                </>
+            },
+        };
+        html!{
+            <div>
+                { invite }
+                <button onclick=|_| Msg::CancelBoard(()),>{ "Cancel the board" }</button>
+            </div>
+        }
+    }
+
+    /// The chat of the board of the user, or of the lobby, not while
+    /// choosing a game to watch
+    fn view_chat(&self, session: &Session) -> Html<Context, Self> {
+        let scope = session.chat_scope();
+        let listing = session.live_boards.is_some() && scope.is_none();
+        if listing || session.chat_board.as_ref().map(|board_id| board_id.as_str()) != scope {
+            return html!{
+                <>
+                </>
+            };
+        }
+        html!{
+            <Chat: messages=&session.chat,
+                error=&session.chat_error,
+                can_mute=!session.board_id.is_empty() && session.opponent.is_some(),
+                muted=session.opponent_muted,
+                onsend=Msg::SendChat,
+                onmute=Msg::MuteOpponent, />
+        }
+    }
+
     fn view_board(&self) -> Html<Context, Self> {
         match self.connected {
+            ConnectionStatus::Connected(ref session) if session.live_boards.is_some() => {
+                html!{
+                    <>
+                    <WatchBoard: boards=session.live_boards.clone().unwrap_or_default(),
+                        watched=&session.watched,
+                        played_move=&self.opponent_move,
+                        game_result=&self.game_result,
+                        away=&session.watched_away,
+                        spectators=session.spectators,
+                        clocks=&session.clocks,
+                        evaluation=self.evaluation,
+                        onwatch=Msg::WatchBoard,
+                        onrefresh=Msg::ListLiveBoards,
+                        onstop=Msg::StopWatching,
+                        onclose=Msg::CloseLiveBoards, />
+                    { self.view_chat(session) }
+                    </>
+                }
+            }
             ConnectionStatus::Connected(ref session) => {
                 html!{
-                    <Board: nickname=&session.nickname,
+                    <>
+                    { self.view_lobby(session) }
+                    <Board: board_id=&session.board_id,
+                        nickname=&session.nickname,
                         opponent=&session.opponent,
+                        rating=&session.rating,
+                        opponent_rating=&session.opponent_rating,
                         color=&session.color,
                         opponent_move=&self.opponent_move,
+                        board_state=&self.board_state,
+                        game_result=&self.game_result,
+                        resumed_moves=&session.resumed_moves,
+                        opponent_away=session.opponent_away,
+                        spectators=session.spectators,
+                        clocks=&session.clocks,
+                        rematch=session.rematch,
+                        hints=self.hints,
+                        evaluation=self.evaluation,
+                        rated=session.rated,
                         onstart=Msg::JoinBoard,
-                        onclick=Msg::BoardCellClicked, 
-                        ongameover=Msg::BoardGameOver, />
+                        onclick=Msg::BoardCellClicked,
+                        onresign=Msg::Resign,
+                        onabort=Msg::Abort,
+                        onrematch=Msg::Rematch, />
+                    { self.view_chat(session) }
+                    </>
+                }
+            }
+            _ if self.puzzles => {
+                html!{
+                    <PuzzleBoard: />
                 }
             }
             _ => {
@@ -380,6 +1276,21 @@ impl AppModel {
     }
 }
 
+/// The `invite` parameter of the query string of the page address
+fn invite_from_query(search: &str) -> Option<String> {
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|param| {
+            let mut parts = param.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("invite"), Some(code)) if !code.is_empty() => Some(code.to_string()),
+                _ => None,
+            }
+        })
+        .next()
+}
+
 fn main() {
     web_logger::init();
     yew::initialize();
@@ -0,0 +1,176 @@
+//! Endgame puzzles extracted from finished games.
+//!
+//! A position makes a puzzle when exactly one move wins, or when the best
+//! move is far better than the second one. Positions are only looked at
+//! near the end of the game, where the endgame solver is exact.
+
+use engine;
+use engine::model::{BoardModel, Cell, BOARD_SIZE};
+use wscommand::Color;
+
+/// Positions with more empty squares are too expensive to solve
+pub const PUZZLE_MAX_EMPTIES: usize = 10;
+
+/// Minimum disc difference between the best and the second best move
+pub const PUZZLE_MIN_GAP: i32 = 10;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Puzzle {
+    /// the position string, see `BoardModel::from_string`
+    pub position: String,
+    /// the player to move
+    pub player: Color,
+    /// the moves that reach the best score
+    pub solution: Vec<usize>,
+    /// the exact final disc difference of the solution
+    pub score: i32,
+}
+
+impl Puzzle {
+    pub fn board(&self) -> BoardModel {
+        BoardModel::from_string(&self.position)
+    }
+
+    pub fn cell(&self) -> Cell {
+        match self.player {
+            Color::Black => Cell::Black,
+            Color::White => Cell::White,
+        }
+    }
+
+    /// The exact score of an answer, `None` if the move is illegal
+    pub fn answer_score(&self, pos: usize) -> Option<i32> {
+        engine::solve(&self.board(), self.cell())
+            .iter()
+            .find(|&&(p, _)| p == pos)
+            .map(|&(_, score)| score)
+    }
+
+    /// Whether an answer of this exact score solves the puzzle: any move
+    /// reaching the best score is accepted, not only the stored solution.
+    pub fn solves(&self, score: i32) -> bool {
+        score >= self.score
+    }
+}
+
+/// Replay a finished game and keep the positions that make a puzzle
+pub fn find_puzzles(moves: &[(Cell, usize)]) -> Vec<Puzzle> {
+    let mut board = BoardModel::new();
+    let mut puzzles = Vec::new();
+    for &(player, pos) in moves {
+        if engine::empties(&board) <= PUZZLE_MAX_EMPTIES {
+            if let Some(puzzle) = make_puzzle(&board, player) {
+                puzzles.push(puzzle);
+            }
+        }
+        let _ = board.set_cell(pos % BOARD_SIZE, pos / BOARD_SIZE, player);
+    }
+    puzzles
+}
+
+/// Solve the position and build a puzzle if it has a single good answer
+pub fn make_puzzle(board: &BoardModel, player: Cell) -> Option<Puzzle> {
+    let solved = engine::solve(board, player);
+    if solved.len() < 2 {
+        return None;
+    }
+    let best = solved[0].1;
+    let second = solved[1].1;
+    let single_win = best > 0 && second <= 0;
+    if !single_win && best - second < PUZZLE_MIN_GAP {
+        return None;
+    }
+    Some(Puzzle {
+        position: board.to_string(),
+        player: match player {
+            Cell::White => Color::White,
+            _ => Color::Black,
+        },
+        solution: solved
+            .iter()
+            .take_while(|&&(_, score)| score == best)
+            .map(|&(pos, _)| pos)
+            .collect(),
+        score: best,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_answer(puzzle: &Puzzle, pos: usize) -> bool {
+        puzzle.answer_score(pos).map_or(false, |score| puzzle.solves(score))
+    }
+
+    #[test]
+    fn test_make_puzzle_single_win() {
+        // Black only wins by taking a8
+        let board = BoardModel::from_string(
+            r#"
+            B W . W W W W W
+            B W W W W W W W
+            B W B W B W W W
+            W W W B W W W W
+            B W W B B W W W
+            . B W W W B W W
+            W . W B B B B W
+            . W B B B B B B
+            "#,
+        );
+        let puzzle = make_puzzle(&board, Cell::Black).unwrap();
+        assert_eq!(puzzle.player, Color::Black);
+        assert_eq!(puzzle.solution, vec![56]);
+        assert_eq!(puzzle.score, 2);
+        assert_eq!(puzzle.board().to_string(), board.to_string());
+        assert!(check_answer(&puzzle, 56));
+        assert!(!check_answer(&puzzle, 2));
+        assert!(!check_answer(&puzzle, 49));
+        assert!(!check_answer(&puzzle, 0));
+    }
+
+    #[test]
+    fn test_make_puzzle_large_gap() {
+        // both moves win, but e8 wins by far more discs
+        let board = BoardModel::from_string(
+            r#"
+            B W W W W B B B
+            B B B B B W B B
+            B B B W B B B B
+            B B W B B B B B
+            W W B B B B B B
+            W W B B W B W B
+            W . W W W W B B
+            W W W W . B B B
+            "#,
+        );
+        let puzzle = make_puzzle(&board, Cell::Black).unwrap();
+        assert_eq!(puzzle.solution, vec![60]);
+        assert_eq!(puzzle.score, 36);
+        assert!(check_answer(&puzzle, 60));
+        assert!(!check_answer(&puzzle, 49));
+    }
+
+    #[test]
+    fn test_make_puzzle_single_move() {
+        let board = BoardModel::from_string(
+            r#"
+            . W B W W W W W
+            W W W W W W W W
+            W W W W W W W W
+            W W W W W W W W
+            W W W W W W W W
+            W W W W W W W W
+            W W W W W W W W
+            W W W W W W W W
+            "#,
+        );
+        // a forced move is not a puzzle
+        assert_eq!(make_puzzle(&board, Cell::Black), None);
+    }
+
+    #[test]
+    fn test_find_puzzles_initial_position() {
+        assert_eq!(find_puzzles(&[(Cell::Black, 19)]), vec![]);
+    }
+}
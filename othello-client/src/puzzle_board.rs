@@ -0,0 +1,204 @@
+use yew::prelude::*;
+use yew::format::Json;
+use yew::services::storage::StorageService;
+
+use failure::Error;
+
+use stdweb::web::event::ClickEvent;
+
+use super::context::Context;
+
+use board::{self, BoardUI, Canvas, Marker};
+use engine::model::BOARD_SIZE;
+use puzzle::Puzzle;
+use review;
+
+/// Key of the puzzles in the browser local storage
+const PUZZLES_KEY: &str = "othello.puzzles";
+
+pub fn load_puzzles(storage: &mut StorageService) -> Vec<Puzzle> {
+    let Json(puzzles): Json<Result<Vec<Puzzle>, Error>> = storage.restore(PUZZLES_KEY);
+    puzzles.unwrap_or_else(|_| Vec::new())
+}
+
+pub fn save_puzzles(storage: &mut StorageService, puzzles: &[Puzzle]) {
+    storage.store(PUZZLES_KEY, Json(&puzzles));
+}
+
+#[derive(PartialEq)]
+enum Status {
+    NotStarted,
+    Solving,
+    /// the square played and its score
+    Solved(usize, i32),
+    Failed(usize, i32),
+}
+
+/// Serve the puzzles found in the finished games
+pub struct PuzzleBoard {
+    canvas: Option<Canvas>,
+    cell_width: u32,
+    puzzles: Vec<Puzzle>,
+    current: usize,
+    status: Status,
+}
+
+pub enum Msg {
+    /// Display the next puzzle
+    Next,
+    Clicked(ClickEvent),
+}
+
+impl PuzzleBoard {
+    fn paint(&self) {
+        if let Some(ref canvas) = self.canvas {
+            let puzzle = &self.puzzles[self.current];
+            let board = BoardUI::new(puzzle.board(), self.cell_width, 1);
+            let mut markers = vec![];
+            let player = match self.status {
+                Status::Solved(pos, score) => {
+                    markers.push(Marker {
+                        pos,
+                        score,
+                        color: board::GOOD_MOVE_COLOR,
+                    });
+                    ::engine::model::Cell::Empty
+                }
+                Status::Failed(pos, score) => {
+                    markers.push(Marker {
+                        pos: puzzle.solution[0],
+                        score: puzzle.score,
+                        color: board::GOOD_MOVE_COLOR,
+                    });
+                    markers.push(Marker {
+                        pos,
+                        score,
+                        color: board::BAD_MOVE_COLOR,
+                    });
+                    ::engine::model::Cell::Empty
+                }
+                _ => puzzle.cell(),
+            };
+            board.paint(player, &markers, &canvas.context());
+        }
+    }
+
+    fn view_status(&self) -> Html<Context, Self> {
+        if self.puzzles.is_empty() {
+            return html!{
+                <p>{ "No puzzle yet, they are found in the games you finish." }</p>
+            };
+        }
+        let puzzle = &self.puzzles[self.current];
+        let player = match puzzle.cell() {
+            ::engine::model::Cell::White => "White",
+            _ => "Black",
+        };
+        let status = match self.status {
+            Status::NotStarted => "".to_string(),
+            Status::Solving => {
+                if puzzle.score > 0 {
+                    format!("{} to play and win", player)
+                } else {
+                    format!("{} to play, find the best move", player)
+                }
+            }
+            Status::Solved(_, score) => format!("Solved! {:+} discs", score),
+            Status::Failed(pos, score) => format!(
+                "{} gives {:+}, the solution was {} ({:+})",
+                review::square_name(pos),
+                score,
+                review::square_name(puzzle.solution[0]),
+                puzzle.score
+            ),
+        };
+        html!{
+            <div>
+                <p>{ format!("Puzzle {}/{}", self.current + 1, self.puzzles.len()) }</p>
+                <p>{ status }</p>
+                <button
+                    onclick=|_|Msg::Next,
+                    >{ if self.status == Status::NotStarted { "Start" } else { "Next puzzle" } }
+                </button>
+            </div>
+        }
+    }
+}
+
+impl Component<Context> for PuzzleBoard {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_: Self::Properties, env: &mut Env<Context, Self>) -> Self {
+        let storage: &mut StorageService = env.as_mut();
+        PuzzleBoard {
+            canvas: None,
+            cell_width: board::cell_width(),
+            puzzles: load_puzzles(storage),
+            current: 0,
+            status: Status::NotStarted,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message, _: &mut Env<Context, Self>) -> ShouldRender {
+        if self.puzzles.is_empty() {
+            return false;
+        }
+        match msg {
+            Msg::Next => {
+                if self.canvas.is_none() {
+                    self.canvas = Some(Canvas::new("#puzzle", self.cell_width));
+                } else {
+                    self.current = (self.current + 1) % self.puzzles.len();
+                }
+                self.status = Status::Solving;
+                self.paint();
+            }
+            Msg::Clicked(ref event) => {
+                if self.status != Status::Solving {
+                    return false;
+                }
+                let x = (event.offset_x() / self.cell_width as f64) as usize;
+                let y = (event.offset_y() / self.cell_width as f64) as usize;
+                if x >= BOARD_SIZE || y >= BOARD_SIZE {
+                    return false;
+                }
+                let pos = x + y * BOARD_SIZE;
+                let puzzle = &self.puzzles[self.current];
+                // solved once, the exact score grades the answer
+                match puzzle.answer_score(pos) {
+                    Some(score) if puzzle.solves(score) => {
+                        self.status = Status::Solved(pos, score);
+                    }
+                    Some(score) => {
+                        self.status = Status::Failed(pos, score);
+                    }
+                    None => {
+                        info!("Illegal move in the puzzle");
+                        return false;
+                    }
+                }
+                self.paint();
+            }
+        }
+        true
+    }
+
+    fn change(&mut self, _: Self::Properties, _: &mut Env<Context, Self>) -> ShouldRender {
+        false
+    }
+}
+
+impl Renderable<Context, PuzzleBoard> for PuzzleBoard {
+    fn view(&self) -> Html<Context, Self> {
+        html! {
+            <div>
+                <canvas
+                    id="puzzle",
+                    onclick=|event|Msg::Clicked(event),
+                    ></canvas>
+                { self.view_status() }
+            </div>
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Post-game review: replay a finished game with the engine and point out
+//! the moves that lost discs.
+
+use engine;
+use engine::model::{BoardModel, Cell, BOARD_SIZE};
+
+/// Depth of the search used to review a game
+pub const REVIEW_DEPTH: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Annotation {
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Annotation {
+    /// Classify a move from the discs it lost compared to the best move
+    pub fn from_loss(loss: i32) -> Self {
+        if loss >= 16 {
+            Annotation::Blunder
+        } else if loss >= 8 {
+            Annotation::Mistake
+        } else if loss >= 4 {
+            Annotation::Inaccuracy
+        } else {
+            Annotation::Good
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Annotation::Good => "good",
+            Annotation::Inaccuracy => "inaccuracy",
+            Annotation::Mistake => "mistake",
+            Annotation::Blunder => "blunder",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MoveReview {
+    /// the player who played the move
+    pub player: Cell,
+    /// the played square
+    pub pos: usize,
+    /// the engine score of the played move
+    pub score: i32,
+    /// the best move according to the engine, with its score
+    pub best: (usize, i32),
+    /// discs lost compared to the best move
+    pub loss: i32,
+    pub annotation: Annotation,
+}
+
+/// Replay `moves` from the initial position and review every one of them.
+pub fn review_game(moves: &[(Cell, usize)], depth: usize) -> Vec<MoveReview> {
+    let mut board = BoardModel::new();
+    let mut reviews = Vec::with_capacity(moves.len());
+    for &(player, pos) in moves {
+        let ranked = engine::rank_moves(&board, player, depth);
+        if ranked.is_empty() {
+            error!("Cannot review an illegal move at {}", square_name(pos));
+            break;
+        }
+        let best = ranked[0];
+        let score = ranked
+            .iter()
+            .find(|&&(p, _)| p == pos)
+            .map(|&(_, s)| s)
+            .unwrap_or(best.1);
+        let loss = best.1 - score;
+        reviews.push(MoveReview {
+            player,
+            pos,
+            score,
+            best,
+            loss,
+            annotation: Annotation::from_loss(loss),
+        });
+        let _ = board.set_cell(pos % BOARD_SIZE, pos / BOARD_SIZE, player);
+    }
+    reviews
+}
+
+/// The position before the move number `ply` of the game
+pub fn position_before(moves: &[(Cell, usize)], ply: usize) -> BoardModel {
+    let mut board = BoardModel::new();
+    for &(player, pos) in moves.iter().take(ply) {
+        let _ = board.set_cell(pos % BOARD_SIZE, pos / BOARD_SIZE, player);
+    }
+    board
+}
+
+/// Name a square as on a real board, from "a1" to "h8"
+pub fn square_name(pos: usize) -> String {
+    format!(
+        "{}{}",
+        (b'a' + (pos % BOARD_SIZE) as u8) as char,
+        pos / BOARD_SIZE + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Play a full game, `pick` choose the move in the ranked list
+    fn play_game<F>(pick: F) -> Vec<(Cell, usize)>
+    where
+        F: Fn(Cell, &[(usize, i32)]) -> usize,
+    {
+        let mut board = BoardModel::new();
+        let mut player = Cell::Black;
+        let mut moves = Vec::new();
+        loop {
+            let ranked = engine::rank_moves(&board, player, REVIEW_DEPTH);
+            if ranked.is_empty() {
+                if board.get_possibilities(player.opposite()).is_empty() {
+                    break;
+                }
+                player = player.opposite();
+                continue;
+            }
+            let pos = pick(player, &ranked);
+            board.set_cell(pos % BOARD_SIZE, pos / BOARD_SIZE, player).unwrap();
+            moves.push((player, pos));
+            player = player.opposite();
+        }
+        moves
+    }
+
+    #[test]
+    fn test_annotation_from_loss() {
+        assert_eq!(Annotation::from_loss(0), Annotation::Good);
+        assert_eq!(Annotation::from_loss(3), Annotation::Good);
+        assert_eq!(Annotation::from_loss(4), Annotation::Inaccuracy);
+        assert_eq!(Annotation::from_loss(8), Annotation::Mistake);
+        assert_eq!(Annotation::from_loss(16), Annotation::Blunder);
+    }
+
+    #[test]
+    fn test_review_best_moves() {
+        let moves = play_game(|_, ranked| ranked[0].0);
+        let reviews = review_game(&moves, REVIEW_DEPTH);
+        assert_eq!(reviews.len(), moves.len());
+        for review in reviews {
+            assert_eq!(review.loss, 0);
+            assert_eq!(review.best.0, review.pos);
+            assert_eq!(review.annotation, Annotation::Good);
+        }
+    }
+
+    #[test]
+    fn test_review_worst_moves() {
+        let moves = play_game(|player, ranked| match player {
+            Cell::Black => ranked[ranked.len() - 1].0,
+            _ => ranked[0].0,
+        });
+        let reviews = review_game(&moves, REVIEW_DEPTH);
+        let black_loss: i32 = reviews
+            .iter()
+            .filter(|r| r.player == Cell::Black)
+            .map(|r| r.loss)
+            .sum();
+        assert!(black_loss > 0);
+        assert!(
+            reviews
+                .iter()
+                .any(|r| r.player == Cell::Black && r.annotation != Annotation::Good)
+        );
+        for review in reviews.iter().filter(|r| r.player == Cell::White) {
+            assert_eq!(review.loss, 0);
+        }
+    }
+
+    #[test]
+    fn test_position_before() {
+        let moves = vec![(Cell::Black, 20), (Cell::White, 19)];
+        assert_eq!(position_before(&moves, 0).score(), (2, 2));
+        assert_eq!(position_before(&moves, 1).score(), (4, 1));
+        assert_eq!(position_before(&moves, 2).score(), (3, 3));
+    }
+
+    #[test]
+    fn test_square_name() {
+        assert_eq!(square_name(0), "a1");
+        assert_eq!(square_name(19), "d3");
+        assert_eq!(square_name(63), "h8");
+    }
+}
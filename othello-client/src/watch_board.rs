@@ -0,0 +1,308 @@
+use yew::prelude::*;
+
+use super::context::Context;
+
+use board::{self, Canvas, Store};
+use clock::LocalClocks;
+use clock_view::ClockView;
+use engine;
+use engine::model::Cell;
+use wscommand::{Color, GameOverReason, WsGameResult, WsLiveBoard, WsWatchedBoard};
+
+/// Follow the game of other players, without playing
+pub struct WatchBoard {
+    canvas: Option<Canvas>,
+    cell_width: u32,
+    /// the watched game, `None` while choosing one
+    store: Option<Store>,
+    boards: Vec<WsLiveBoard>,
+    watched: Option<WsWatchedBoard>,
+    away: Option<Color>,
+    spectators: usize,
+    clocks: Option<LocalClocks>,
+    evaluation: bool,
+    onwatch: Option<Callback<String>>,
+    onrefresh: Option<Callback<()>>,
+    onstop: Option<Callback<()>>,
+    onclose: Option<Callback<()>>,
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Props {
+    /// the games in progress, to choose one
+    pub boards: Vec<WsLiveBoard>,
+    /// the position of the watched game when it has been joined
+    pub watched: Option<WsWatchedBoard>,
+    /// a move of the watched game
+    pub played_move: Option<(usize, usize)>,
+    /// the final result of the watched game
+    pub game_result: Option<WsGameResult>,
+    /// the player who lost the connection, if any
+    pub away: Option<Color>,
+    pub spectators: usize,
+    /// `None` for a game without clock
+    pub clocks: Option<LocalClocks>,
+    /// display the engine evaluation of the position
+    pub evaluation: bool,
+    pub onwatch: Option<Callback<String>>,
+    pub onrefresh: Option<Callback<()>>,
+    pub onstop: Option<Callback<()>>,
+    pub onclose: Option<Callback<()>>,
+}
+
+impl Default for Props {
+    fn default() -> Self {
+        Props {
+            boards: Vec::new(),
+            watched: None,
+            played_move: None,
+            game_result: None,
+            away: None,
+            spectators: 0,
+            clocks: None,
+            evaluation: false,
+            onwatch: None,
+            onrefresh: None,
+            onstop: None,
+            onclose: None,
+        }
+    }
+}
+
+pub enum Msg {
+    Watch(String),
+    /// Ask the server the games in progress again
+    Refresh,
+    /// Back to the list of the games
+    Stop,
+    Close,
+}
+
+impl WatchBoard {
+    fn paint(&self) {
+        if let (Some(ref canvas), Some(ref store)) = (&self.canvas, &self.store) {
+            store.paint(&canvas.context());
+        }
+    }
+
+    fn view_live_board(&self, live: &WsLiveBoard) -> Html<Context, Self> {
+        let board_id = live.board_id.clone();
+        html!{
+            <li>
+                { format!("{} ({}) - {} ({}), {} spectator(s) ",
+                          live.black, live.score.0, live.white, live.score.1, live.spectators) }
+                <button onclick=|_| Msg::Watch(board_id.clone()),>{ "Watch" }</button>
+            </li>
+        }
+    }
+
+    fn view_boards(&self) -> Html<Context, Self> {
+        if self.watched.is_some() {
+            return html!{
+                <>
+                </>
+            };
+        }
+        html!{
+            <div>
+                { if self.boards.is_empty() { "No game in progress" } else { "Games in progress" } }
+                <ul>
+                    { for self.boards.iter().map(|live| self.view_live_board(live)) }
+                </ul>
+                <button onclick=|_| Msg::Refresh,>{ "Refresh" }</button>
+                <button onclick=|_| Msg::Close,>{ "Close" }</button>
+            </div>
+        }
+    }
+
+    fn view_player(&self, color: Color, name: &str) -> String {
+        let cell = match color {
+            Color::Black => Cell::Black,
+            Color::White => Cell::White,
+        };
+        let store = self.store.as_ref().unwrap();
+        let mut line = format!("{:?}: {}", color, name);
+        if self.away == Some(color) && !store.is_over() {
+            line.push_str(" (reconnecting…)");
+        }
+        if store.current_player() == cell && !store.is_over() {
+            line.push_str(" ◀");
+        }
+        line
+    }
+
+    /// The engine assessment, from the black side
+    fn view_evaluation(&self, store: &Store) -> String {
+        match store.evaluation() {
+            Some(score) if self.evaluation => {
+                let leader = if score >= 0 { "Black" } else { "White" };
+                format!(
+                    "Evaluation: {} {:+} ({:.0}% for black)",
+                    leader,
+                    score.abs(),
+                    engine::win_probability(score) * 100.
+                )
+            }
+            _ => "".to_string(),
+        }
+    }
+
+    fn view_result(&self, result: &WsGameResult) -> String {
+        if result.reason == GameOverReason::Aborted {
+            return "The game has been aborted".to_string();
+        }
+        let watched = self.watched.as_ref().unwrap();
+        let name = match result.winner {
+            Some(Color::Black) => watched.black.as_str(),
+            Some(Color::White) => watched.white.as_str(),
+            None => return format!("Draw {} - {}", result.score.0, result.score.1),
+        };
+        let reason = match result.reason {
+            GameOverReason::Normal => "",
+            GameOverReason::Resignation => " by resignation",
+            GameOverReason::Timeout => " on time",
+            GameOverReason::Disconnect => ", the opponent left the game",
+            GameOverReason::Aborted => "",
+        };
+        format!("{} win{} {} - {}", name, reason, result.score.0, result.score.1)
+    }
+
+    fn view_game(&self) -> Html<Context, Self> {
+        let (watched, store) = match (&self.watched, &self.store) {
+            (&Some(ref watched), &Some(ref store)) => (watched, store),
+            _ => {
+                return html!{
+                    <>
+                    </>
+                }
+            }
+        };
+        let score = store.score();
+        let status = match store.result() {
+            Some(result) => self.view_result(result),
+            None => format!("Black {} - White {}", score.0, score.1),
+        };
+        html!{
+            <div>
+                <ul>
+                    <li>
+                        { self.view_player(Color::Black, &watched.black) }
+                        <ClockView: clocks=&self.clocks, color=Color::Black, />
+                    </li>
+                    <li>
+                        { self.view_player(Color::White, &watched.white) }
+                        <ClockView: clocks=&self.clocks, color=Color::White, />
+                    </li>
+                </ul>
+                <p>{ status }</p>
+                <p><small>{ self.view_evaluation(store) }</small></p>
+                <p>{ format!("{} spectator(s)", self.spectators) }</p>
+                <button onclick=|_| Msg::Stop,>{ "Stop watching" }</button>
+            </div>
+        }
+    }
+}
+
+impl Component<Context> for WatchBoard {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _: &mut Env<Context, Self>) -> Self {
+        WatchBoard {
+            canvas: None,
+            cell_width: board::cell_width(),
+            store: None,
+            boards: props.boards,
+            watched: None,
+            away: props.away,
+            spectators: props.spectators,
+            clocks: props.clocks,
+            evaluation: props.evaluation,
+            onwatch: props.onwatch,
+            onrefresh: props.onrefresh,
+            onstop: props.onstop,
+            onclose: props.onclose,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message, _: &mut Env<Context, Self>) -> ShouldRender {
+        match msg {
+            Msg::Watch(board_id) => {
+                if let Some(ref onwatch) = self.onwatch {
+                    onwatch.emit(board_id);
+                }
+            }
+            Msg::Refresh => {
+                if let Some(ref onrefresh) = self.onrefresh {
+                    onrefresh.emit(());
+                }
+            }
+            Msg::Stop => {
+                if let Some(ref onstop) = self.onstop {
+                    onstop.emit(());
+                }
+            }
+            Msg::Close => {
+                if let Some(ref onclose) = self.onclose {
+                    onclose.emit(());
+                }
+            }
+        }
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties, _: &mut Env<Context, Self>) -> ShouldRender {
+        let board_id = props.watched.as_ref().map(|w| w.board_id.clone());
+        if board_id != self.watched.as_ref().map(|w| w.board_id.clone()) {
+            // replay the game from the beginning, the canvas is in the page
+            self.store = props.watched.as_ref().map(|watched| {
+                let mut store = Store::new(self.cell_width);
+                store.restore(&watched.moves);
+                store
+            });
+            if self.store.is_some() && self.canvas.is_none() {
+                self.canvas = Some(Canvas::new("#watch", self.cell_width));
+            }
+        }
+        self.boards = props.boards;
+        self.watched = props.watched;
+        self.away = props.away;
+        self.spectators = props.spectators;
+        self.clocks = props.clocks;
+        self.evaluation = props.evaluation;
+        self.onwatch = props.onwatch;
+        self.onrefresh = props.onrefresh;
+        self.onstop = props.onstop;
+        self.onclose = props.onclose;
+
+        if let Some(ref mut store) = self.store {
+            let mut moved = false;
+            if let Some((x, y)) = props.played_move {
+                moved = store.play(x, y).is_ok();
+            }
+            if let Some(ref result) = props.game_result {
+                store.finish(result);
+            }
+            if self.evaluation && (moved || store.evaluation().is_none()) {
+                store.evaluate();
+            }
+        }
+        self.paint();
+        true
+    }
+}
+
+impl Renderable<Context, WatchBoard> for WatchBoard {
+    fn view(&self) -> Html<Context, Self> {
+        html! {
+            <div>
+                { self.view_boards() }
+                <canvas
+                    id="watch",
+                    style={ if self.store.is_some() { "" } else { "display: none" } },
+                    ></canvas>
+                { self.view_game() }
+            </div>
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 /// This type is an expected response from a websocket connection.
 #[derive(Serialize, Debug)]
 pub struct WsConnectingParam<'a> {
@@ -7,6 +9,57 @@ pub struct WsConnectingParam<'a> {
 #[derive(Serialize, Debug)]
 pub struct WsJoinBoard<'a> {
     pub session_id: &'a str,
+    /// `None` for a quick game
+    pub board_id: Option<&'a str>,
+    /// the code of a private board
+    pub invite_code: Option<&'a str>,
+    /// the clock of a quick game
+    pub time_control: Option<TimeControl>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ColorPreference {
+    Black,
+    White,
+    Random,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ClockMode {
+    /// the increment is added after each move
+    Fischer,
+    /// the time used is given back after each move, up to the increment
+    Bronstein,
+}
+
+fn fischer() -> ClockMode {
+    ClockMode::Fischer
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TimeControl {
+    pub minutes: u32,
+    /// seconds given back after each move
+    pub increment: u32,
+    #[serde(default = "fischer")]
+    pub mode: ClockMode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WsBoardSettings {
+    /// the color of the creator of the board
+    pub color: ColorPreference,
+    pub time_control: Option<TimeControl>,
+    pub variant: String,
+    pub rated: bool,
+    /// only joined with the invite code
+    pub private: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WsCreateBoard<'a> {
+    pub session_id: &'a str,
+    pub settings: &'a WsBoardSettings,
 }
 
 #[derive(Serialize, Debug)]
@@ -16,25 +69,79 @@ pub struct WsPlayBoard<'a> {
     pub pos: (usize, usize),
 }
 
+#[derive(Serialize, Debug)]
+pub struct WsResume<'a> {
+    /// the token received when the session has been created
+    pub resume_token: &'a str,
+}
 
 #[derive(Serialize, Debug)]
-pub struct WsGameOver<'a> {
-    /// the user session to validate
+pub struct WsWatchBoard<'a> {
     pub session_id: &'a str,
-    /// the board id to leave
     pub board_id: &'a str,
-    /// (black score, white score)
-    pub score: (usize, usize),
 }
 
+#[derive(Serialize, Debug)]
+pub struct WsCancelBoard<'a> {
+    pub session_id: &'a str,
+    pub board_id: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WsSendChat<'a> {
+    pub session_id: &'a str,
+    /// the board of the user, `None` for the lobby
+    pub board_id: Option<&'a str>,
+    pub text: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WsMuteOpponent<'a> {
+    pub session_id: &'a str,
+    pub board_id: &'a str,
+    pub muted: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WsGameAction<'a> {
+    pub session_id: &'a str,
+    /// the board of the game, the finished one for a rematch
+    pub board_id: &'a str,
+}
 
 /// Web Socket Client Request
 #[derive(Serialize, Debug)]
 pub enum WsRequest<'a> {
     ConnectingParam(WsConnectingParam<'a>),
     JoinBoard(WsJoinBoard<'a>),
+    CreateBoard(WsCreateBoard<'a>),
+    /// Leave the board before an opponent joins it
+    CancelBoard(WsCancelBoard<'a>),
+    /// Receive the open boards until leaving the lobby
+    ListOpenBoards,
+    LeaveLobby,
     PlayBoard(WsPlayBoard<'a>),
-    GameOver(WsGameOver<'a>),
+    /// Tell the server the user is still there
+    KeepAlive,
+    /// Take over a previous session
+    Resume(WsResume<'a>),
+    /// Ask for the games that can be watched
+    ListLiveBoards,
+    /// Follow a game as a spectator
+    WatchBoard(WsWatchBoard<'a>),
+    StopWatching(WsWatchBoard<'a>),
+    /// Talk to the players and spectators of the board, or to the lobby
+    SendChat(WsSendChat<'a>),
+    /// Stop receiving the messages of the opponent, or receive them again
+    MuteOpponent(WsMuteOpponent<'a>),
+    /// Give the game to the opponent
+    Resign(WsGameAction<'a>),
+    /// End the game without result, before both players have moved
+    Abort(WsGameAction<'a>),
+    /// Play the finished game again with the colors swapped, or accept the
+    /// offer of the opponent
+    OfferRematch(WsGameAction<'a>),
+    DeclineRematch(WsGameAction<'a>),
 }
 
 
@@ -44,14 +151,35 @@ pub struct WsConnectedParam {
     pub session_id: String,
     /// the number of users that are connected to the game
     pub users_count: usize,
+    /// a secret to resume the session after a reload
+    pub resume_token: String,
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Color {
     Black,
     White,
 }
 
+/// The rating of a player, sent by a server that rates the players
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WsRating {
+    pub rating: f64,
+    pub deviation: f64,
+    /// not reliable yet
+    pub provisional: bool,
+}
+
+impl fmt::Display for WsRating {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.0}", self.rating)?;
+        if self.provisional {
+            write!(f, "?")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct WsJoinedBoard {
     /// a session_id to reuse while playing
@@ -62,6 +190,28 @@ pub struct WsJoinedBoard {
     pub color: Color,
     // the nickname received in the ConnectionParam
     pub opponent: Option<String>,
+    /// the code to share, for a private board
+    pub invite_code: Option<String>,
+    #[serde(default)]
+    pub rating: Option<WsRating>,
+    #[serde(default)]
+    pub opponent_rating: Option<WsRating>,
+    /// the game is rated, the engine does not help the players
+    #[serde(default)]
+    pub rated: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsBoardCancelled {
+    pub session_id: String,
+    pub board_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsJoinFailed {
+    pub session_id: String,
+    /// why no board can be joined
+    pub reason: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -72,6 +222,27 @@ pub struct WsOpponentJoinedBoard {
     pub board_id: String,
     // the nickname received in the ConnectionParam
     pub opponent: String,
+    #[serde(default)]
+    pub opponent_rating: Option<WsRating>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WsOpenBoard {
+    pub board_id: String,
+    /// the nickname of the user waiting on the board
+    pub creator: String,
+    /// the color of the user who joins the board
+    pub color: Color,
+    pub settings: WsBoardSettings,
+}
+
+/// The time left to the players, in milliseconds
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WsClocks {
+    pub black: u64,
+    pub white: u64,
+    /// the player whose clock runs
+    pub running: Option<Color>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -79,16 +250,205 @@ pub struct WsPlayedBoard {
     pub session_id: String,
     pub board_id: String,
     pub pos: (usize, usize),
+    /// `None` for a game without clock
+    #[serde(default)]
+    pub clocks: Option<WsClocks>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsBoardClocks {
+    pub session_id: String,
+    pub board_id: String,
+    pub clocks: WsClocks,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum GameOverReason {
+    Normal,
+    Resignation,
+    Timeout,
+    Disconnect,
+    Aborted,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WsGameResult {
+    pub session_id: String,
+    /// the board id, closed by the server
+    pub board_id: String,
+    /// the winner, `None` for a draw
+    pub winner: Option<Color>,
+    /// (black score, white score)
+    pub score: (usize, usize),
+    pub reason: GameOverReason,
+}
+
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WsBoardState {
+    /// 64 squares, `B` and `W` for discs, `.` for empty squares
+    pub position: String,
+    /// the player who has to move
+    pub current_player: Color,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsRejectedMove {
+    pub session_id: String,
+    pub board_id: String,
+    /// the refused position
+    pub pos: (usize, usize),
+    /// why the server refused the move
+    pub reason: String,
+    /// the state of the board on the server
+    pub board: Option<WsBoardState>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WsResumedBoard {
+    pub board_id: String,
+    /// the color where the user play
+    pub color: Color,
+    pub opponent: Option<String>,
+    pub state: WsBoardState,
+    /// the moves played since the beginning, `x + y * 8`
+    pub moves: Vec<usize>,
+    #[serde(default)]
+    pub clocks: Option<WsClocks>,
+    /// the game is rated, the engine does not help the players
+    #[serde(default)]
+    pub rated: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsResumed {
+    /// the session_id of the resumed session
+    pub session_id: String,
+    pub nickname: Option<String>,
+    pub users_count: usize,
+    /// the game in progress
+    pub board: Option<WsResumedBoard>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsResumeFailed {
+    pub reason: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum WsErrorCode {
+    InvalidRequest,
+    InvalidInput,
+    InvalidSession,
+    RateLimited,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsError {
+    pub code: WsErrorCode,
+    pub message: String,
+    /// the name of the refused request, if the server could read it
+    pub request: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsLoginFailed {
+    pub session_id: String,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsOpponentPresence {
+    pub session_id: String,
+    pub board_id: String,
+    /// the color of the player who left or came back
+    pub color: Color,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WsLiveBoard {
+    pub board_id: String,
+    pub black: String,
+    pub white: String,
+    /// (black score, white score)
+    pub score: (usize, usize),
+    pub spectators: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WsWatchedBoard {
+    pub session_id: String,
+    pub board_id: String,
+    pub black: String,
+    pub white: String,
+    pub state: WsBoardState,
+    /// the moves played since the beginning, `x + y * 8`
+    pub moves: Vec<usize>,
+    pub spectators: usize,
+    #[serde(default)]
+    pub clocks: Option<WsClocks>,
 }
 
 #[derive(Deserialize, Debug)]
-pub struct WsOpponentDisconnected {
-    /// the user session to validate
+pub struct WsWatchFailed {
     pub session_id: String,
-    /// the board id to leave
     pub board_id: String,
+    /// why the game cannot be watched
+    pub reason: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct WsSpectators {
+    pub session_id: String,
+    pub board_id: String,
+    /// the number of sessions watching the board
+    pub count: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WsChatMessage {
+    pub author: String,
+    pub text: String,
+    /// milliseconds since the unix epoch
+    pub sent_at: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsChat {
+    pub session_id: String,
+    /// `None` for the lobby
+    pub board_id: Option<String>,
+    pub message: WsChatMessage,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsChatHistory {
+    pub session_id: String,
+    /// `None` for the lobby
+    pub board_id: Option<String>,
+    /// the oldest first
+    pub messages: Vec<WsChatMessage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsChatFailed {
+    pub session_id: String,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsRematch {
+    pub session_id: String,
+    /// the board of the finished game
+    pub board_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WsGameActionFailed {
+    pub session_id: String,
+    pub board_id: String,
+    pub reason: String,
+}
 
 #[derive(Deserialize, Debug)]
 pub enum WsResponse {
@@ -96,10 +456,52 @@ pub enum WsResponse {
     ConnectedParam(WsConnectedParam),
     /// `join board` command parameters
     JoinedBoard(WsJoinedBoard),
+    /// no board can be joined for now
+    JoinFailed(WsJoinFailed),
+    /// the board of the user has been closed before the game started
+    BoardCancelled(WsBoardCancelled),
     /// when the opponent join the board someone created
     OpponentJoinedBoard(WsOpponentJoinedBoard),
+    /// The boards waiting for an opponent, sent again when they change
+    OpenBoards(Vec<WsOpenBoard>),
     /// Reveiced the move from the opponent,
     PlayedBoard(WsPlayedBoard),
-    /// Received when the opponent users disconnect during a game, will leave the board
-    OpponentDisconnected(WsOpponentDisconnected),
+    /// The clocks when the game starts, and after the moves of the user
+    Clocks(WsBoardClocks),
+    /// Received by both players when the game is over, the board is closed
+    GameResult(WsGameResult),
+    /// The server refused the move of the user
+    RejectedMove(WsRejectedMove),
+    /// The answer of the server to a keep alive
+    KeepAlive,
+    /// The previous session is back, with the game in progress
+    Resumed(WsResumed),
+    /// The previous session has expired, a new one has to be created
+    ResumeFailed(WsResumeFailed),
+    /// The opponent lost the connection, its seat is held for a while
+    OpponentReconnecting(WsOpponentPresence),
+    OpponentReconnected(WsOpponentPresence),
+    /// The games in progress
+    LiveBoards(Vec<WsLiveBoard>),
+    /// The position of the watched game, its moves and result follow
+    WatchedBoard(WsWatchedBoard),
+    WatchFailed(WsWatchFailed),
+    /// The number of spectators of the board changed
+    Spectators(WsSpectators),
+    /// A message of the board or of the lobby
+    Chat(WsChat),
+    /// The last messages, when the user arrives on a board or in the lobby
+    ChatHistory(WsChatHistory),
+    /// The server refused the message of the user
+    ChatFailed(WsChatFailed),
+    /// The opponent of the finished game wants to play again
+    RematchOffered(WsRematch),
+    /// The opponent declined the rematch, or left
+    RematchDeclined(WsRematch),
+    /// The server refused to resign, abort or start the rematch
+    GameActionFailed(WsGameActionFailed),
+    /// The nickname is taken, or the name or the password is wrong
+    LoginFailed(WsLoginFailed),
+    /// The server refused a request, it disconnects after too many
+    Error(WsError),
 }
@@ -0,0 +1,287 @@
+//! A small negamax engine with alpha-beta pruning, shared by the front ends
+//! and the bot of the server.
+//!
+//! The engine searches any board implementing `Position`, such as the
+//! `BoardModel` of the `model` module that holds the rules of the game.
+//!
+//! Scores are a disc difference from the point of view of the player to
+//! move. They are exact when the end of the game is reachable by the search,
+//! and estimated from the position otherwise.
+
+use std::cmp::Reverse;
+
+pub mod model;
+
+pub const BOARD_SIZE: usize = 8;
+pub const BOARD_SIZE_SQUARE: usize = BOARD_SIZE * BOARD_SIZE;
+
+/// Under this number of empty squares, the game is solved until the end
+pub const EXACT_EMPTIES: usize = 8;
+
+const INFINITY: i32 = 1000;
+
+/// Square values: corners are gold, squares next to them give them away
+const WEIGHTS: [i32; BOARD_SIZE_SQUARE] = [
+    100, -20, 10, 5, 5, 10, -20, 100,
+    -20, -50, -2, -2, -2, -2, -50, -20,
+    10, -2, -1, -1, -1, -1, -2, 10,
+    5, -2, -1, -1, -1, -1, -2, 5,
+    5, -2, -1, -1, -1, -1, -2, 5,
+    10, -2, -1, -1, -1, -1, -2, 10,
+    -20, -50, -2, -2, -2, -2, -50, -20,
+    100, -20, 10, 5, 5, 10, -20, 100,
+];
+
+/// What a square holds, the players are the colors of their discs
+pub trait Disc: Copy + PartialEq {
+    /// the player the final disc difference is counted for
+    const BLACK: Self;
+
+    fn opposite(&self) -> Self;
+}
+
+/// A board the engine can search, the squares are numbered
+/// `x + y * BOARD_SIZE`
+pub trait Position: Clone {
+    type Disc: Disc;
+
+    fn square(&self, pos: usize) -> Self::Disc;
+
+    /// (black discs, white discs)
+    fn score(&self) -> (usize, usize);
+
+    /// The squares where `player` can play
+    fn moves(&self, player: Self::Disc) -> Vec<usize>;
+
+    /// The board once `player` has played `pos`, a legal move
+    fn play(&self, pos: usize, player: Self::Disc) -> Self;
+}
+
+/// Estimate the final disc difference of a position for `player`.
+pub fn evaluate<P: Position>(board: &P, player: P::Disc) -> i32 {
+    let opponent = player.opposite();
+    let mut position = 0;
+    for (pos, weight) in WEIGHTS.iter().enumerate() {
+        let disc = board.square(pos);
+        if disc == player {
+            position += weight;
+        } else if disc == opponent {
+            position -= weight;
+        }
+    }
+    let mobility = board.moves(player).len() as i32 - board.moves(opponent).len() as i32;
+    let limit = BOARD_SIZE_SQUARE as i32 - 1;
+    let estimate = (position + 4 * mobility) / 8;
+    if estimate > limit {
+        limit
+    } else if estimate < -limit {
+        -limit
+    } else {
+        estimate
+    }
+}
+
+/// Score of the position for `player`, when `player` has to move.
+pub fn assess<P: Position>(board: &P, player: P::Disc, depth: usize) -> i32 {
+    negamax(board, player, search_depth(board, depth), -INFINITY, INFINITY)
+}
+
+/// Turn a disc difference into the chance of winning the game
+pub fn win_probability(score: i32) -> f64 {
+    1. / (1. + (-score as f64 / 8.).exp())
+}
+
+/// The move the engine would play for `player`, with its score.
+///
+/// Return `None` if the player has to pass.
+pub fn best_move<P: Position>(board: &P, player: P::Disc, depth: usize) -> Option<(usize, i32)> {
+    let depth = search_depth(board, depth);
+    let mut alpha = -INFINITY;
+    let mut best = None;
+    for pos in ordered_moves(board, player) {
+        let next = board.play(pos, player);
+        let score = -negamax(&next, player.opposite(), depth - 1, -INFINITY, -alpha);
+        if score > alpha {
+            alpha = score;
+            best = Some((pos, score));
+        }
+    }
+    best
+}
+
+/// Score every legal move of `player`, the best one first.
+pub fn rank_moves<P: Position>(board: &P, player: P::Disc, depth: usize) -> Vec<(usize, i32)> {
+    let depth = search_depth(board, depth);
+    let mut ranked: Vec<(usize, i32)> = ordered_moves(board, player)
+        .into_iter()
+        .map(|pos| {
+            let next = board.play(pos, player);
+            let score = -negamax(&next, player.opposite(), depth - 1, -INFINITY, INFINITY);
+            (pos, score)
+        })
+        .collect();
+    ranked.sort_by_key(|&(_, score)| Reverse(score));
+    ranked
+}
+
+/// Solve the endgame: the exact score of every legal move, best first.
+///
+/// The cost grows exponentially with the number of empty squares.
+pub fn solve<P: Position>(board: &P, player: P::Disc) -> Vec<(usize, i32)> {
+    rank_moves(board, player, empties(board))
+}
+
+/// Number of empty squares left on the board
+pub fn empties<P: Position>(board: &P) -> usize {
+    let score = board.score();
+    BOARD_SIZE_SQUARE - score.0 - score.1
+}
+
+fn search_depth<P: Position>(board: &P, depth: usize) -> usize {
+    let empties = empties(board);
+    if empties <= EXACT_EMPTIES {
+        empties.max(1)
+    } else {
+        depth.max(1)
+    }
+}
+
+/// Disc difference of a finished game, empty squares go to the winner
+fn final_score<P: Position>(board: &P, player: P::Disc) -> i32 {
+    let (black, white) = board.score();
+    let empties = (BOARD_SIZE_SQUARE - black - white) as i32;
+    let diff = black as i32 - white as i32;
+    let diff = if diff > 0 {
+        diff + empties
+    } else if diff < 0 {
+        diff - empties
+    } else {
+        diff
+    };
+    if player == P::Disc::BLACK {
+        diff
+    } else {
+        -diff
+    }
+}
+
+/// Legal moves sorted by square value, to cut the search earlier
+fn ordered_moves<P: Position>(board: &P, player: P::Disc) -> Vec<usize> {
+    let mut moves = board.moves(player);
+    moves.sort_by_key(|&pos| (-WEIGHTS[pos], pos));
+    moves
+}
+
+fn negamax<P: Position>(board: &P, player: P::Disc, depth: usize, mut alpha: i32, beta: i32) -> i32 {
+    let moves = ordered_moves(board, player);
+    if moves.is_empty() {
+        if board.moves(player.opposite()).is_empty() {
+            return final_score(board, player);
+        }
+        // pass
+        return -negamax(board, player.opposite(), depth, -beta, -alpha);
+    }
+    if depth == 0 {
+        return evaluate(board, player);
+    }
+    let mut best = -INFINITY;
+    for pos in moves {
+        let next = board.play(pos, player);
+        let score = -negamax(&next, player.opposite(), depth - 1, -beta, -alpha);
+        if score > best {
+            best = score;
+        }
+        if best > alpha {
+            alpha = best;
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::{BoardModel, Cell};
+
+    /// Black to play, white has one disc left to take
+    const ENDGAME: &str = r#"
+        . W B W W W W W
+        W W W W W W W W
+        W W W W W W W W
+        W W W W W W W W
+        W W W W W W W W
+        W W W W W W W W
+        W W W W W W W W
+        W W W W W W W W
+        "#;
+
+    #[test]
+    fn test_best_move_take_corner() {
+        let board = BoardModel::from_string(
+            r#"
+            . . . . . . . .
+            . W . . . . . .
+            . . W . . . . .
+            . . . W B . . .
+            . . . B B . . .
+            . . . . . . . .
+            . . . . . . . .
+            . . . . . . . .
+            "#,
+        );
+        let (pos, _) = best_move(&board, Cell::Black, 4).unwrap();
+        assert_eq!(pos, 0);
+    }
+
+    #[test]
+    fn test_best_move_exact_endgame() {
+        let board = BoardModel::from_string(ENDGAME);
+        assert_eq!(best_move(&board, Cell::Black, 4), Some((0, -58)));
+        assert_eq!(rank_moves(&board, Cell::Black, 4), vec![(0, -58)]);
+        assert_eq!(solve(&board, Cell::Black), vec![(0, -58)]);
+    }
+
+    #[test]
+    fn test_best_move_pass() {
+        let board = BoardModel::from_string(ENDGAME);
+        assert_eq!(best_move(&board, Cell::White, 4), None);
+    }
+
+    #[test]
+    fn test_assess() {
+        let board = BoardModel::from_string(ENDGAME);
+        assert_eq!(assess(&board, Cell::Black, 4), -58);
+        let over = board.play(0, Cell::Black);
+        assert_eq!(assess(&over, Cell::White, 4), 58);
+    }
+
+    #[test]
+    fn test_win_probability() {
+        assert_eq!(win_probability(0), 0.5);
+        assert!(win_probability(10) > 0.75);
+        assert!(win_probability(64) > 0.99);
+        assert!((win_probability(-10) + win_probability(10) - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_evaluate_symmetry() {
+        let board = BoardModel::from_string(
+            r#"
+            . . . . . . . .
+            . . . . . . . .
+            . . . . . . . .
+            . . . B W . . .
+            . . . W B . . .
+            . . . . . . . .
+            . . . . . . . .
+            . . . . . . . .
+            "#,
+        );
+        assert_eq!(evaluate(&board, Cell::Black), 0);
+        assert_eq!(evaluate(&board, Cell::White), 0);
+        assert_eq!(empties(&board), 60);
+    }
+}
@@ -1,7 +1,11 @@
+//! The rules of the game, shared by the server that referees the games and
+//! by the front ends.
+
 use std::collections::HashSet;
 
-pub const BOARD_SIZE: usize = 8;
-pub const BOARD_SIZE_SQUARE: usize = BOARD_SIZE * BOARD_SIZE;
+use {Disc, Position};
+
+pub use {BOARD_SIZE, BOARD_SIZE_SQUARE};
 
 macro_rules! traverse_board {
 
@@ -109,8 +113,9 @@ impl BoardModel {
         BoardModel { cells }
     }
 
-    #[cfg(test)]
-    fn from_string(boardstr: &str) -> Self {
+    /// Read a position string, `B` and `W` are discs, anything else is
+    /// an empty square, spaces and new lines are ignored.
+    pub fn from_string(boardstr: &str) -> Self {
         let mut cells = [Cell::Empty; BOARD_SIZE_SQUARE];
         let boardstr = boardstr.replace("\n", "");
         let boardstr = boardstr.replace(" ", "");
@@ -124,6 +129,18 @@ impl BoardModel {
         BoardModel { cells }
     }
 
+    /// Write the position string read by `from_string`
+    pub fn to_string(&self) -> String {
+        self.cells
+            .iter()
+            .map(|cell| match *cell {
+                Cell::Black => 'B',
+                Cell::White => 'W',
+                Cell::Empty => '.',
+            })
+            .collect()
+    }
+
     pub fn cell(&self, x: usize, y: usize) -> &Cell {
         let pos = x + y * BOARD_SIZE;
         &self.cells[pos]
@@ -315,6 +332,36 @@ impl BoardModel {
     );
 }
 
+impl Disc for Cell {
+    const BLACK: Cell = Cell::Black;
+
+    fn opposite(&self) -> Cell {
+        Cell::opposite(self)
+    }
+}
+
+impl Position for BoardModel {
+    type Disc = Cell;
+
+    fn square(&self, pos: usize) -> Cell {
+        self.cells[pos]
+    }
+
+    fn score(&self) -> (usize, usize) {
+        BoardModel::score(self)
+    }
+
+    fn moves(&self, player: Cell) -> Vec<usize> {
+        self.get_possibilities(player)
+    }
+
+    fn play(&self, pos: usize, player: Cell) -> BoardModel {
+        let mut next = self.clone();
+        let _ = next.set_cell(pos % BOARD_SIZE, pos / BOARD_SIZE, player);
+        next
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -518,6 +565,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_position_string() {
+        let board = BoardModel::new();
+        let position = board.to_string();
+        assert_eq!(position.len(), BOARD_SIZE_SQUARE);
+        assert_eq!(&position[24..40], "...BW......WB...");
+        assert_eq!(BoardModel::from_string(&position).to_string(), position);
+    }
+
     #[test]
     fn test_score() {
         let board = BoardModel::from_string(
@@ -0,0 +1,286 @@
+//! Registered accounts.
+//!
+//! The name of an account is reserved: a guest cannot connect with it, and
+//! the ratings and the games of the name are those of the account. The
+//! passwords are kept as argon2id hashes, in the PHC string format that
+//! carries the salt and the parameters along.
+//!
+//! Hashing takes tens of milliseconds and megabytes of memory: it runs on
+//! the threads of `AccountExecutor`, the store only reads and writes the
+//! accounts.
+
+use std::error::Error;
+use std::fmt;
+
+use actix::prelude::*;
+use argon2::{self, Config, ThreadMode, Variant, Version};
+use futures::Future;
+use rand::{self, Rng};
+
+use bot::BOT_NAME;
+use game;
+use storage::{AccountRecord, CreateAccount, DbExecutor, GameStore, GetAccount, StoreError};
+
+pub const MIN_NAME_LEN: usize = 2;
+pub const MAX_NAME_LEN: usize = 20;
+pub const MIN_PASSWORD_LEN: usize = 8;
+/// Longer passwords are refused, not to hash megabytes
+pub const MAX_PASSWORD_LEN: usize = 128;
+
+/// The parameters recommended by OWASP for argon2id: 19 MiB, 2 passes
+const HASH_CONFIG: Config<'static> = Config {
+    ad: &[],
+    hash_length: 32,
+    lanes: 1,
+    mem_cost: 19 * 1024,
+    secret: &[],
+    thread_mode: ThreadMode::Sequential,
+    time_cost: 2,
+    variant: Variant::Argon2id,
+    version: Version::Version13,
+};
+
+#[derive(Debug)]
+pub enum AccountError {
+    /// the name or the password is refused
+    Invalid(String),
+    NameTaken,
+    /// the name is unknown or the password is wrong, not telling which
+    WrongCredentials,
+    Store(StoreError),
+    /// the store did not answer
+    Unavailable,
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AccountError::Invalid(ref reason) => write!(f, "{}", reason),
+            AccountError::NameTaken => write!(f, "The name is already taken"),
+            AccountError::WrongCredentials => write!(f, "Wrong name or password"),
+            AccountError::Store(ref err) => write!(f, "{}", err),
+            AccountError::Unavailable => write!(f, "The accounts are unavailable"),
+        }
+    }
+}
+
+impl Error for AccountError {
+    fn description(&self) -> &str {
+        match *self {
+            AccountError::Invalid(_) => "invalid account",
+            AccountError::NameTaken => "name taken",
+            AccountError::WrongCredentials => "wrong credentials",
+            AccountError::Store(_) => "store error",
+            AccountError::Unavailable => "accounts unavailable",
+        }
+    }
+}
+
+impl From<StoreError> for AccountError {
+    fn from(err: StoreError) -> Self {
+        AccountError::Store(err)
+    }
+}
+
+/// Two names are the same account regardless of their case
+pub fn name_key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Refuse the names hard to tell apart or to write
+pub fn check_name(name: &str) -> Result<(), String> {
+    let len = name.chars().count();
+    if len < MIN_NAME_LEN {
+        return Err(format!("The name must have at least {} characters", MIN_NAME_LEN));
+    }
+    if len > MAX_NAME_LEN {
+        return Err(format!("The name cannot exceed {} characters", MAX_NAME_LEN));
+    }
+    if !name.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("The name can only have letters, digits, - and _".to_string());
+    }
+    if name_key(name) == name_key(BOT_NAME) {
+        return Err("The name of the bot is reserved".to_string());
+    }
+    Ok(())
+}
+
+pub fn check_password(password: &str) -> Result<(), String> {
+    let len = password.chars().count();
+    if len < MIN_PASSWORD_LEN {
+        return Err(format!(
+            "The password must have at least {} characters",
+            MIN_PASSWORD_LEN
+        ));
+    }
+    if len > MAX_PASSWORD_LEN {
+        return Err(format!(
+            "The password cannot exceed {} characters",
+            MAX_PASSWORD_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// The hash of the password, with a salt of its own
+pub fn hash_password(password: &str) -> String {
+    let salt: [u8; 16] = rand::thread_rng().gen();
+    argon2::hash_encoded(password.as_bytes(), &salt, &HASH_CONFIG)
+        .expect("The argon2 parameters are valid")
+}
+
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    match argon2::verify_encoded(hash, password.as_bytes()) {
+        Ok(valid) => valid,
+        Err(err) => {
+            error!("Cannot verify a password hash: {}", err);
+            false
+        }
+    }
+}
+
+/// The account to create, its password hashed
+pub fn new_account(name: &str, password: &str) -> Result<AccountRecord, AccountError> {
+    check_name(name).map_err(AccountError::Invalid)?;
+    check_password(password).map_err(AccountError::Invalid)?;
+    Ok(AccountRecord {
+        name: name.to_string(),
+        password_hash: hash_password(password),
+        created_at: game::timestamp(),
+    })
+}
+
+/// Save the account, its name must be free
+pub fn create(store: &mut dyn GameStore, account: AccountRecord) -> Result<AccountRecord, AccountError> {
+    if store.create_account(&account)? {
+        Ok(account)
+    } else {
+        Err(AccountError::NameTaken)
+    }
+}
+
+/// The account read from the store, if the password is right
+pub fn check_login(account: Option<AccountRecord>, password: &str) -> Result<AccountRecord, AccountError> {
+    match account {
+        Some(ref account) if verify_password(&account.password_hash, password) => {
+            Ok(account.clone())
+        }
+        _ => Err(AccountError::WrongCredentials),
+    }
+}
+
+/// Hash and check the passwords on `SyncArbiter` threads of their own, the
+/// accounts are read and written by the store
+pub struct AccountExecutor {
+    pub db: Addr<Syn, DbExecutor>,
+}
+
+impl Actor for AccountExecutor {
+    type Context = SyncContext<Self>;
+}
+
+/// Create an account
+#[derive(Message)]
+#[rtype(result = "Result<AccountRecord, AccountError>")]
+pub struct Register {
+    pub name: String,
+    pub password: String,
+}
+
+/// Check the password of an account
+#[derive(Message)]
+#[rtype(result = "Result<AccountRecord, AccountError>")]
+pub struct Login {
+    pub name: String,
+    pub password: String,
+}
+
+impl Handler<Register> for AccountExecutor {
+    type Result = Result<AccountRecord, AccountError>;
+
+    fn handle(&mut self, msg: Register, _: &mut Self::Context) -> Self::Result {
+        let account = new_account(&msg.name, &msg.password)?;
+        // this thread waits for the store, the hashing threads do nothing else
+        self.db
+            .send(CreateAccount(account))
+            .wait()
+            .map_err(|_| AccountError::Unavailable)?
+    }
+}
+
+impl Handler<Login> for AccountExecutor {
+    type Result = Result<AccountRecord, AccountError>;
+
+    fn handle(&mut self, msg: Login, _: &mut Self::Context) -> Self::Result {
+        let account = self.db
+            .send(GetAccount(msg.name))
+            .wait()
+            .map_err(|_| AccountError::Unavailable)??;
+        check_login(account, &msg.password)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::MemoryStore;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("alice").is_ok());
+        assert!(check_name("Bob_2-b").is_ok());
+        assert!(check_name("a").is_err());
+        assert!(check_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+        assert!(check_name("alice bob").is_err());
+        assert!(check_name("alicé").is_err());
+        assert!(check_name("BOT").is_err());
+        assert!(check_password("correct horse").is_ok());
+        assert!(check_password("short").is_err());
+    }
+
+    #[test]
+    fn test_hash_password() {
+        let hash = hash_password("correct horse");
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(!hash.contains("correct horse"));
+        assert!(verify_password(&hash, "correct horse"));
+        assert!(!verify_password(&hash, "battery staple"));
+        // salted
+        assert_ne!(hash, hash_password("correct horse"));
+        assert!(!verify_password("not a hash", "correct horse"));
+    }
+
+    #[test]
+    fn test_register_and_login() {
+        let mut store = MemoryStore::default();
+        let register = |store: &mut MemoryStore, name: &str, password: &str| {
+            new_account(name, password).and_then(|account| create(store, account))
+        };
+        let login = |store: &MemoryStore, name: &str, password: &str| {
+            check_login(store.account(name).unwrap(), password)
+        };
+        let alice = register(&mut store, "Alice", "correct horse").unwrap();
+        assert_eq!(alice.name, "Alice");
+        match register(&mut store, "alice", "battery staple") {
+            Err(AccountError::NameTaken) => {}
+            other => panic!("the name is taken: {:?}", other),
+        }
+        match register(&mut store, "bob", "short") {
+            Err(AccountError::Invalid(_)) => {}
+            other => panic!("the password is too short: {:?}", other),
+        }
+
+        // the name is not case sensitive
+        assert_eq!(login(&store, "ALICE", "correct horse").unwrap().name, "Alice");
+        match login(&store, "alice", "battery staple") {
+            Err(AccountError::WrongCredentials) => {}
+            other => panic!("the password is wrong: {:?}", other),
+        }
+        match login(&store, "bob", "correct horse") {
+            Err(AccountError::WrongCredentials) => {}
+            other => panic!("bob has no account: {:?}", other),
+        }
+    }
+}
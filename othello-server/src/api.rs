@@ -0,0 +1,570 @@
+//! JSON endpoints: the history of the games, the live boards and the
+//! registration of the accounts.
+//!
+//! - `GET /api/games?player=&result=&since=&until=&limit=&offset=`: the
+//!   finished games, the most recent first. `result` is one of `black`,
+//!   `white`, `draw`, or `win` and `loss` for the `player`. `since` and
+//!   `until` are milliseconds since the unix epoch.
+//! - `GET /api/games/{id}`: a finished game with its moves and its final
+//!   position.
+//! - `GET /api/boards?player=`: the games in progress and the boards waiting
+//!   for an opponent.
+//! - `GET /api/tournaments`: the tournaments, by name.
+//! - `GET /api/tournaments/{id}`: the standings and the current round of a
+//!   tournament.
+//! - `POST /api/accounts` with `{"name": ..., "password": ...}`: register an
+//!   account, the name is then reserved. The login is done on the websocket.
+//!   An address registers a few accounts at most, the next ones are answered
+//!   `429 Too Many Requests`.
+//! - `GET /api/players/{name}`: the rating of a player of rated games.
+//! - `GET /api/players/{name}/ratings?limit=`: the ratings of a player after
+//!   each rated game, the most recent first.
+
+use std::time::Instant;
+
+use actix_web::dev::HttpResponseBuilder;
+use actix_web::http::StatusCode;
+use actix_web::{AsyncResponder, FutureResponse, HttpRequest, HttpResponse, Json, Query};
+use futures::future::{self, Future};
+
+use account::{AccountError, Register};
+use engine::model::{BoardModel, Cell, BOARD_SIZE};
+use rating::Rating;
+use server::{AccountRegistered, GetTournament, ListBoards, ListTournaments};
+use storage::{GameFilter, GameRecord, GetGame, GetPlayer, ListGames, PlayerRecord, RatingChange,
+              RatingHistory, ResultFilter};
+use validation::{REGISTER_RATE, REGISTER_WINDOW};
+use wscommand::{Color, GameOverReason};
+use AppState;
+
+/// Above this number, the games are listed by pages
+pub const MAX_LIMIT: usize = 200;
+
+#[derive(Serialize, Debug)]
+struct ApiError {
+    error: String,
+}
+
+fn error_response(mut builder: HttpResponseBuilder, error: &str) -> HttpResponse {
+    builder.json(ApiError {
+        error: error.to_string(),
+    })
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct GamesQuery {
+    player: Option<String>,
+    result: Option<String>,
+    since: Option<i64>,
+    until: Option<i64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+impl GamesQuery {
+    fn filter(self) -> Result<GameFilter, String> {
+        let result = match self.result {
+            Some(ref name) => match ResultFilter::parse(name) {
+                Some(result) => Some(result),
+                None => return Err(format!("Unknown result {:?}", name)),
+            },
+            None => None,
+        };
+        if self.player.is_none()
+            && (result == Some(ResultFilter::Win) || result == Some(ResultFilter::Loss))
+        {
+            return Err("The win and loss results require a player".to_string());
+        }
+        let default = GameFilter::default();
+        let limit = self.limit.unwrap_or(default.limit);
+        if limit > MAX_LIMIT {
+            return Err(format!("The limit cannot exceed {}", MAX_LIMIT));
+        }
+        Ok(GameFilter {
+            player: self.player,
+            result,
+            since: self.since,
+            until: self.until,
+            limit,
+            offset: self.offset.unwrap_or(default.offset),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BoardsQuery {
+    player: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct HistoryQuery {
+    limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    fn limit(&self) -> Result<usize, String> {
+        let limit = self.limit.unwrap_or_else(|| GameFilter::default().limit);
+        if limit > MAX_LIMIT {
+            return Err(format!("The limit cannot exceed {}", MAX_LIMIT));
+        }
+        Ok(limit)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct GameSummary<'a> {
+    id: Option<i64>,
+    board_id: &'a str,
+    black: &'a str,
+    white: &'a str,
+    /// the black player has no account
+    black_guest: bool,
+    /// the white player has no account
+    white_guest: bool,
+    /// `None` for a draw
+    winner: Option<Color>,
+    score: (usize, usize),
+    reason: GameOverReason,
+    variant: &'a str,
+    time_control: &'a Option<String>,
+    rated: bool,
+    started_at: i64,
+    ended_at: i64,
+}
+
+impl<'a> From<&'a GameRecord> for GameSummary<'a> {
+    fn from(game: &'a GameRecord) -> Self {
+        GameSummary {
+            id: game.id,
+            board_id: &game.board_id,
+            black: &game.black,
+            white: &game.white,
+            black_guest: game.black_guest,
+            white_guest: game.white_guest,
+            winner: game.winner,
+            score: game.score,
+            reason: game.reason,
+            variant: &game.variant,
+            time_control: &game.time_control,
+            rated: game.rated,
+            started_at: game.started_at,
+            ended_at: game.ended_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct MoveView {
+    color: Color,
+    pos: (usize, usize),
+    played_at: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct GameDetail<'a> {
+    #[serde(flatten)]
+    summary: GameSummary<'a>,
+    moves: Vec<MoveView>,
+    /// 64 squares, `B` and `W` for discs, `.` for empty squares
+    position: String,
+}
+
+/// Replay the moves of a game from the initial position
+fn final_position(game: &GameRecord) -> BoardModel {
+    let mut board = BoardModel::new();
+    for mv in &game.moves {
+        let cell = match mv.color {
+            Color::Black => Cell::Black,
+            Color::White => Cell::White,
+        };
+        if board
+            .set_cell(mv.pos % BOARD_SIZE, mv.pos / BOARD_SIZE, cell)
+            .is_err()
+        {
+            warn!("Game {:?} has an illegal move {}", game.id, mv.pos);
+        }
+    }
+    board
+}
+
+impl<'a> From<&'a GameRecord> for GameDetail<'a> {
+    fn from(game: &'a GameRecord) -> Self {
+        GameDetail {
+            summary: GameSummary::from(game),
+            moves: game.moves
+                .iter()
+                .map(|mv| MoveView {
+                    color: mv.color,
+                    pos: (mv.pos % BOARD_SIZE, mv.pos / BOARD_SIZE),
+                    played_at: mv.played_at,
+                })
+                .collect(),
+            position: final_position(game).to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct RatingView {
+    rating: f64,
+    deviation: f64,
+    volatility: f64,
+    /// the rating is not reliable yet
+    provisional: bool,
+}
+
+impl<'a> From<&'a Rating> for RatingView {
+    fn from(rating: &'a Rating) -> Self {
+        RatingView {
+            rating: rating.rating,
+            deviation: rating.deviation,
+            volatility: rating.volatility,
+            provisional: rating.is_provisional(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct PlayerView<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    rating: RatingView,
+    /// number of rated games
+    games: u32,
+    updated_at: i64,
+}
+
+impl<'a> From<&'a PlayerRecord> for PlayerView<'a> {
+    fn from(player: &'a PlayerRecord) -> Self {
+        PlayerView {
+            name: &player.name,
+            rating: RatingView::from(&player.rating),
+            games: player.games,
+            updated_at: player.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct RatingChangeView {
+    /// the rated game
+    game_id: i64,
+    #[serde(flatten)]
+    rating: RatingView,
+    rated_at: i64,
+}
+
+impl<'a> From<&'a RatingChange> for RatingChangeView {
+    fn from(change: &'a RatingChange) -> Self {
+        RatingChangeView {
+            game_id: change.game_id,
+            rating: RatingView::from(&change.rating),
+            rated_at: change.rated_at,
+        }
+    }
+}
+
+fn no_database() -> FutureResponse<HttpResponse> {
+    Box::new(future::ok(error_response(
+        HttpResponse::ServiceUnavailable(),
+        "The games are not saved by this server",
+    )))
+}
+
+/// `GET /api/games`
+pub fn list_games(
+    req: HttpRequest<AppState>,
+    query: Query<GamesQuery>,
+) -> FutureResponse<HttpResponse> {
+    let filter = match query.into_inner().filter() {
+        Ok(filter) => filter,
+        Err(err) => {
+            return Box::new(future::ok(error_response(HttpResponse::BadRequest(), &err)))
+        }
+    };
+    let db = match req.state().db {
+        Some(ref db) => db,
+        None => return no_database(),
+    };
+    db.send(ListGames(filter))
+        .from_err()
+        .and_then(|res| match res {
+            Ok(games) => {
+                let games: Vec<GameSummary> = games.iter().map(GameSummary::from).collect();
+                Ok(HttpResponse::Ok().json(games))
+            }
+            Err(err) => {
+                error!("Cannot list the games: {}", err);
+                Ok(error_response(HttpResponse::InternalServerError(), "Cannot list the games"))
+            }
+        })
+        .responder()
+}
+
+/// `GET /api/games/{id}`
+pub fn get_game(req: HttpRequest<AppState>) -> FutureResponse<HttpResponse> {
+    let id: i64 = match req.match_info().query("id") {
+        Ok(id) => id,
+        Err(_) => {
+            return Box::new(future::ok(error_response(HttpResponse::NotFound(), "Unknown game")))
+        }
+    };
+    let db = match req.state().db {
+        Some(ref db) => db,
+        None => return no_database(),
+    };
+    db.send(GetGame(id))
+        .from_err()
+        .and_then(move |res| match res {
+            Ok(Some(game)) => Ok(HttpResponse::Ok().json(GameDetail::from(&game))),
+            Ok(None) => Ok(error_response(HttpResponse::NotFound(), "Unknown game")),
+            Err(err) => {
+                error!("Cannot read the game {}: {}", id, err);
+                Ok(error_response(HttpResponse::InternalServerError(), "Cannot read the game"))
+            }
+        })
+        .responder()
+}
+
+/// `GET /api/players/{name}`
+pub fn get_player(req: HttpRequest<AppState>) -> FutureResponse<HttpResponse> {
+    let name: String = match req.match_info().query("name") {
+        Ok(name) => name,
+        Err(_) => {
+            return Box::new(future::ok(error_response(HttpResponse::NotFound(), "Unknown player")))
+        }
+    };
+    let db = match req.state().db {
+        Some(ref db) => db,
+        None => return no_database(),
+    };
+    db.send(GetPlayer(name.clone()))
+        .from_err()
+        .and_then(move |res| match res {
+            Ok(Some(player)) => Ok(HttpResponse::Ok().json(PlayerView::from(&player))),
+            Ok(None) => Ok(error_response(HttpResponse::NotFound(), "Unknown player")),
+            Err(err) => {
+                error!("Cannot read the player {}: {}", name, err);
+                Ok(error_response(HttpResponse::InternalServerError(), "Cannot read the player"))
+            }
+        })
+        .responder()
+}
+
+/// `GET /api/players/{name}/ratings`
+pub fn rating_history(
+    req: HttpRequest<AppState>,
+    query: Query<HistoryQuery>,
+) -> FutureResponse<HttpResponse> {
+    let limit = match query.limit() {
+        Ok(limit) => limit,
+        Err(err) => {
+            return Box::new(future::ok(error_response(HttpResponse::BadRequest(), &err)))
+        }
+    };
+    let name: String = match req.match_info().query("name") {
+        Ok(name) => name,
+        Err(_) => {
+            return Box::new(future::ok(error_response(HttpResponse::NotFound(), "Unknown player")))
+        }
+    };
+    let db = match req.state().db {
+        Some(ref db) => db,
+        None => return no_database(),
+    };
+    db.send(RatingHistory {
+        player: name.clone(),
+        limit,
+    }).from_err()
+        .and_then(move |res| match res {
+            Ok(history) => {
+                let history: Vec<RatingChangeView> =
+                    history.iter().map(RatingChangeView::from).collect();
+                Ok(HttpResponse::Ok().json(history))
+            }
+            Err(err) => {
+                error!("Cannot read the ratings of {}: {}", name, err);
+                Ok(error_response(HttpResponse::InternalServerError(), "Cannot read the ratings"))
+            }
+        })
+        .responder()
+}
+
+#[derive(Deserialize)]
+pub struct AccountForm {
+    name: String,
+    password: String,
+}
+
+#[derive(Serialize, Debug)]
+struct AccountView {
+    name: String,
+    /// milliseconds since the unix epoch
+    created_at: i64,
+}
+
+/// `POST /api/accounts`
+pub fn register(req: HttpRequest<AppState>, form: Json<AccountForm>) -> FutureResponse<HttpResponse> {
+    let passwords = match req.state().passwords {
+        Some(ref passwords) => passwords,
+        None => return no_database(),
+    };
+    let remote = req.peer_addr().map(|addr| addr.ip());
+    if !req.state().registrations.allow(remote, Instant::now()) {
+        warn!("Too many registrations from {:?}", remote);
+        let message = format!(
+            "Too many registrations, at most {} per {} seconds",
+            REGISTER_RATE, REGISTER_WINDOW
+        );
+        return Box::new(future::ok(error_response(
+            HttpResponse::build(StatusCode::TOO_MANY_REQUESTS),
+            &message,
+        )));
+    }
+    let form = form.into_inner();
+    let addr = req.state().addr.clone();
+    passwords.send(Register {
+        name: form.name,
+        password: form.password,
+    }).from_err()
+        .and_then(move |res| match res {
+            Ok(account) => {
+                addr.do_send(AccountRegistered(account.name.clone()));
+                Ok(HttpResponse::Created().json(AccountView {
+                    name: account.name,
+                    created_at: account.created_at,
+                }))
+            }
+            Err(AccountError::Store(_)) | Err(AccountError::Unavailable) => Ok(error_response(
+                HttpResponse::InternalServerError(),
+                "Cannot register the account",
+            )),
+            Err(err @ AccountError::NameTaken) => {
+                Ok(error_response(HttpResponse::Conflict(), &err.to_string()))
+            }
+            Err(err) => Ok(error_response(HttpResponse::BadRequest(), &err.to_string())),
+        })
+        .responder()
+}
+
+/// `GET /api/boards`
+pub fn list_boards(
+    req: HttpRequest<AppState>,
+    query: Query<BoardsQuery>,
+) -> FutureResponse<HttpResponse> {
+    req.state()
+        .addr
+        .send(ListBoards {
+            player: query.into_inner().player,
+        })
+        .from_err()
+        .and_then(|boards| Ok(HttpResponse::Ok().json(boards)))
+        .responder()
+}
+
+/// `GET /api/tournaments`
+pub fn list_tournaments(req: HttpRequest<AppState>) -> FutureResponse<HttpResponse> {
+    req.state()
+        .addr
+        .send(ListTournaments)
+        .from_err()
+        .and_then(|tournaments| Ok(HttpResponse::Ok().json(tournaments)))
+        .responder()
+}
+
+/// `GET /api/tournaments/{id}`
+pub fn get_tournament(req: HttpRequest<AppState>) -> FutureResponse<HttpResponse> {
+    let tournament_id: String = match req.match_info().query("id") {
+        Ok(tournament_id) => tournament_id,
+        Err(_) => {
+            return Box::new(future::ok(error_response(
+                HttpResponse::NotFound(),
+                "Unknown tournament",
+            )))
+        }
+    };
+    req.state()
+        .addr
+        .send(GetTournament { tournament_id })
+        .from_err()
+        .and_then(|tournament| match tournament {
+            Some(tournament) => Ok(HttpResponse::Ok().json(tournament)),
+            None => Ok(error_response(HttpResponse::NotFound(), "Unknown tournament")),
+        })
+        .responder()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::tests::game_record;
+
+    #[test]
+    fn test_games_query() {
+        let filter = GamesQuery::default().filter().unwrap();
+        assert_eq!(filter, GameFilter::default());
+
+        let filter = GamesQuery {
+            player: Some("alice".to_string()),
+            result: Some("win".to_string()),
+            since: Some(1000),
+            limit: Some(10),
+            ..GamesQuery::default()
+        }.filter()
+            .unwrap();
+        assert_eq!(filter.player, Some("alice".to_string()));
+        assert_eq!(filter.result, Some(ResultFilter::Win));
+        assert_eq!(filter.since, Some(1000));
+        assert_eq!(filter.limit, 10);
+
+        let unknown = GamesQuery {
+            result: Some("lost".to_string()),
+            ..GamesQuery::default()
+        };
+        assert!(unknown.filter().is_err());
+        let no_player = GamesQuery {
+            result: Some("loss".to_string()),
+            ..GamesQuery::default()
+        };
+        assert!(no_player.filter().is_err());
+        let too_many = GamesQuery {
+            limit: Some(MAX_LIMIT + 1),
+            ..GamesQuery::default()
+        };
+        assert!(too_many.filter().is_err());
+    }
+
+    #[test]
+    fn test_history_query() {
+        assert_eq!(HistoryQuery::default().limit(), Ok(GameFilter::default().limit));
+        let page = HistoryQuery { limit: Some(10) };
+        assert_eq!(page.limit(), Ok(10));
+        let too_many = HistoryQuery {
+            limit: Some(MAX_LIMIT + 1),
+        };
+        assert!(too_many.limit().is_err());
+    }
+
+    #[test]
+    fn test_game_detail() {
+        let game = game_record("board");
+        let detail = GameDetail::from(&game);
+        assert_eq!(detail.moves[0].pos, (4, 2));
+        assert_eq!(detail.moves[1].pos, (3, 2));
+        assert_eq!(
+            detail.position,
+            BoardModel::from_string(
+                r#"
+                . . . . . . . .
+                . . . . . . . .
+                . . . W B . . .
+                . . . W B . . .
+                . . . W B . . .
+                . . . . . . . .
+                . . . . . . . .
+                . . . . . . . .
+                "#
+            ).to_string()
+        );
+    }
+}
@@ -0,0 +1,1382 @@
+//! `BoardActor` runs the game of a board.
+//!
+//! The server seats the players and forwards them the requests about the
+//! game. Its `Seats` are the ones that count, the board actor mirrors them:
+//! it is sent the seats along with the player who takes the free one. The
+//! board actor plays the moves, runs the clocks, the bot and the chat of the
+//! board, and answers the players and the spectators directly.
+//! It tells the server how the game goes with `BoardChanged` and its result
+//! with `GameOver`, then the server closes it.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix::prelude::*;
+
+use bot::{self, BOT_ID, BOT_NAME};
+use chat::ChatHistory;
+use clock::GameClock;
+use game::{self, Game};
+use metrics::Metrics;
+use engine::model::{Cell, BOARD_SIZE};
+use server::{BoardChanged, BoardStatus, BoardSummary, GameOver, OthelloActor, Outgoing};
+use storage::{GameRecord, MoveRecord};
+use wscommand::{Color, GameOverReason, WsBoardClocks, WsBoardSettings, WsChat, WsChatHistory,
+                WsChatMessage, WsClocks, WsGameActionFailed, WsGameResult, WsJoinedBoard,
+                WsOpenBoard, WsOpponentJoinedBoard, WsOpponentPresence, WsPlayBoard, WsRating,
+                WsRejectedMove, WsResponse, WsResumed, WsResumedBoard, WsSpectators,
+                WsWatchFailed, WsWatchedBoard};
+
+/// Milliseconds the bot waits before playing, not to play instantly
+const BOT_DELAY: u64 = 500;
+
+/// The players of a board and how they play, seated by the server and
+/// mirrored by the board actor
+#[derive(Clone, Debug)]
+pub struct Seats {
+    // the session id of the black player, or empty string while boarding
+    pub black: String,
+    // the session id of the white player, or empty string while boarding
+    pub white: String,
+    // the nicknames of the players, kept for the record of the game
+    pub black_name: String,
+    pub white_name: String,
+    // how the game is played, chosen by the creator of the board
+    pub settings: WsBoardSettings,
+    // the code to join a private board, until its free seat is taken
+    pub invite_code: Option<String>,
+    // the tournament id of a tournament game
+    pub tournament: Option<String>,
+}
+
+impl Seats {
+    /// A board waiting for an opponent, its creator seated at `color`
+    pub fn new(creator: String, name: String, color: Color, settings: WsBoardSettings) -> Self {
+        let mut seats = Seats {
+            black: "".to_owned(),
+            white: "".to_owned(),
+            black_name: "".to_owned(),
+            white_name: "".to_owned(),
+            settings,
+            invite_code: None,
+            tournament: None,
+        };
+        seats.sit(color, creator, name);
+        seats
+    }
+
+    fn sit(&mut self, color: Color, session_id: String, name: String) {
+        match color {
+            Color::Black => {
+                self.black = session_id;
+                self.black_name = name;
+            }
+            Color::White => {
+                self.white = session_id;
+                self.white_name = name;
+            }
+        }
+    }
+
+    /// The color of the free seat, `None` once the game has started
+    pub fn free_seat(&self) -> Option<Color> {
+        if self.black.is_empty() {
+            Some(Color::Black)
+        } else if self.white.is_empty() {
+            Some(Color::White)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.free_seat().is_some()
+    }
+
+    /// The session waiting for an opponent on an open board
+    pub fn creator(&self) -> &str {
+        if self.black.is_empty() {
+            &self.white
+        } else {
+            &self.black
+        }
+    }
+
+    /// Take the free seat
+    pub fn take(&mut self, session_id: String, name: String) -> Option<Color> {
+        let color = self.free_seat()?;
+        self.sit(color, session_id, name);
+        Some(color)
+    }
+
+    /// The board as listed in the lobby, `None` once the game has started
+    pub fn open_board(&self, board_id: &str, creator_guest: bool) -> Option<WsOpenBoard> {
+        let color = self.free_seat()?;
+        Some(WsOpenBoard {
+            board_id: board_id.to_string(),
+            creator: match color {
+                Color::Black => self.white_name.clone(),
+                Color::White => self.black_name.clone(),
+            },
+            creator_guest,
+            color,
+            settings: self.settings.clone(),
+        })
+    }
+
+    /// The session id of the other player
+    pub fn opponent(&self, session_id: &str) -> &str {
+        if self.black == session_id {
+            &self.white
+        } else {
+            &self.black
+        }
+    }
+
+    /// The color of the session on the board, `Cell::Empty` if not seated
+    pub fn player(&self, session_id: &str) -> Cell {
+        if !self.black.is_empty() && self.black == session_id {
+            Cell::Black
+        } else if !self.white.is_empty() && self.white == session_id {
+            Cell::White
+        } else {
+            Cell::Empty
+        }
+    }
+}
+
+/// The game played on a board
+pub struct BoardData {
+    seats: Seats,
+    // the game played on the board, validated by the server
+    game: Game,
+    // the last messages of the players and the spectators
+    chat: ChatHistory,
+    // the clocks of the players, `None` for a game without clock
+    clock: Option<GameClock>,
+}
+
+impl BoardData {
+    /// The game starts if both seats are taken
+    pub fn new(seats: Seats) -> Self {
+        let mut brd = BoardData {
+            seats,
+            game: Game::default(),
+            chat: ChatHistory::default(),
+            clock: None,
+        };
+        if !brd.seats.is_open() {
+            brd.start();
+        }
+        brd
+    }
+
+    /// Start the clock, the game keeps the time the board was created at
+    fn start(&mut self) {
+        self.clock = self.seats.settings.time_control.map(GameClock::new);
+        if let Some(ref mut clock) = self.clock {
+            clock.start(Cell::Black, Instant::now());
+        }
+    }
+
+    /// The server seated the opponent, the game starts.
+    ///
+    /// Return false if the board was not waiting for them.
+    fn join(&mut self, seats: Seats) -> bool {
+        if !self.seats.is_open() || seats.is_open() {
+            return false;
+        }
+        self.seats = seats;
+        self.start();
+        true
+    }
+
+    /// The time left to the players, `None` for a game without clock
+    fn clocks(&self) -> Option<WsClocks> {
+        self.clock
+            .as_ref()
+            .map(|clock| clock.clocks(Instant::now()))
+    }
+
+    /// The player who ran out of time, if any
+    fn flag_fallen(&self, now: Instant) -> Option<Cell> {
+        self.clock.as_ref()?.flag_fallen(now)
+    }
+
+    /// Stop the clock of the player who just moved and start the one of the
+    /// player to move. The flag has been checked before the move.
+    fn punch_clock(&mut self, now: Instant) {
+        let next = if self.game.is_over() {
+            Cell::Empty
+        } else {
+            self.game.current_player()
+        };
+        if let Some(ref mut clock) = self.clock {
+            if let Err(player) = clock.punch(next, now) {
+                error!("The flag of {:?} fell while playing", player);
+            }
+        }
+    }
+
+    /// The record of the finished game, `guests` tells which players have
+    /// no account
+    fn record(
+        &self,
+        board_id: &str,
+        winner: Cell,
+        reason: GameOverReason,
+        guests: (bool, bool),
+    ) -> GameRecord {
+        let seats = &self.seats;
+        GameRecord {
+            id: None,
+            board_id: board_id.to_string(),
+            black: seats.black_name.clone(),
+            white: seats.white_name.clone(),
+            black_guest: guests.0,
+            white_guest: guests.1,
+            moves: self.game
+                .moves()
+                .iter()
+                .filter_map(|mv| {
+                    game::color(mv.player).map(|color| MoveRecord {
+                        color,
+                        pos: mv.pos,
+                        played_at: mv.played_at,
+                    })
+                })
+                .collect(),
+            winner: game::color(winner),
+            score: self.game.score(),
+            reason,
+            variant: seats.settings.variant.clone(),
+            time_control: seats.settings.time_control.map(|tc| tc.to_string()),
+            // the bot and the guests have no rating
+            rated: seats.settings.rated && seats.black != BOT_ID && seats.white != BOT_ID
+                && !guests.0 && !guests.1,
+            started_at: self.game.started_at(),
+            ended_at: game::timestamp(),
+        }
+    }
+
+    pub fn summary(&self, board_id: &str, spectators: usize) -> BoardSummary {
+        let seats = &self.seats;
+        BoardSummary {
+            board_id: board_id.to_string(),
+            status: if seats.is_open() {
+                BoardStatus::Open
+            } else {
+                BoardStatus::Playing
+            },
+            black: if seats.black.is_empty() {
+                None
+            } else {
+                Some(seats.black_name.clone())
+            },
+            white: if seats.white.is_empty() {
+                None
+            } else {
+                Some(seats.white_name.clone())
+            },
+            state: self.game.state(),
+            score: self.game.score(),
+            moves: self.game.moves().len(),
+            spectators,
+            started_at: self.game.started_at(),
+        }
+    }
+
+    /// The game can be aborted until both players have moved
+    fn can_abort(&self) -> bool {
+        let moved = |player| self.game.moves().iter().any(|mv| mv.player == player);
+        !(moved(Cell::Black) && moved(Cell::White))
+    }
+}
+
+/// Where a session at the board is joinable
+pub struct Member {
+    pub addr: Recipient<Syn, Outgoing>,
+    /// the session ids of the users whose messages the session does not
+    /// receive
+    pub muted: Vec<String>,
+}
+
+/// A player seated by the server
+pub struct Player {
+    pub sess_id: String,
+    pub nickname: String,
+    pub guest: bool,
+    pub rating: Option<WsRating>,
+    /// `None` for the bot
+    pub member: Option<Member>,
+}
+
+impl Player {
+    pub fn bot() -> Self {
+        Player {
+            sess_id: BOT_ID.to_string(),
+            nickname: BOT_NAME.to_string(),
+            guest: false,
+            rating: None,
+            member: None,
+        }
+    }
+}
+
+/// The server seated the player on the free seat, the game starts
+#[derive(Message)]
+pub struct Join {
+    pub player: Player,
+    /// the seats of the server, the player seated
+    pub seats: Seats,
+}
+
+#[derive(Message)]
+pub struct Play {
+    pub sess_id: String,
+    pub param: WsPlayBoard,
+}
+
+/// The session is sent the moves of the game
+#[derive(Message)]
+pub struct Watch {
+    pub sess_id: String,
+    pub member: Member,
+}
+
+#[derive(Message)]
+pub struct Unwatch {
+    pub sess_id: String,
+}
+
+/// A message of a player or a spectator, checked by the server
+#[derive(Message)]
+pub struct Chat {
+    pub sess_id: String,
+    pub message: WsChatMessage,
+}
+
+/// The session ids whose messages the session does not receive anymore
+#[derive(Message)]
+pub struct Mute {
+    pub sess_id: String,
+    pub muted: Vec<String>,
+}
+
+#[derive(Message)]
+pub struct Resign {
+    pub sess_id: String,
+}
+
+#[derive(Message)]
+pub struct Abort {
+    pub sess_id: String,
+}
+
+/// The server ends the game, when a player leaves it
+#[derive(Message)]
+pub struct EndGame {
+    pub winner: Cell,
+    pub reason: GameOverReason,
+}
+
+/// The websocket of the player is closed, their seat is held
+#[derive(Message)]
+pub struct Away {
+    pub sess_id: String,
+}
+
+/// The player resumed their session from a new websocket, they are sent
+/// `resumed` with the game
+#[derive(Message)]
+pub struct Back {
+    pub sess_id: String,
+    pub addr: Recipient<Syn, Outgoing>,
+    pub resumed: WsResumed,
+}
+
+/// The server forgets the board, the actor stops
+#[derive(Message)]
+pub struct Close;
+
+pub struct BoardActor {
+    board_id: String,
+    brd: BoardData,
+    /// the players seated at the board, by session id
+    players: HashMap<String, Player>,
+    /// the sessions watching the game, by session id
+    spectators: HashMap<String, Member>,
+    /// ends the game when the flag of the player to move falls
+    flag: Option<SpawnHandle>,
+    /// the game is over, the server is told and closes the board
+    over: bool,
+    server: Addr<Syn, OthelloActor>,
+    metrics: Arc<Metrics>,
+}
+
+impl BoardActor {
+    pub fn new(
+        board_id: String,
+        brd: BoardData,
+        players: Vec<Player>,
+        server: Addr<Syn, OthelloActor>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        BoardActor {
+            board_id,
+            brd,
+            players: players
+                .into_iter()
+                .map(|player| (player.sess_id.clone(), player))
+                .collect(),
+            spectators: HashMap::new(),
+            flag: None,
+            over: false,
+            server,
+            metrics,
+        }
+    }
+
+    /// The players and the spectators who can be sent a message
+    fn members(&self) -> Vec<(&str, &Member)> {
+        self.players
+            .values()
+            .filter_map(|player| {
+                player
+                    .member
+                    .as_ref()
+                    .map(|member| (player.sess_id.as_str(), member))
+            })
+            .chain(self.spectators.iter().map(|(id, member)| (id.as_str(), member)))
+            .collect()
+    }
+
+    fn send(&self, resp: WsResponse, dest_id: &str) {
+        let member = match self.players.get(dest_id) {
+            Some(player) => player.member.as_ref(),
+            None => self.spectators.get(dest_id),
+        };
+        if let Some(member) = member {
+            let _ = member.addr.do_send(Outgoing::new(resp));
+        }
+    }
+
+    /// Tell the server the score, the players or the spectators changed
+    fn send_changed(&self) {
+        self.server
+            .do_send(BoardChanged(self.brd.summary(&self.board_id, self.spectators.len())));
+    }
+
+    /// Tell the player they are seated, with the last messages of the board
+    fn send_joined(&self, sess_id: &str) {
+        let seats = &self.brd.seats;
+        let color = match game::color(seats.player(sess_id)) {
+            Some(color) => color,
+            None => return,
+        };
+        let opponent = self.players.get(seats.opponent(sess_id));
+        let back = WsResponse::JoinedBoard(WsJoinedBoard {
+            session_id: sess_id.to_string(),
+            board_id: self.board_id.clone(),
+            color,
+            opponent: opponent.map(|opponent| opponent.nickname.clone()),
+            opponent_guest: opponent.map(|opponent| opponent.guest).unwrap_or(false),
+            invite_code: seats.invite_code.clone().filter(|_| seats.is_open()),
+            rating: self.players.get(sess_id).and_then(|player| player.rating),
+            opponent_rating: opponent.and_then(|opponent| opponent.rating),
+            rated: seats.settings.rated,
+        });
+        self.send(back, sess_id);
+        self.send_chat_history(sess_id);
+    }
+
+    /// Send the last messages of the board to the session
+    fn send_chat_history(&self, sess_id: &str) {
+        let muted = match self.players.get(sess_id) {
+            Some(player) => player.member.as_ref(),
+            None => self.spectators.get(sess_id),
+        }.map(|member| member.muted.as_slice());
+        if let Some(muted) = muted {
+            let back = WsResponse::ChatHistory(WsChatHistory {
+                session_id: sess_id.to_string(),
+                board_id: Some(self.board_id.clone()),
+                messages: self.brd.chat.messages(muted),
+            });
+            self.send(back, sess_id);
+        }
+    }
+
+    /// Send the clocks to the players and let the bot play first
+    fn start_game(&mut self, ctx: &mut Context<Self>) {
+        if let Some(clocks) = self.brd.clocks() {
+            for sess_id in &[&self.brd.seats.black, &self.brd.seats.white] {
+                let back = WsResponse::Clocks(WsBoardClocks {
+                    session_id: sess_id.to_string(),
+                    board_id: self.board_id.clone(),
+                    clocks: clocks.clone(),
+                });
+                self.send(back, sess_id);
+            }
+        }
+        self.schedule_flag(ctx);
+        if self.brd.seats.black == BOT_ID {
+            self.schedule_bot(ctx);
+        }
+        self.send_changed();
+    }
+
+    fn schedule_bot(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(Duration::from_millis(BOT_DELAY), |act, ctx| {
+            act.play_bot(ctx);
+        });
+    }
+
+    /// Play the move of the bot, if it is its turn
+    fn play_bot(&mut self, ctx: &mut Context<Self>) {
+        let now = Instant::now();
+        let player = self.brd.seats.player(BOT_ID);
+        if self.over || player == Cell::Empty || self.brd.game.is_over()
+            || self.brd.game.current_player() != player
+            || self.brd.flag_fallen(now).is_some()
+        {
+            return;
+        }
+        let pos = match bot::choose_move(self.brd.game.board(), player) {
+            Some(pos) => (pos % BOARD_SIZE, pos / BOARD_SIZE),
+            None => return,
+        };
+        if let Err(err) = self.brd.game.play(player, pos) {
+            error!("The bot played {:?} on {}: {:?}", pos, self.board_id, err);
+            return;
+        }
+        self.brd.punch_clock(now);
+        let opponent_id = self.brd.seats.opponent(BOT_ID).to_string();
+        self.forward_move(&opponent_id, pos);
+        if self.brd.game.is_over() {
+            let winner = self.brd.game.winner();
+            self.end_game(winner, GameOverReason::Normal, ctx);
+            return;
+        }
+        self.schedule_flag(ctx);
+        // the opponent passes
+        if self.brd.game.current_player() == player {
+            self.schedule_bot(ctx);
+        }
+    }
+
+    /// Send a move to the opponent of its player and to the spectators, with
+    /// the clocks, and the clocks to the player
+    fn forward_move(&self, opponent_id: &str, pos: (usize, usize)) {
+        let player_id = self.brd.seats.opponent(opponent_id);
+        let clocks = self.brd.clocks();
+        self.metrics.count_move();
+        let watching = self.spectators.keys().map(|id| id.as_str());
+        for sess_id in watching.chain(Some(opponent_id)) {
+            let back = WsResponse::PlayedBoard(WsPlayBoard {
+                session_id: sess_id.to_string(),
+                board_id: self.board_id.clone(),
+                pos,
+                clocks: clocks.clone(),
+            });
+            self.send(back, sess_id);
+        }
+        if let Some(clocks) = clocks {
+            let back = WsResponse::Clocks(WsBoardClocks {
+                session_id: player_id.to_string(),
+                board_id: self.board_id.clone(),
+                clocks,
+            });
+            self.send(back, player_id);
+        }
+        self.send_changed();
+    }
+
+    /// End the game when the flag of the player to move falls, unless they
+    /// move before
+    fn schedule_flag(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.flag.take() {
+            ctx.cancel_future(handle);
+        }
+        let time_to_flag = match self.brd.clock {
+            Some(ref clock) => clock.time_to_flag(Instant::now()),
+            None => None,
+        };
+        if let Some(time_to_flag) = time_to_flag {
+            let handle = ctx.run_later(time_to_flag, |act, ctx| {
+                act.flag = None;
+                act.check_flag(ctx);
+            });
+            self.flag = Some(handle);
+        }
+    }
+
+    /// End the game if the flag of the player to move fell
+    fn check_flag(&mut self, ctx: &mut Context<Self>) {
+        match self.brd.flag_fallen(Instant::now()) {
+            Some(player) => {
+                info!("The flag of {:?} fell on {}", player, self.board_id);
+                self.end_game(player.opposite(), GameOverReason::Timeout, ctx);
+            }
+            None => self.schedule_flag(ctx),
+        }
+    }
+
+    /// Tell the server the result, then the players and the spectators
+    fn end_game(&mut self, winner: Cell, reason: GameOverReason, ctx: &mut Context<Self>) {
+        if self.over {
+            return;
+        }
+        self.over = true;
+        info!("Game over on board {}: {:?}", self.board_id, reason);
+        if let Some(handle) = self.flag.take() {
+            ctx.cancel_future(handle);
+        }
+        let guest = |sess_id: &str| self.players.get(sess_id).map(|player| player.guest).unwrap_or(true);
+        let guests = (guest(&self.brd.seats.black), guest(&self.brd.seats.white));
+        // the server knows the game is over before the players offer a rematch
+        self.server.do_send(GameOver {
+            board_id: self.board_id.clone(),
+            winner,
+            reason,
+            record: self.brd.record(&self.board_id, winner, reason, guests),
+        });
+        let score = self.brd.game.score();
+        for (sess_id, member) in self.members() {
+            let result = WsResponse::GameResult(WsGameResult {
+                session_id: sess_id.to_string(),
+                board_id: self.board_id.clone(),
+                winner: game::color(winner),
+                score,
+                reason,
+            });
+            let _ = member.addr.do_send(Outgoing::new(result));
+        }
+    }
+
+    /// The color of the player, `None` if the game is over or has not started
+    fn playing(&self, sess_id: &str) -> Option<Cell> {
+        let player = self.brd.seats.player(sess_id);
+        if self.over || self.brd.seats.is_open() || player == Cell::Empty {
+            None
+        } else {
+            Some(player)
+        }
+    }
+
+    /// Tell the player their resignation or abort is refused
+    fn game_action_failed(&self, sess_id: &str, reason: &str) {
+        let back = WsResponse::GameActionFailed(WsGameActionFailed {
+            session_id: sess_id.to_string(),
+            board_id: self.board_id.clone(),
+            reason: reason.to_string(),
+        });
+        self.send(back, sess_id);
+    }
+
+    /// Tell the opponent and the spectators the player left or came back
+    fn send_presence(&self, sess_id: &str, back: bool) {
+        let color = game::color(self.brd.seats.player(sess_id)).unwrap_or(Color::Black);
+        let opponent_id = self.brd.seats.opponent(sess_id);
+        let watching = self.spectators.keys().map(|id| id.as_str());
+        for dest_id in watching.chain(Some(opponent_id)) {
+            let presence = WsOpponentPresence {
+                session_id: dest_id.to_string(),
+                board_id: self.board_id.clone(),
+                color,
+            };
+            let resp = if back {
+                WsResponse::OpponentReconnected(presence)
+            } else {
+                WsResponse::OpponentReconnecting(presence)
+            };
+            self.send(resp, dest_id);
+        }
+    }
+
+    /// Tell the players and the spectators how many sessions watch the board
+    fn send_spectators_count(&self) {
+        for (sess_id, member) in self.members() {
+            let back = WsResponse::Spectators(WsSpectators {
+                session_id: sess_id.to_string(),
+                board_id: self.board_id.clone(),
+                count: self.spectators.len(),
+            });
+            let _ = member.addr.do_send(Outgoing::new(back));
+        }
+    }
+}
+
+impl Actor for BoardActor {
+    type Context = Context<Self>;
+
+    /// The players seated by the server learn their board
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let seated: Vec<String> = self.players.keys().cloned().collect();
+        for sess_id in &seated {
+            self.send_joined(sess_id);
+        }
+        if self.brd.seats.is_open() {
+            self.send_changed();
+        } else {
+            self.start_game(ctx);
+        }
+    }
+}
+
+impl Handler<Join> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Join, ctx: &mut Context<Self>) {
+        let Join { player, seats } = msg;
+        let creator_id = self.brd.seats.creator().to_string();
+        if self.over || !self.brd.join(seats) {
+            error!("{} cannot join the board {}", player.sess_id, self.board_id);
+            return;
+        }
+        // notify the creator of the board the game starts
+        let back = WsResponse::OpponentJoinedBoard(WsOpponentJoinedBoard {
+            session_id: creator_id.clone(),
+            board_id: self.board_id.clone(),
+            opponent: player.nickname.clone(),
+            opponent_guest: player.guest,
+            opponent_rating: player.rating,
+        });
+        self.send(back, &creator_id);
+        let sess_id = player.sess_id.clone();
+        self.players.insert(sess_id.clone(), player);
+        self.send_joined(&sess_id);
+        self.start_game(ctx);
+    }
+}
+
+impl Handler<Play> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Play, ctx: &mut Context<Self>) {
+        let Play { sess_id, param } = msg;
+        let now = Instant::now();
+        if !self.over {
+            if let Some(player) = self.brd.flag_fallen(now) {
+                // the move came too late, before the flag has been checked
+                self.end_game(player.opposite(), GameOverReason::Timeout, ctx);
+                return;
+            }
+        }
+        let brd = &mut self.brd;
+        let player = brd.seats.player(&sess_id);
+        let played = if player == Cell::Empty {
+            error!("Session {} is playing on a board it does not seat", sess_id);
+            Err(("not seated at this board".to_string(), None))
+        } else if brd.seats.is_open() {
+            Err(("waiting for an opponent".to_string(), Some(brd.game.state())))
+        } else if self.over {
+            Err(("the game is over".to_string(), Some(brd.game.state())))
+        } else {
+            match brd.game.play(player, param.pos) {
+                Ok(()) => {
+                    brd.punch_clock(now);
+                    Ok(brd.seats.opponent(&sess_id).to_string())
+                }
+                Err(err) => {
+                    warn!("Move {:?} rejected: {:?}", param.pos, err);
+                    Err((err.reason().to_string(), Some(brd.game.state())))
+                }
+            }
+        };
+        match played {
+            Ok(opponent_id) => {
+                info!("Forwarding the move");
+                self.forward_move(&opponent_id, param.pos);
+                if self.brd.game.is_over() {
+                    let winner = self.brd.game.winner();
+                    self.end_game(winner, GameOverReason::Normal, ctx);
+                } else {
+                    self.schedule_flag(ctx);
+                    if opponent_id == BOT_ID {
+                        self.schedule_bot(ctx);
+                    }
+                }
+            }
+            Err((reason, board)) => {
+                let back = WsResponse::RejectedMove(WsRejectedMove {
+                    session_id: sess_id.clone(),
+                    board_id: param.board_id,
+                    pos: param.pos,
+                    reason,
+                    board,
+                });
+                self.send(back, &sess_id);
+            }
+        }
+    }
+}
+
+impl Handler<Watch> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Watch, _: &mut Context<Self>) {
+        if self.over {
+            let back = WsResponse::WatchFailed(WsWatchFailed {
+                session_id: msg.sess_id.clone(),
+                board_id: self.board_id.clone(),
+                reason: "the game is over".to_string(),
+            });
+            let _ = msg.member.addr.do_send(Outgoing::new(back));
+            return;
+        }
+        let sess_id = msg.sess_id;
+        self.spectators.insert(sess_id.clone(), msg.member);
+        info!("Session {} is watching {}", sess_id, self.board_id);
+        let seats = &self.brd.seats;
+        let back = WsResponse::WatchedBoard(WsWatchedBoard {
+            session_id: sess_id.clone(),
+            board_id: self.board_id.clone(),
+            black: seats.black_name.clone(),
+            white: seats.white_name.clone(),
+            state: self.brd.game.state(),
+            moves: self.brd.game.moves().iter().map(|m| m.pos).collect(),
+            spectators: self.spectators.len(),
+            clocks: self.brd.clocks(),
+        });
+        self.send(back, &sess_id);
+        self.send_chat_history(&sess_id);
+        self.send_spectators_count();
+        self.send_changed();
+    }
+}
+
+impl Handler<Unwatch> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unwatch, _: &mut Context<Self>) {
+        if self.spectators.remove(&msg.sess_id).is_some() {
+            info!("Session {} stopped watching {}", msg.sess_id, self.board_id);
+            self.send_spectators_count();
+            self.send_changed();
+        }
+    }
+}
+
+impl Handler<Chat> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Chat, _: &mut Context<Self>) {
+        self.brd.chat.push(&msg.sess_id, msg.message.clone());
+        for (dest_id, member) in self.members() {
+            if member.muted.contains(&msg.sess_id) {
+                continue;
+            }
+            let back = WsResponse::Chat(WsChat {
+                session_id: dest_id.to_string(),
+                board_id: Some(self.board_id.clone()),
+                message: msg.message.clone(),
+            });
+            let _ = member.addr.do_send(Outgoing::new(back));
+        }
+    }
+}
+
+impl Handler<Mute> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Mute, _: &mut Context<Self>) {
+        let member = match self.players.get_mut(&msg.sess_id) {
+            Some(player) => player.member.as_mut(),
+            None => self.spectators.get_mut(&msg.sess_id),
+        };
+        if let Some(member) = member {
+            member.muted = msg.muted;
+        }
+    }
+}
+
+impl Handler<Resign> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Resign, ctx: &mut Context<Self>) {
+        match self.playing(&msg.sess_id) {
+            Some(player) => {
+                info!("Session {} resigns on {}", msg.sess_id, self.board_id);
+                self.end_game(player.opposite(), GameOverReason::Resignation, ctx);
+            }
+            None => self.game_action_failed(&msg.sess_id, "you are not playing on this board"),
+        }
+    }
+}
+
+impl Handler<Abort> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Abort, ctx: &mut Context<Self>) {
+        let refused = if self.playing(&msg.sess_id).is_none() {
+            Some("you are not playing on this board")
+        } else if self.brd.seats.tournament.is_some() {
+            Some("a tournament game can only be resigned")
+        } else if !self.brd.can_abort() {
+            Some("both players have moved, the game can only be resigned")
+        } else {
+            None
+        };
+        if let Some(reason) = refused {
+            self.game_action_failed(&msg.sess_id, reason);
+            return;
+        }
+        info!("Session {} aborts the game on {}", msg.sess_id, self.board_id);
+        self.end_game(Cell::Empty, GameOverReason::Aborted, ctx);
+    }
+}
+
+impl Handler<EndGame> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: EndGame, ctx: &mut Context<Self>) {
+        self.end_game(msg.winner, msg.reason, ctx);
+    }
+}
+
+impl Handler<Away> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Away, _: &mut Context<Self>) {
+        if !self.over {
+            self.send_presence(&msg.sess_id, false);
+        }
+    }
+}
+
+impl Handler<Back> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Back, _: &mut Context<Self>) {
+        let Back {
+            sess_id,
+            addr,
+            mut resumed,
+        } = msg;
+        if let Some(member) = self.players
+            .get_mut(&sess_id)
+            .and_then(|player| player.member.as_mut())
+        {
+            member.addr = addr;
+        }
+        let seats = &self.brd.seats;
+        let player = seats.player(&sess_id);
+        // the game ended meanwhile, its result has been sent
+        if !self.over && player != Cell::Empty {
+            let opponent_name = match player {
+                Cell::Black => &seats.white_name,
+                _ => &seats.black_name,
+            };
+            resumed.board = Some(WsResumedBoard {
+                board_id: self.board_id.clone(),
+                color: game::color(player).unwrap_or(Color::Black),
+                opponent: Some(opponent_name.clone()),
+                state: self.brd.game.state(),
+                moves: self.brd.game.moves().iter().map(|m| m.pos).collect(),
+                clocks: self.brd.clocks(),
+                rated: seats.settings.rated,
+            });
+        }
+        let resumed_game = resumed.board.is_some();
+        self.send(WsResponse::Resumed(resumed), &sess_id);
+        if resumed_game {
+            self.send_presence(&sess_id, true);
+            self.send_chat_history(&sess_id);
+        }
+    }
+}
+
+impl Handler<Close> for BoardActor {
+    type Result = ();
+
+    fn handle(&mut self, _: Close, ctx: &mut Context<Self>) {
+        ctx.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix::SystemRunner;
+    use config::Config;
+    use wscommand::{ClockMode, ColorPreference, TimeControl};
+
+    /// A websocket session keeping what it is sent
+    #[derive(Default)]
+    struct Client {
+        received: Vec<WsResponse>,
+    }
+
+    impl Actor for Client {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<Outgoing> for Client {
+        type Result = ();
+
+        fn handle(&mut self, msg: Outgoing, _: &mut Context<Self>) {
+            self.received.push(msg.resp);
+        }
+    }
+
+    /// The responses received since the last time
+    struct Received;
+
+    impl Message for Received {
+        type Result = Vec<WsResponse>;
+    }
+
+    impl Handler<Received> for Client {
+        type Result = MessageResult<Received>;
+
+        fn handle(&mut self, _: Received, _: &mut Context<Self>) -> Self::Result {
+            MessageResult(self.received.drain(..).collect())
+        }
+    }
+
+    /// A board actor and the clients of its players, bob plays black
+    struct Table {
+        sys: SystemRunner,
+        board: Addr<Syn, BoardActor>,
+        alice: Addr<Syn, Client>,
+        bob: Addr<Syn, Client>,
+    }
+
+    impl Table {
+        /// Start the game, the players are told they joined the board
+        fn new(time_control: Option<TimeControl>) -> Table {
+            Table::with_game(time_control, |_| {})
+        }
+
+        /// Start the game once `prepare` has set it up
+        fn with_game<F>(time_control: Option<TimeControl>, prepare: F) -> Table
+        where
+            F: FnOnce(&mut BoardData),
+        {
+            let sys = System::new("test");
+            let settings = WsBoardSettings {
+                time_control,
+                ..WsBoardSettings::default()
+            };
+            let mut seats = Seats::new("alice-id".into(), "alice".into(), Color::White, settings);
+            seats.take("bob-id".into(), "bob".into());
+            let mut brd = BoardData::new(seats);
+            prepare(&mut brd);
+            let alice: Addr<Syn, _> = Client::default().start();
+            let bob: Addr<Syn, _> = Client::default().start();
+            let player = |sess_id: &str, nickname: &str, client: &Addr<Syn, Client>| Player {
+                sess_id: sess_id.to_string(),
+                nickname: nickname.to_string(),
+                guest: true,
+                rating: None,
+                member: Some(Member {
+                    addr: client.clone().recipient(),
+                    muted: Vec::new(),
+                }),
+            };
+            let players = vec![player("alice-id", "alice", &alice), player("bob-id", "bob", &bob)];
+            let server: Addr<Syn, _> =
+                OthelloActor::new(&Config::default(), None, None, Arc::default()).start();
+            let board: Addr<Syn, _> =
+                BoardActor::new("board".into(), brd, players, server, Arc::default()).start();
+            let mut table = Table {
+                sys,
+                board,
+                alice,
+                bob,
+            };
+            // the actor has started once it handles a message
+            table.send(Unwatch {
+                sess_id: String::new(),
+            });
+            for client in &[table.alice.clone(), table.bob.clone()] {
+                match table.received(client).first() {
+                    Some(&WsResponse::JoinedBoard(_)) => {}
+                    other => panic!("the player is told they joined the board: {:?}", other),
+                }
+            }
+            table
+        }
+
+        /// Send the message to the board and wait for it to be handled
+        fn send<M>(&mut self, msg: M)
+        where
+            M: Message<Result = ()> + Send + 'static,
+            BoardActor: Handler<M>,
+        {
+            self.sys.run_until_complete(self.board.send(msg)).unwrap();
+        }
+
+        fn received(&mut self, client: &Addr<Syn, Client>) -> Vec<WsResponse> {
+            self.sys.run_until_complete(client.send(Received)).unwrap()
+        }
+
+        fn play(&mut self, sess_id: &str, pos: (usize, usize)) {
+            self.send(Play {
+                sess_id: sess_id.to_string(),
+                param: WsPlayBoard {
+                    session_id: sess_id.to_string(),
+                    board_id: "board".to_string(),
+                    pos,
+                    clocks: None,
+                },
+            });
+        }
+    }
+
+    /// The results of the game received by a client: winner and reason
+    fn results(received: &[WsResponse]) -> Vec<(Option<Color>, GameOverReason)> {
+        received
+            .iter()
+            .filter_map(|resp| match *resp {
+                WsResponse::GameResult(ref result) => Some((result.winner, result.reason)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The reasons of the refused resignations and aborts
+    fn failures(received: &[WsResponse]) -> Vec<String> {
+        received
+            .iter()
+            .filter_map(|resp| match *resp {
+                WsResponse::GameActionFailed(ref failed) => Some(failed.reason.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_board_seats() {
+        let settings = WsBoardSettings {
+            color: ColorPreference::White,
+            time_control: Some(TimeControl {
+                minutes: 5,
+                increment: 3,
+                mode: ClockMode::Fischer,
+            }),
+            ..WsBoardSettings::default()
+        };
+        let mut seats = Seats::new("alice-id".into(), "alice".into(), Color::White, settings);
+        assert_eq!(seats.free_seat(), Some(Color::Black));
+        assert_eq!(seats.creator(), "alice-id");
+        assert_eq!(seats.player("alice-id"), Cell::White);
+        assert_eq!(seats.player(""), Cell::Empty);
+        let open = seats.open_board("board", true).unwrap();
+        assert_eq!(open.creator, "alice");
+        assert!(open.creator_guest);
+        assert_eq!(open.color, Color::Black);
+
+        let mut brd = BoardData::new(seats.clone());
+        let created_at = brd.game.started_at();
+        assert!(brd.clocks().is_none());
+        assert!(!brd.join(seats.clone()));
+        assert_eq!(seats.take("bob-id".into(), "bob".into()), Some(Color::Black));
+        assert!(!seats.is_open());
+        assert!(seats.open_board("board", false).is_none());
+        assert_eq!(seats.take("carol-id".into(), "carol".into()), None);
+        assert_eq!(seats.player("bob-id"), Cell::Black);
+        assert_eq!(seats.opponent("bob-id"), "alice-id");
+
+        // the clock of black starts with the game
+        assert!(brd.join(seats.clone()));
+        assert!(!brd.join(seats.clone()));
+        assert_eq!(brd.game.started_at(), created_at);
+        let clocks = brd.clocks().unwrap();
+        assert_eq!(clocks.running, Some(Color::Black));
+        assert_eq!(clocks.white, 5 * 60 * 1000);
+        assert_eq!(BoardData::new(seats).clocks().unwrap().running, Some(Color::Black));
+
+        // the game can be aborted until both players have moved
+        assert!(brd.can_abort());
+        brd.game.play(Cell::Black, (4, 2)).unwrap();
+        assert!(brd.can_abort());
+        brd.game.play(Cell::White, (3, 2)).unwrap();
+        assert!(!brd.can_abort());
+        let summary = brd.summary("board", 2);
+        assert_eq!(summary.status, BoardStatus::Playing);
+        assert_eq!(summary.moves, 2);
+        assert_eq!(summary.spectators, 2);
+
+        let record = brd.record("board", Cell::White, GameOverReason::Normal, (false, true));
+        assert_eq!(record.black, "bob");
+        assert_eq!(record.white, "alice");
+        assert!(record.white_guest);
+        assert_eq!(record.time_control, Some("5+3".to_string()));
+        assert!(!record.rated);
+
+        // a guest is not rated
+        brd.seats.settings.rated = true;
+        assert!(brd.record("board", Cell::White, GameOverReason::Normal, (false, false)).rated);
+        assert!(!brd.record("board", Cell::White, GameOverReason::Normal, (false, true)).rated);
+    }
+
+    #[test]
+    fn test_play() {
+        let mut table = Table::new(None);
+        table.play("bob-id", (4, 2));
+        let alice = table.alice.clone();
+        match table.received(&alice).as_slice() {
+            [WsResponse::PlayedBoard(played)] => {
+                assert_eq!(played.session_id, "alice-id");
+                assert_eq!(played.pos, (4, 2));
+                assert!(played.clocks.is_none());
+            }
+            other => panic!("alice is sent the move: {:?}", other),
+        }
+        let bob = table.bob.clone();
+        assert!(table.received(&bob).is_empty());
+
+        // not the turn of bob anymore
+        table.play("bob-id", (3, 2));
+        match table.received(&bob).as_slice() {
+            [WsResponse::RejectedMove(rejected)] => {
+                assert_eq!(rejected.pos, (3, 2));
+                assert!(rejected.board.is_some());
+            }
+            other => panic!("the move of bob is rejected: {:?}", other),
+        }
+        assert!(table.received(&alice).is_empty());
+    }
+
+    #[test]
+    fn test_resign() {
+        let mut table = Table::new(None);
+        table.send(Resign {
+            sess_id: "bob-id".into(),
+        });
+        let (alice, bob) = (table.alice.clone(), table.bob.clone());
+        let resigned = vec![(Some(Color::White), GameOverReason::Resignation)];
+        assert_eq!(results(&table.received(&alice)), resigned);
+        assert_eq!(results(&table.received(&bob)), resigned);
+
+        // the game is over
+        table.send(Resign {
+            sess_id: "alice-id".into(),
+        });
+        table.play("bob-id", (4, 2));
+        let received = table.received(&alice);
+        assert!(results(&received).is_empty());
+        assert_eq!(failures(&received), vec!["you are not playing on this board"]);
+        match table.received(&bob).as_slice() {
+            [WsResponse::RejectedMove(rejected)] => assert_eq!(rejected.reason, "the game is over"),
+            other => panic!("the game is over: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_abort() {
+        let mut table = Table::new(None);
+        table.play("bob-id", (4, 2));
+        table.send(Abort {
+            sess_id: "alice-id".into(),
+        });
+        let (alice, bob) = (table.alice.clone(), table.bob.clone());
+        assert_eq!(results(&table.received(&alice)), vec![(None, GameOverReason::Aborted)]);
+        assert_eq!(results(&table.received(&bob)), vec![(None, GameOverReason::Aborted)]);
+
+        // both players have moved
+        let mut table = Table::new(None);
+        table.play("bob-id", (4, 2));
+        table.play("alice-id", (3, 2));
+        table.send(Abort {
+            sess_id: "bob-id".into(),
+        });
+        let (alice, bob) = (table.alice.clone(), table.bob.clone());
+        let received = table.received(&bob);
+        assert!(results(&received).is_empty());
+        assert_eq!(
+            failures(&received),
+            vec!["both players have moved, the game can only be resigned"]
+        );
+        table.received(&alice);
+        table.send(Abort {
+            sess_id: "carol-id".into(),
+        });
+        assert!(table.received(&alice).is_empty());
+        assert!(table.received(&bob).is_empty());
+    }
+
+    #[test]
+    fn test_away_and_back() {
+        let mut table = Table::new(None);
+        table.play("bob-id", (4, 2));
+        let (alice, bob) = (table.alice.clone(), table.bob.clone());
+        table.received(&alice);
+        table.send(Away {
+            sess_id: "bob-id".into(),
+        });
+        match table.received(&alice).as_slice() {
+            [WsResponse::OpponentReconnecting(presence)] => {
+                assert_eq!(presence.color, Color::Black)
+            }
+            other => panic!("alice is told bob left: {:?}", other),
+        }
+
+        // bob comes back from another websocket
+        let websocket: Addr<Syn, _> = Client::default().start();
+        table.send(Back {
+            sess_id: "bob-id".into(),
+            addr: websocket.clone().recipient(),
+            resumed: WsResumed {
+                session_id: "bob-id".into(),
+                nickname: Some("bob".into()),
+                users_count: 2,
+                board: None,
+            },
+        });
+        match table.received(&alice).as_slice() {
+            [WsResponse::OpponentReconnected(presence)] => assert_eq!(presence.color, Color::Black),
+            other => panic!("alice is told bob is back: {:?}", other),
+        }
+        match table.received(&websocket).as_slice() {
+            [WsResponse::Resumed(resumed), WsResponse::ChatHistory(_)] => {
+                let board = resumed.board.as_ref().unwrap();
+                assert_eq!(board.color, Color::Black);
+                assert_eq!(board.opponent, Some("alice".to_string()));
+                assert_eq!(board.moves, vec![20]);
+            }
+            other => panic!("bob resumes the game: {:?}", other),
+        }
+        assert!(table.received(&bob).is_empty());
+
+        // the game goes on with the new websocket
+        table.play("alice-id", (3, 2));
+        assert_eq!(table.received(&websocket).len(), 1);
+    }
+
+    #[test]
+    fn test_flag_fallen() {
+        let time_control = TimeControl {
+            minutes: 1,
+            increment: 0,
+            mode: ClockMode::Fischer,
+        };
+        let mut table = Table::with_game(Some(time_control), |brd| {
+            let mut clock = GameClock::new(time_control);
+            clock.start(Cell::Black, Instant::now() - Duration::from_secs(61));
+            brd.clock = Some(clock);
+        });
+        // the flag fell before the move, checked or not yet
+        table.play("bob-id", (4, 2));
+        let alice = table.alice.clone();
+        let received = table.received(&alice);
+        // the result only, not the move
+        assert_eq!(received.len(), 1);
+        assert_eq!(results(&received), vec![(Some(Color::White), GameOverReason::Timeout)]);
+    }
+
+    #[test]
+    fn test_close() {
+        let mut table = Table::new(None);
+        table.send(Close);
+        let board = table.board.clone();
+        let unwatch = board.send(Unwatch {
+            sess_id: "carol-id".into(),
+        });
+        assert!(table.sys.run_until_complete(unwatch).is_err());
+        let alice = table.alice.clone();
+        assert!(table.received(&alice).is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+//! The opponent of the players nobody came to play with.
+//!
+//! It plays at once, one ply deep with the engine of the front ends: the
+//! value of the squares and the moves left to each player.
+
+use engine;
+use engine::model::{BoardModel, Cell};
+
+/// The session id of the bot, no session id is that short
+pub const BOT_ID: &str = "bot";
+/// The nickname of the bot
+pub const BOT_NAME: &str = "Bot";
+
+/// The square the bot plays, `None` if it has to pass
+pub fn choose_move(board: &BoardModel, player: Cell) -> Option<usize> {
+    engine::best_move(board, player, 1).map(|(pos, _)| pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_corner() {
+        let board = BoardModel::from_string(
+            r#"
+            . W B . . . . .
+            . . . . . . . .
+            . . . . . . . .
+            . . . W B . . .
+            . . . B W . . .
+            . . . . . . . .
+            . . . . . . . .
+            . . . . . . . .
+            "#,
+        );
+        assert_eq!(choose_move(&board, Cell::Black), Some(0));
+    }
+
+    #[test]
+    fn test_choose_legal_move() {
+        let board = BoardModel::new();
+        let pos = choose_move(&board, Cell::Black).unwrap();
+        assert!(board.get_possibilities(Cell::Black).contains(&pos));
+    }
+
+    #[test]
+    fn test_pass() {
+        let board = BoardModel::from_string(
+            r#"
+            B B B B B B B B
+            B B B B B B B B
+            B B B B B B B B
+            B B B B B B B B
+            B B B B B B B B
+            B B B B B B B B
+            B B B B B B B B
+            B B B B B B B .
+            "#,
+        );
+        assert_eq!(choose_move(&board, Cell::White), None);
+    }
+}
@@ -0,0 +1,117 @@
+//! The chat of the boards and of the lobby.
+//!
+//! The server keeps the last messages of each board and of the lobby, to send
+//! them to the users who arrive, and limits how fast a session can talk.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use validation::RateLimit;
+use wscommand::WsChatMessage;
+
+/// The longest message, in characters
+pub const MAX_MESSAGE_LEN: usize = 300;
+/// The messages kept for the users who arrive
+const HISTORY_LEN: usize = 50;
+/// A session sends at most `RATE_MESSAGES` messages per `RATE_WINDOW` seconds
+const RATE_MESSAGES: usize = 5;
+const RATE_WINDOW: u64 = 10;
+
+/// The text to send once trimmed, or why it is refused
+pub fn check_text(text: &str) -> Result<String, &'static str> {
+    let text = text.trim();
+    if text.is_empty() {
+        Err("the message is empty")
+    } else if text.chars().count() > MAX_MESSAGE_LEN {
+        Err("the message is too long")
+    } else {
+        Ok(text.chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect())
+    }
+}
+
+/// The messages recently sent by a session
+pub fn rate_limit() -> RateLimit {
+    RateLimit::new(RATE_MESSAGES, Duration::from_secs(RATE_WINDOW))
+}
+
+/// The last messages of a board or of the lobby, with the session of their
+/// author
+#[derive(Default)]
+pub struct ChatHistory {
+    messages: VecDeque<(String, WsChatMessage)>,
+}
+
+impl ChatHistory {
+    pub fn push(&mut self, author_id: &str, message: WsChatMessage) {
+        if self.messages.len() >= HISTORY_LEN {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((author_id.to_string(), message));
+    }
+
+    /// The messages but the ones of the `muted` sessions, the oldest first
+    pub fn messages(&self, muted: &[String]) -> Vec<WsChatMessage> {
+        self.messages
+            .iter()
+            .filter(|(author_id, _)| !muted.contains(author_id))
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn message(text: &str) -> WsChatMessage {
+        WsChatMessage {
+            author: "alice".to_string(),
+            text: text.to_string(),
+            sent_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_check_text() {
+        assert_eq!(check_text("  hello "), Ok("hello".to_string()));
+        assert_eq!(check_text("good\ngame"), Ok("good game".to_string()));
+        assert!(check_text(" \t").is_err());
+        let long: String = "é".repeat(MAX_MESSAGE_LEN);
+        assert_eq!(check_text(&long), Ok(long.clone()));
+        assert!(check_text(&(long + "!")).is_err());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut limit = rate_limit();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        for i in 0..RATE_MESSAGES {
+            assert!(limit.allow(at(i as u64)));
+        }
+        assert!(!limit.allow(at(RATE_MESSAGES as u64)));
+        // the first message is out of the window, the refused one is not counted
+        assert!(limit.allow(at(RATE_WINDOW)));
+        assert!(!limit.allow(at(RATE_WINDOW)));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = ChatHistory::default();
+        for i in 0..HISTORY_LEN + 2 {
+            let author_id = if i % 2 == 0 { "alice" } else { "bob" };
+            history.push(author_id, message(&i.to_string()));
+        }
+        let messages = history.messages(&[]);
+        assert_eq!(messages.len(), HISTORY_LEN);
+        assert_eq!(messages[0].text, "2");
+        assert_eq!(messages[HISTORY_LEN - 1].text, (HISTORY_LEN + 1).to_string());
+
+        let muted = history.messages(&["bob".to_string()]);
+        assert_eq!(muted.len(), HISTORY_LEN / 2);
+        assert!(muted.iter().all(|message| message.text.parse::<usize>().unwrap() % 2 == 0));
+    }
+}
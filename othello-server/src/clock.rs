@@ -0,0 +1,192 @@
+//! The clocks of the players, kept by the server.
+//!
+//! Only the clock of the player to move runs. It is punched when the move is
+//! received: the time used is taken from the player, then the increment of
+//! the time control is given back.
+
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use game;
+use engine::model::Cell;
+use wscommand::{ClockMode, TimeControl, WsClocks};
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+pub struct GameClock {
+    control: TimeControl,
+    /// the time left to the black player, when their clock was last started
+    black: Duration,
+    /// the time left to the white player, when their clock was last started
+    white: Duration,
+    /// the player whose clock runs, and since when
+    running: Option<(Cell, Instant)>,
+}
+
+impl GameClock {
+    /// Both clocks stopped, with the time of the game
+    pub fn new(control: TimeControl) -> GameClock {
+        let time = Duration::from_secs(u64::from(control.minutes) * 60);
+        GameClock {
+            control,
+            black: time,
+            white: time,
+            running: None,
+        }
+    }
+
+    fn left(&self, player: Cell) -> Duration {
+        match player {
+            Cell::Black => self.black,
+            Cell::White => self.white,
+            Cell::Empty => Duration::from_secs(0),
+        }
+    }
+
+    fn set_left(&mut self, player: Cell, left: Duration) {
+        match player {
+            Cell::Black => self.black = left,
+            Cell::White => self.white = left,
+            Cell::Empty => {}
+        }
+    }
+
+    /// Start the clock of the player to move
+    pub fn start(&mut self, player: Cell, now: Instant) {
+        self.running = Some((player, now));
+    }
+
+    /// The time left to the player at `now`, zero once their flag fell
+    pub fn remaining(&self, player: Cell, now: Instant) -> Duration {
+        let left = self.left(player);
+        match self.running {
+            Some((running, since)) if running == player => left
+                .checked_sub(now.duration_since(since))
+                .unwrap_or_default(),
+            _ => left,
+        }
+    }
+
+    /// Stop the clock of the player who moved, then start the one of `next`,
+    /// `Cell::Empty` once the game is over. Fails with the player whose flag
+    /// fell before the move.
+    pub fn punch(&mut self, next: Cell, now: Instant) -> Result<(), Cell> {
+        if let Some((player, since)) = self.running {
+            let used = now.duration_since(since);
+            let left = match self.left(player).checked_sub(used) {
+                Some(left) if left > Duration::from_secs(0) => left,
+                _ => return Err(player),
+            };
+            let increment = Duration::from_secs(u64::from(self.control.increment));
+            let bonus = match self.control.mode {
+                ClockMode::Fischer => increment,
+                ClockMode::Bronstein => cmp::min(used, increment),
+            };
+            self.set_left(player, left + bonus);
+        }
+        self.running = if next == Cell::Empty {
+            None
+        } else {
+            Some((next, now))
+        };
+        Ok(())
+    }
+
+    /// The player whose flag fell, if any
+    pub fn flag_fallen(&self, now: Instant) -> Option<Cell> {
+        match self.running {
+            Some((player, _)) if self.remaining(player, now) == Duration::from_secs(0) => {
+                Some(player)
+            }
+            _ => None,
+        }
+    }
+
+    /// How long until the flag of the running clock falls
+    pub fn time_to_flag(&self, now: Instant) -> Option<Duration> {
+        self.running
+            .map(|(player, _)| self.remaining(player, now))
+    }
+
+    /// The clocks sent to the clients
+    pub fn clocks(&self, now: Instant) -> WsClocks {
+        WsClocks {
+            black: millis(self.remaining(Cell::Black, now)),
+            white: millis(self.remaining(Cell::White, now)),
+            running: self.running
+                .and_then(|(player, _)| game::color(player)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(minutes: u32, increment: u32, mode: ClockMode) -> GameClock {
+        GameClock::new(TimeControl {
+            minutes,
+            increment,
+            mode,
+        })
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_sudden_death() {
+        let mut clock = clock(1, 0, ClockMode::Fischer);
+        let start = Instant::now();
+        assert_eq!(clock.time_to_flag(start), None);
+        clock.start(Cell::Black, start);
+        assert_eq!(clock.punch(Cell::White, start + secs(20)), Ok(()));
+        assert_eq!(clock.remaining(Cell::Black, start + secs(50)), secs(40));
+        assert_eq!(clock.remaining(Cell::White, start + secs(50)), secs(30));
+        assert_eq!(clock.time_to_flag(start + secs(50)), Some(secs(30)));
+        assert_eq!(clock.flag_fallen(start + secs(79)), None);
+        assert_eq!(clock.flag_fallen(start + secs(80)), Some(Cell::White));
+        assert_eq!(clock.punch(Cell::Black, start + secs(80)), Err(Cell::White));
+    }
+
+    #[test]
+    fn test_fischer() {
+        let mut clock = clock(1, 5, ClockMode::Fischer);
+        let start = Instant::now();
+        clock.start(Cell::Black, start);
+        assert_eq!(clock.punch(Cell::White, start + secs(2)), Ok(()));
+        // more time than at the start
+        assert_eq!(clock.remaining(Cell::Black, start + secs(2)), secs(63));
+        let clocks = clock.clocks(start + secs(3));
+        assert_eq!(clocks.black, 63_000);
+        assert_eq!(clocks.white, 59_000);
+        assert_eq!(clocks.running, Some(::wscommand::Color::White));
+    }
+
+    #[test]
+    fn test_bronstein() {
+        let mut clock = clock(1, 5, ClockMode::Bronstein);
+        let start = Instant::now();
+        clock.start(Cell::Black, start);
+        assert_eq!(clock.punch(Cell::White, start + secs(2)), Ok(()));
+        assert_eq!(clock.remaining(Cell::Black, start + secs(2)), secs(60));
+        assert_eq!(clock.punch(Cell::Black, start + secs(12)), Ok(()));
+        assert_eq!(clock.remaining(Cell::White, start + secs(12)), secs(55));
+    }
+
+    #[test]
+    fn test_pass_and_game_over() {
+        let mut clock = clock(1, 0, ClockMode::Fischer);
+        let start = Instant::now();
+        clock.start(Cell::Black, start);
+        // white passes, black moves again
+        assert_eq!(clock.punch(Cell::Black, start + secs(10)), Ok(()));
+        assert_eq!(clock.remaining(Cell::Black, start + secs(15)), secs(45));
+        assert_eq!(clock.punch(Cell::Empty, start + secs(20)), Ok(()));
+        assert_eq!(clock.time_to_flag(start + secs(100)), None);
+        assert_eq!(clock.clocks(start + secs(100)).running, None);
+    }
+}
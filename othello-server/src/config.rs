@@ -0,0 +1,349 @@
+//! Server settings.
+//!
+//! Every setting has a default value, overridden by the TOML configuration
+//! file, then by the `OTHELLO_*` environment variables, then by the command
+//! line flags.
+
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches};
+use toml;
+
+/// Environment variable of the configuration file, when `--config` is missing
+const CONFIG_ENV: &str = "OTHELLO_CONFIG";
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// addresses where the http server listen
+    pub listen: Vec<String>,
+    /// directory of the static files, the client application
+    pub static_dir: String,
+    /// seconds between two pings sent to the clients
+    pub heartbeat_interval: u64,
+    /// seconds of silence before a client is disconnected
+    pub client_timeout: u64,
+    /// seconds the seat of a disconnected player is held during a game
+    pub resume_grace: u64,
+    /// connections refused above this number of sessions
+    pub max_sessions: usize,
+    /// boards refused above this number of boards
+    pub max_boards: usize,
+    /// threads running the boards, the games spread over them
+    pub board_arbiters: usize,
+    /// seconds a player waits for a quick game before playing a bot, 0 to
+    /// wait for a human
+    pub bot_deadline: u64,
+    /// a `RUST_LOG` filter, `RUST_LOG` wins if it is set
+    pub log_level: String,
+    /// SQLite file where the finished games are saved, empty to save nothing
+    pub database: String,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            listen: vec!["[::1]:8080".to_string()],
+            static_dir: "static".to_string(),
+            heartbeat_interval: 5,
+            client_timeout: 15,
+            resume_grace: 30,
+            max_sessions: 1000,
+            max_boards: 500,
+            board_arbiters: 4,
+            bot_deadline: 30,
+            log_level: "info".to_string(),
+            database: "othello.sqlite".to_string(),
+        }
+    }
+}
+
+fn parse<T>(name: &str, value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|err| format!("Invalid value {:?} for {}: {}", value, name, err))
+}
+
+impl Config {
+    /// Read the settings from the command line, the file and the environment
+    pub fn load() -> Result<Config, String> {
+        let matches = app().get_matches();
+        let mut config = match matches
+            .value_of("config")
+            .map(|path| path.to_string())
+            .or_else(|| env::var(CONFIG_ENV).ok())
+        {
+            Some(path) => Config::from_file(&path)?,
+            None => Config::default(),
+        };
+        config.apply_env(|name| env::var(name).ok())?;
+        config.apply_args(&matches)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &str) -> Result<Config, String> {
+        let mut content = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .map_err(|err| format!("Cannot read {}: {}", path, err))?;
+        Config::from_toml(&content).map_err(|err| format!("Invalid file {}: {}", path, err))
+    }
+
+    pub fn from_toml(content: &str) -> Result<Config, String> {
+        toml::from_str(content).map_err(|err| err.to_string())
+    }
+
+    /// Override the settings with the `OTHELLO_*` variables found by `var`
+    pub fn apply_env<F>(&mut self, var: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(value) = var("OTHELLO_LISTEN") {
+            self.listen = value.split(',').map(|addr| addr.trim().to_string()).collect();
+        }
+        if let Some(value) = var("OTHELLO_STATIC_DIR") {
+            self.static_dir = value;
+        }
+        if let Some(value) = var("OTHELLO_HEARTBEAT_INTERVAL") {
+            self.heartbeat_interval = parse("OTHELLO_HEARTBEAT_INTERVAL", &value)?;
+        }
+        if let Some(value) = var("OTHELLO_CLIENT_TIMEOUT") {
+            self.client_timeout = parse("OTHELLO_CLIENT_TIMEOUT", &value)?;
+        }
+        if let Some(value) = var("OTHELLO_RESUME_GRACE") {
+            self.resume_grace = parse("OTHELLO_RESUME_GRACE", &value)?;
+        }
+        if let Some(value) = var("OTHELLO_MAX_SESSIONS") {
+            self.max_sessions = parse("OTHELLO_MAX_SESSIONS", &value)?;
+        }
+        if let Some(value) = var("OTHELLO_MAX_BOARDS") {
+            self.max_boards = parse("OTHELLO_MAX_BOARDS", &value)?;
+        }
+        if let Some(value) = var("OTHELLO_BOARD_ARBITERS") {
+            self.board_arbiters = parse("OTHELLO_BOARD_ARBITERS", &value)?;
+        }
+        if let Some(value) = var("OTHELLO_BOT_DEADLINE") {
+            self.bot_deadline = parse("OTHELLO_BOT_DEADLINE", &value)?;
+        }
+        if let Some(value) = var("OTHELLO_LOG_LEVEL") {
+            self.log_level = value;
+        }
+        if let Some(value) = var("OTHELLO_DATABASE") {
+            self.database = value;
+        }
+        Ok(())
+    }
+
+    /// Override the settings with the command line flags
+    pub fn apply_args(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        if let Some(values) = matches.values_of("listen") {
+            self.listen = values.map(|addr| addr.to_string()).collect();
+        }
+        if let Some(value) = matches.value_of("static-dir") {
+            self.static_dir = value.to_string();
+        }
+        if let Some(value) = matches.value_of("heartbeat-interval") {
+            self.heartbeat_interval = parse("--heartbeat-interval", value)?;
+        }
+        if let Some(value) = matches.value_of("client-timeout") {
+            self.client_timeout = parse("--client-timeout", value)?;
+        }
+        if let Some(value) = matches.value_of("resume-grace") {
+            self.resume_grace = parse("--resume-grace", value)?;
+        }
+        if let Some(value) = matches.value_of("max-sessions") {
+            self.max_sessions = parse("--max-sessions", value)?;
+        }
+        if let Some(value) = matches.value_of("max-boards") {
+            self.max_boards = parse("--max-boards", value)?;
+        }
+        if let Some(value) = matches.value_of("board-arbiters") {
+            self.board_arbiters = parse("--board-arbiters", value)?;
+        }
+        if let Some(value) = matches.value_of("bot-deadline") {
+            self.bot_deadline = parse("--bot-deadline", value)?;
+        }
+        if let Some(value) = matches.value_of("log-level") {
+            self.log_level = value.to_string();
+        }
+        if let Some(value) = matches.value_of("database") {
+            self.database = value.to_string();
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.listen.is_empty() {
+            return Err("At least one listen address is required".to_string());
+        }
+        if self.heartbeat_interval == 0 {
+            return Err("The heartbeat interval cannot be 0".to_string());
+        }
+        if self.client_timeout <= self.heartbeat_interval {
+            return Err("The client timeout must be longer than the heartbeat interval".to_string());
+        }
+        if self.board_arbiters == 0 {
+            return Err("At least one board arbiter is required".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_interval)
+    }
+
+    pub fn client_timeout(&self) -> Duration {
+        Duration::from_secs(self.client_timeout)
+    }
+
+    pub fn resume_grace(&self) -> Duration {
+        Duration::from_secs(self.resume_grace)
+    }
+
+    /// `None` when the quick games are never played against a bot
+    pub fn bot_deadline(&self) -> Option<Duration> {
+        if self.bot_deadline == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.bot_deadline))
+        }
+    }
+}
+
+/// The command line flags
+fn app<'a, 'b>() -> App<'a, 'b> {
+    let value = |name: &'a str, help: &'a str| {
+        Arg::with_name(name)
+            .long(name)
+            .value_name("VALUE")
+            .takes_value(true)
+            .help(help)
+    };
+    App::new("othello-server")
+        .about("Play othello online")
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("TOML configuration file, default to $OTHELLO_CONFIG"),
+        )
+        .arg(
+            Arg::with_name("listen")
+                .short("l")
+                .long("listen")
+                .value_name("ADDR")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Address to listen, may be repeated"),
+        )
+        .arg(value("static-dir", "Directory of the static files"))
+        .arg(value("heartbeat-interval", "Seconds between two pings"))
+        .arg(value("client-timeout", "Seconds before a silent client is dropped"))
+        .arg(value("resume-grace", "Seconds to wait for a disconnected player"))
+        .arg(value("max-sessions", "Maximum number of sessions"))
+        .arg(value("max-boards", "Maximum number of boards"))
+        .arg(value("board-arbiters", "Number of threads running the boards"))
+        .arg(value("bot-deadline", "Seconds before a bot plays a quick game, 0 to disable"))
+        .arg(value("log-level", "Log filter, as RUST_LOG"))
+        .arg(value("database", "SQLite file of the finished games, empty to disable"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            listen = ["0.0.0.0:80", "[::]:80"]
+            static_dir = "/srv/othello"
+            max_boards = 10
+            "#,
+        ).unwrap();
+        assert_eq!(config.listen, vec!["0.0.0.0:80", "[::]:80"]);
+        assert_eq!(config.static_dir, "/srv/othello");
+        assert_eq!(config.max_boards, 10);
+        // missing settings keep their default value
+        assert_eq!(config.max_sessions, Config::default().max_sessions);
+    }
+
+    #[test]
+    fn test_from_toml_unknown_field() {
+        assert!(Config::from_toml("max_board = 10").is_err());
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let mut config = Config::default();
+        config
+            .apply_env(|name| match name {
+                "OTHELLO_LISTEN" => Some("127.0.0.1:8080, [::1]:8080".to_string()),
+                "OTHELLO_CLIENT_TIMEOUT" => Some("60".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.listen, vec!["127.0.0.1:8080", "[::1]:8080"]);
+        assert_eq!(config.client_timeout(), Duration::from_secs(60));
+        assert_eq!(config.static_dir, "static");
+
+        let invalid = config.apply_env(|name| match name {
+            "OTHELLO_MAX_SESSIONS" => Some("many".to_string()),
+            _ => None,
+        });
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_apply_args() {
+        let matches = app().get_matches_from(vec![
+            "othello-server",
+            "--listen",
+            "127.0.0.1:80",
+            "--listen",
+            "127.0.0.1:81",
+            "--max-sessions",
+            "2",
+            "--database",
+            "",
+        ]);
+        let mut config = Config::default();
+        config.apply_args(&matches).unwrap();
+        assert_eq!(config.listen, vec!["127.0.0.1:80", "127.0.0.1:81"]);
+        assert_eq!(config.max_sessions, 2);
+        assert_eq!(config.max_boards, Config::default().max_boards);
+        assert_eq!(config.database, "");
+
+        let matches = app().get_matches_from(vec!["othello-server", "--bot-deadline", "0"]);
+        config.apply_args(&matches).unwrap();
+        assert_eq!(config.bot_deadline(), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Config::default().validate().is_ok());
+        let config = Config {
+            client_timeout: 5,
+            ..Config::default()
+        };
+        assert!(config.validate().is_err());
+        let config = Config {
+            board_arbiters: 0,
+            ..Config::default()
+        };
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! A game played on a board, every move is validated by the server.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use engine::model::{BoardModel, Cell, BOARD_SIZE};
+use wscommand::{Color, WsBoardState};
+
+/// Milliseconds since the unix epoch
+pub fn timestamp() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    now.as_secs() as i64 * 1000 + i64::from(now.subsec_millis())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Move {
+    pub player: Cell,
+    /// the played square, `x + y * 8`
+    pub pos: usize,
+    /// see `timestamp`
+    pub played_at: i64,
+}
+
+/// Reasons to reject a move
+#[derive(Debug, PartialEq)]
+pub enum MoveError {
+    /// the game is already over
+    GameOver,
+    /// the opponent has to play
+    NotYourTurn,
+    /// the position is outside of the board
+    OutOfBoard,
+    /// the move does not flip any disc
+    IllegalMove,
+}
+
+impl MoveError {
+    pub fn reason(&self) -> &'static str {
+        match *self {
+            MoveError::GameOver => "the game is over",
+            MoveError::NotYourTurn => "it is not your turn",
+            MoveError::OutOfBoard => "the position is outside of the board",
+            MoveError::IllegalMove => "the move does not flip any disc",
+        }
+    }
+}
+
+pub struct Game {
+    board: BoardModel,
+    current_player: Cell,
+    game_over: bool,
+    /// moves played since the beginning of the game
+    moves: Vec<Move>,
+    /// see `timestamp`
+    started_at: i64,
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game {
+            board: BoardModel::new(),
+            current_player: Cell::Black,
+            game_over: false,
+            moves: Vec::new(),
+            started_at: timestamp(),
+        }
+    }
+}
+
+impl Game {
+    pub fn is_over(&self) -> bool {
+        self.game_over
+    }
+
+    pub fn score(&self) -> (usize, usize) {
+        self.board.score()
+    }
+
+    /// The moves played since the beginning of the game
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn started_at(&self) -> i64 {
+        self.started_at
+    }
+
+    /// The player to move
+    pub fn current_player(&self) -> Cell {
+        self.current_player
+    }
+
+    pub fn board(&self) -> &BoardModel {
+        &self.board
+    }
+
+    /// The player with the more discs, `Cell::Empty` for a draw
+    pub fn winner(&self) -> Cell {
+        let (black, white) = self.board.score();
+        if black > white {
+            Cell::Black
+        } else if white > black {
+            Cell::White
+        } else {
+            Cell::Empty
+        }
+    }
+
+    /// Validate and play the move of `player`, the turn passes if the
+    /// opponent can play.
+    pub fn play(&mut self, player: Cell, pos: (usize, usize)) -> Result<(), MoveError> {
+        if self.game_over {
+            return Err(MoveError::GameOver);
+        }
+        if player != self.current_player {
+            return Err(MoveError::NotYourTurn);
+        }
+        let (x, y) = pos;
+        if x >= BOARD_SIZE || y >= BOARD_SIZE {
+            return Err(MoveError::OutOfBoard);
+        }
+        if self.board.set_cell(x, y, player).is_err() {
+            return Err(MoveError::IllegalMove);
+        }
+        self.moves.push(Move {
+            player,
+            pos: x + y * BOARD_SIZE,
+            played_at: timestamp(),
+        });
+        if !self.board.get_possibilities(player.opposite()).is_empty() {
+            self.current_player = player.opposite();
+        } else if self.board.get_possibilities(player).is_empty() {
+            self.game_over = true;
+        }
+        Ok(())
+    }
+
+    /// The state sent to a client to draw the board
+    pub fn state(&self) -> WsBoardState {
+        WsBoardState {
+            position: self.board.to_string(),
+            current_player: color(self.current_player).unwrap_or(Color::Black),
+        }
+    }
+}
+
+/// The color of a player, `None` for `Cell::Empty`
+pub fn color(cell: Cell) -> Option<Color> {
+    match cell {
+        Cell::Black => Some(Color::Black),
+        Cell::White => Some(Color::White),
+        Cell::Empty => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_play_turns() {
+        let mut game = Game::default();
+        assert_eq!(game.play(Cell::White, (4, 2)), Err(MoveError::NotYourTurn));
+        assert_eq!(game.play(Cell::Black, (0, 0)), Err(MoveError::IllegalMove));
+        assert_eq!(game.play(Cell::Black, (8, 2)), Err(MoveError::OutOfBoard));
+        assert_eq!(game.play(Cell::Black, (2, 8)), Err(MoveError::OutOfBoard));
+        assert_eq!(game.play(Cell::Black, (4, 2)), Ok(()));
+        assert_eq!(game.current_player, Cell::White);
+        assert_eq!(game.score(), (4, 1));
+        assert_eq!(game.play(Cell::Black, (3, 2)), Err(MoveError::NotYourTurn));
+        assert_eq!(game.play(Cell::White, (3, 2)), Ok(()));
+        assert_eq!(game.current_player, Cell::Black);
+        let moves: Vec<(Cell, usize)> = game.moves().iter().map(|m| (m.player, m.pos)).collect();
+        assert_eq!(moves, vec![(Cell::Black, 20), (Cell::White, 19)]);
+        assert!(game.moves()[0].played_at >= game.started_at());
+    }
+
+    #[test]
+    fn test_play_game_over() {
+        let mut game = Game {
+            board: BoardModel::from_string(
+                r#"
+                . W B W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                "#,
+            ),
+            ..Game::default()
+        };
+        assert_eq!(game.play(Cell::Black, (0, 0)), Ok(()));
+        assert!(game.is_over());
+        assert_eq!(game.score(), (3, 61));
+        assert_eq!(game.winner(), Cell::White);
+        assert_eq!(game.play(Cell::White, (0, 0)), Err(MoveError::GameOver));
+    }
+
+    #[test]
+    fn test_play_pass() {
+        let mut game = Game {
+            board: BoardModel::from_string(
+                r#"
+                . W B W W W W .
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                W W W W W W W W
+                "#,
+            ),
+            ..Game::default()
+        };
+        // white cannot play after black, black plays again
+        assert_eq!(game.play(Cell::Black, (0, 0)), Ok(()));
+        assert!(!game.is_over());
+        assert_eq!(game.current_player, Cell::Black);
+    }
+}
@@ -2,15 +2,20 @@
 extern crate log;
 extern crate pretty_env_logger;
 
+extern crate argon2;
 extern crate byteorder;
 extern crate bytes;
+extern crate clap;
 extern crate futures;
+extern crate othello_engine as engine;
 extern crate rand;
+extern crate rusqlite;
 
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate toml;
 
 extern crate tokio_core;
 extern crate tokio_io;
@@ -19,30 +24,72 @@ extern crate tokio_io;
 extern crate actix;
 extern crate actix_web;
 
-use std::time::Instant;
+use std::env;
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use actix::{fut, Actor, Addr, Arbiter, Handler, Running, StreamHandler, Syn, prelude::*};
+use actix::{fut, Actor, Addr, Arbiter, Handler, Running, StreamHandler, Syn, SyncArbiter,
+            prelude::*};
 use actix_web::server::HttpServer;
-use actix_web::{fs, ws, middleware, App, Error, HttpRequest, HttpResponse};
+use actix_web::{fs, http, ws, middleware, App, Error, HttpRequest, HttpResponse};
 
+mod account;
+mod api;
+mod board;
+mod bot;
+mod chat;
+mod clock;
+mod config;
+mod game;
+mod matchmaking;
+mod metrics;
+mod rating;
 mod server;
+mod sqlite;
+mod storage;
+mod tournament;
+mod validation;
 mod wscommand;
 
-use wscommand::{WsRequest, WsResponse};
+use account::AccountExecutor;
+use config::Config;
+use metrics::Metrics;
+use sqlite::SqliteStore;
+use storage::DbExecutor;
+use validation::{AddrRateLimits, RateLimit};
+use wscommand::{WsError, WsRequest, WsResponse};
 
-/// This is our websocket route state, this state is shared with all route
+/// The logins and the registrations hashed at the same time
+const PASSWORD_THREADS: usize = 2;
+
+/// This is our application state, this state is shared with all route
 /// instances via `HttpContext::state()`
-struct WsOthelloSessionState {
+struct AppState {
     addr: Addr<Syn, server::OthelloActor>,
+    /// the saved games, `None` if no database is configured
+    db: Option<Addr<Syn, DbExecutor>>,
+    /// hashes the passwords of the accounts, `None` without database
+    passwords: Option<Addr<Syn, AccountExecutor>>,
+    /// the counters of the sessions, shared with the server
+    metrics: Arc<Metrics>,
+    /// the registrations of the accounts by remote address
+    registrations: Arc<AddrRateLimits>,
+    /// how often the clients are pinged
+    heartbeat_interval: Duration,
+    /// a session that does not send anything for this duration is dropped
+    client_timeout: Duration,
 }
 
 /// Entry point for our route
-fn ws_route(req: HttpRequest<WsOthelloSessionState>) -> Result<HttpResponse, Error> {
+fn ws_route(req: HttpRequest<AppState>) -> Result<HttpResponse, Error> {
     ws::start(
         req,
         WsOthelloSession {
             id: "".to_string(),
             hb: Instant::now(),
+            requests: RateLimit::requests(),
+            errors: RateLimit::errors(),
         },
     )
 }
@@ -50,13 +97,59 @@ fn ws_route(req: HttpRequest<WsOthelloSessionState>) -> Result<HttpResponse, Err
 struct WsOthelloSession {
     /// unique session id
     id: String,
-    /// Client must send ping at least once per 10 seconds, otherwise we drop
-    /// connection.
+    /// Client must send ping at least once per `client_timeout`, otherwise
+    /// we drop connection.
     hb: Instant,
+    /// the requests are refused when the client sends too many
+    requests: RateLimit,
+    /// the connection is closed when the client keeps sending invalid requests
+    errors: RateLimit,
+}
+
+impl WsOthelloSession {
+    /// Ping the client every `heartbeat_interval` and stop the session if
+    /// it has been silent for too long, `stopping` disconnect it.
+    fn heartbeat(&self, ctx: &mut <Self as Actor>::Context) {
+        let interval = ctx.state().heartbeat_interval;
+        ctx.run_later(interval, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > ctx.state().client_timeout {
+                warn!("Session {} timed out, disconnecting", act.id);
+                ctx.state().metrics.count_eviction();
+                ctx.stop();
+                return;
+            }
+            ctx.ping("");
+            act.heartbeat(ctx);
+        });
+    }
+
+    /// Send the response to the client, counting the errors to disconnect a
+    /// client that keeps sending invalid requests
+    fn send(&mut self, resp: WsResponse, ctx: &mut <Self as Actor>::Context) {
+        let repeated = match resp {
+            WsResponse::Error(ref err) => {
+                warn!("Request of session {} refused: {}", self.id, err.message);
+                ctx.state().metrics.count_error(err.code);
+                !self.errors.allow(Instant::now())
+            }
+            _ => false,
+        };
+        let resp = serde_json::to_string(&resp).unwrap();
+        info!("Sending response back: {}", resp);
+        ctx.text(resp);
+        if repeated {
+            warn!("Too many errors from session {}, disconnecting", self.id);
+            ctx.stop();
+        }
+    }
+
+    fn refuse(&mut self, err: WsError, ctx: &mut <Self as Actor>::Context) {
+        self.send(WsResponse::Error(err), ctx);
+    }
 }
 
 impl Actor for WsOthelloSession {
-    type Context = ws::WebsocketContext<Self, WsOthelloSessionState>;
+    type Context = ws::WebsocketContext<Self, AppState>;
 
     /// Method is called on actor start.
     /// We register ws session with OthelloActor
@@ -64,7 +157,7 @@ impl Actor for WsOthelloSession {
         // register self in othello server. `AsyncContext::wait` register
         // future within context, but context waits until this future resolves
         // before processing any other events.
-        // HttpContext::state() is instance of WsOthelloSessionState, state is shared
+        // HttpContext::state() is instance of AppState, state is shared
         // across all routes within application
         let addr: Addr<Syn, _> = ctx.address();
         ctx.state()
@@ -75,20 +168,23 @@ impl Actor for WsOthelloSession {
             .into_actor(self)
             .then(|res, act, ctx| {
                 match res {
-                    Ok(res) => act.id = res,
-                    // something is wrong with chat server
+                    Ok(Some(res)) => act.id = res,
+                    // the server is full, or something is wrong with chat server
                     _ => ctx.stop(),
                 }
                 fut::ok(())
             })
             .wait(ctx);
+        self.heartbeat(ctx);
     }
 
     fn stopping(&mut self, ctx: &mut Self::Context) -> Running {
-        // notify chat server
-        ctx.state().addr.do_send(server::Disconnect {
-            id: self.id.clone(),
-        });
+        // notify chat server, if the session has been registered
+        if !self.id.is_empty() {
+            ctx.state().addr.do_send(server::Disconnect {
+                id: self.id.clone(),
+            });
+        }
         Running::Stop
     }
 }
@@ -97,11 +193,12 @@ impl Actor for WsOthelloSession {
 /// Text message are json parsed and send to the OthelloActor
 impl StreamHandler<ws::Message, ws::ProtocolError> for WsOthelloSession {
     fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
-        println!("WEBSOCKET MESSAGE: {:?}", msg);
+        debug!("Websocket message: {:?}", msg);
         match msg {
             ws::Message::Ping(msg) => {
                 debug!("Ping Received");
                 debug!("Sending Pong");
+                self.hb = Instant::now();
                 ctx.pong(&msg)
             }
             ws::Message::Pong(_) => {
@@ -110,16 +207,48 @@ impl StreamHandler<ws::Message, ws::ProtocolError> for WsOthelloSession {
                 self.hb = Instant::now()
             }
             ws::Message::Text(text) => {
-                let req: Result<WsRequest, _> = serde_json::from_str(text.as_str());
-                if req.is_err() {
-                    ctx.stop();
+                self.hb = Instant::now();
+                if !self.requests.allow(self.hb) {
+                    self.refuse(validation::rate_limited(), ctx);
                     return;
                 }
-                let req = req.unwrap();
-                ctx.state().addr.do_send(server::ClientMessage {
-                    id: self.id.clone(),
-                    request: req,
-                })
+                let req = match validation::parse_request(text.as_str()) {
+                    Ok(req) => req,
+                    Err(err) => {
+                        self.refuse(err, ctx);
+                        return;
+                    }
+                };
+                if let Err(err) = validation::check_request(&req, &self.id) {
+                    self.refuse(err, ctx);
+                    return;
+                }
+                match req {
+                    // answered here, the server only hears of the session
+                    // when it connects, resumes or goes away
+                    WsRequest::KeepAlive => self.send(WsResponse::KeepAlive, ctx),
+                    WsRequest::Resume(param) => {
+                        // the websocket takes over the id of the resumed session
+                        ctx.state()
+                            .addr
+                            .send(server::Resume {
+                                id: self.id.clone(),
+                                resume_token: param.resume_token,
+                            })
+                            .into_actor(self)
+                            .then(|res, act, _| {
+                                if let Ok(Some(id)) = res {
+                                    act.id = id;
+                                }
+                                fut::ok(())
+                            })
+                            .wait(ctx);
+                    }
+                    req => ctx.state().addr.do_send(server::ClientMessage {
+                        id: self.id.clone(),
+                        request: req,
+                    }),
+                }
             }
             ws::Message::Binary(_) => {
                 error!("Unexpected binary, give up");
@@ -133,28 +262,78 @@ impl StreamHandler<ws::Message, ws::ProtocolError> for WsOthelloSession {
 }
 
 /// Send the websocket Response to the peer websocket
-impl Handler<WsResponse> for WsOthelloSession {
+impl Handler<server::Outgoing> for WsOthelloSession {
     type Result = ();
 
-    fn handle(&mut self, resp: WsResponse, ctx: &mut Self::Context) {
-        let resp = serde_json::to_string(&resp).unwrap();
-        info!("Sending response back: {}", resp);
-        ctx.text(resp);
+    fn handle(&mut self, msg: server::Outgoing, ctx: &mut Self::Context) {
+        let latency = Instant::now().duration_since(msg.queued_at);
+        ctx.state().metrics.observe_send_latency(latency);
+        self.send(msg.resp, ctx);
     }
 }
 
 fn main() {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+    if env::var("RUST_LOG").is_err() {
+        env::set_var("RUST_LOG", &config.log_level);
+    }
     let _ = pretty_env_logger::init();
     let sys = actix::System::new("othello-server");
 
+    // Finished games are saved and read by a single thread, SQLite
+    // serializes the writes anyway. The database is opened first to report errors early.
+    let db = if config.database.is_empty() {
+        warn!("No database configured, the games will not be saved");
+        None
+    } else {
+        if let Err(err) = SqliteStore::open(&config.database) {
+            error!("Cannot open the database {}: {}", config.database, err);
+            process::exit(1);
+        }
+        let database = config.database.clone();
+        Some(SyncArbiter::start(1, move || {
+            let store = SqliteStore::open(&database).expect("The database cannot be opened");
+            DbExecutor(Box::new(store))
+        }))
+    };
+    // The passwords are hashed on threads of their own, not to hold the
+    // database while hashing.
+    let passwords = db.clone().map(|db| {
+        SyncArbiter::start(PASSWORD_THREADS, move || AccountExecutor { db: db.clone() })
+    });
+
     // Start chat server actor in separate thread
-    let server: Addr<Syn, _> = Arbiter::start(|_| server::OthelloActor::default());
+    let metrics = Arc::new(Metrics::default());
+    let actor_config = config.clone();
+    let actor_db = db.clone();
+    let actor_passwords = passwords.clone();
+    let actor_metrics = metrics.clone();
+    let server: Addr<Syn, _> = Arbiter::start(move |_| {
+        server::OthelloActor::new(&actor_config, actor_db, actor_passwords, actor_metrics)
+    });
+
+    let registrations = Arc::new(AddrRateLimits::new(RateLimit::registrations));
+    let heartbeat_interval = config.heartbeat_interval();
+    let client_timeout = config.client_timeout();
+    let static_dir = config.static_dir.clone();
 
     // Create Http server with websocket support
-    HttpServer::new(move || {
-        // Websocket sessions state
-        let state = WsOthelloSessionState {
+    let mut http_server = HttpServer::new(move || {
+        // Websocket sessions and API state
+        let state = AppState {
             addr: server.clone(),
+            db: db.clone(),
+            passwords: passwords.clone(),
+            metrics: metrics.clone(),
+            registrations: registrations.clone(),
+            heartbeat_interval,
+            client_timeout,
         };
 
         App::with_state(state)
@@ -162,11 +341,36 @@ fn main() {
                 .middleware(middleware::Logger::default())
                 // websocket
                 .resource("/ws/", |r| r.route().f(ws_route))
-                .handler("/", fs::StaticFiles::new("static").index_file("index.html"))
-    }).bind("[::1]:8080")
-        .unwrap()
-        .start();
+                // read only JSON API
+                .resource("/api/games", |r| r.method(http::Method::GET).with2(api::list_games))
+                .resource("/api/games/{id}", |r| r.method(http::Method::GET).f(api::get_game))
+                .resource("/api/boards", |r| r.method(http::Method::GET).with2(api::list_boards))
+                .resource("/api/tournaments", |r| {
+                    r.method(http::Method::GET).f(api::list_tournaments)
+                })
+                .resource("/api/tournaments/{id}", |r| {
+                    r.method(http::Method::GET).f(api::get_tournament)
+                })
+                .resource("/api/accounts", |r| r.method(http::Method::POST).with2(api::register))
+                .resource("/api/players/{name}", |r| r.method(http::Method::GET).f(api::get_player))
+                .resource("/api/players/{name}/ratings", |r| {
+                    r.method(http::Method::GET).with2(api::rating_history)
+                })
+                // Prometheus metrics
+                .resource("/metrics", |r| r.method(http::Method::GET).f(metrics::scrape))
+                .handler("/", fs::StaticFiles::new(static_dir.as_str()).index_file("index.html"))
+    });
+    for addr in &config.listen {
+        http_server = match http_server.bind(addr.as_str()) {
+            Ok(http_server) => http_server,
+            Err(err) => {
+                error!("Cannot listen on {}: {}", addr, err);
+                process::exit(1);
+            }
+        };
+        info!("Started http server: {}", addr);
+    }
+    http_server.start();
 
-    info!("Started http server: [::1]:8080");
     let _ = sys.run();
 }
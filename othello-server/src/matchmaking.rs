@@ -0,0 +1,321 @@
+//! Pair the players asking for a quick game by strength.
+//!
+//! A player waits in the pool until another player with the same time
+//! control and a close rating asks for a game. The accepted rating gap widens
+//! while the player waits, until a bot takes the seat after a deadline.
+
+use std::time::{Duration, Instant};
+
+use wscommand::TimeControl;
+
+/// The rating gap accepted when a player starts waiting
+const INITIAL_GAP: f64 = 100.;
+/// How fast the accepted gap widens while waiting
+const GAP_PER_SECOND: f64 = 10.;
+/// The accepted gap stops widening there
+const MAX_GAP: f64 = 600.;
+
+/// Tell the time, the tests need a clock they control
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A player waiting for a quick game
+#[derive(Clone, Debug, PartialEq)]
+pub struct Seeker {
+    pub session_id: String,
+    pub rating: f64,
+    /// `None` for a game without clock
+    pub time_control: Option<TimeControl>,
+    since: Instant,
+}
+
+/// The players to seat at the same board
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pairing {
+    /// the player waiting the longest first
+    Players(String, String),
+    /// nobody came before the deadline
+    Bot(String),
+}
+
+pub struct Matchmaker<C: Clock> {
+    clock: C,
+    /// the waiting players, the oldest first
+    pool: Vec<Seeker>,
+    /// how long a player waits before playing a bot, `None` to wait forever
+    bot_deadline: Option<Duration>,
+}
+
+impl<C: Clock> Matchmaker<C> {
+    pub fn new(clock: C, bot_deadline: Option<Duration>) -> Self {
+        Matchmaker {
+            clock,
+            pool: Vec::new(),
+            bot_deadline,
+        }
+    }
+
+    /// The rating gap the seeker accepts, wider the longer they wait
+    fn accepted_gap(&self, seeker: &Seeker, now: Instant) -> f64 {
+        let waited = now.duration_since(seeker.since);
+        let waited = waited.as_secs() as f64 + f64::from(waited.subsec_millis()) / 1000.;
+        (INITIAL_GAP + GAP_PER_SECOND * waited).min(MAX_GAP)
+    }
+
+    /// Whether both seekers accept to play each other
+    fn accepts(&self, first: &Seeker, second: &Seeker, now: Instant) -> bool {
+        let gap = (first.rating - second.rating).abs();
+        first.time_control == second.time_control && gap <= self.accepted_gap(first, now)
+            && gap <= self.accepted_gap(second, now)
+    }
+
+    /// The waiting player closest in rating accepted by `seeker`, if any
+    fn best_opponent(&self, seeker: &Seeker, now: Instant) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        for (index, other) in self.pool.iter().enumerate() {
+            if other.session_id == seeker.session_id || !self.accepts(seeker, other, now) {
+                continue;
+            }
+            let gap = (seeker.rating - other.rating).abs();
+            match best {
+                Some((_, best_gap)) if best_gap <= gap => {}
+                _ => best = Some((index, gap)),
+            }
+        }
+        best.map(|(index, _)| index)
+    }
+
+    /// Pair the player with a waiting one, or add them to the pool.
+    ///
+    /// A player already waiting starts waiting again.
+    pub fn seek(
+        &mut self,
+        session_id: &str,
+        rating: f64,
+        time_control: Option<TimeControl>,
+    ) -> Option<Pairing> {
+        self.cancel(session_id);
+        let now = self.clock.now();
+        let seeker = Seeker {
+            session_id: session_id.to_string(),
+            rating,
+            time_control,
+            since: now,
+        };
+        match self.best_opponent(&seeker, now) {
+            Some(index) => {
+                let opponent = self.pool.remove(index);
+                Some(Pairing::Players(opponent.session_id, seeker.session_id))
+            }
+            None => {
+                self.pool.push(seeker);
+                None
+            }
+        }
+    }
+
+    /// Remove the player from the pool, return whether they were waiting
+    pub fn cancel(&mut self, session_id: &str) -> bool {
+        let len = self.pool.len();
+        self.pool.retain(|seeker| seeker.session_id != session_id);
+        self.pool.len() != len
+    }
+
+    /// Keep only the players for whom `waiting` is true, the others left
+    pub fn retain<F: FnMut(&str) -> bool>(&mut self, mut waiting: F) {
+        self.pool.retain(|seeker| waiting(&seeker.session_id));
+    }
+
+    /// Pair the players whose gaps have widened enough, then hand the
+    /// players waiting since the deadline to the bot.
+    pub fn tick(&mut self) -> Vec<Pairing> {
+        let now = self.clock.now();
+        let mut pairings = Vec::new();
+        let mut index = 0;
+        while index < self.pool.len() {
+            let found = {
+                let seeker = &self.pool[index];
+                self.best_opponent(seeker, now)
+            };
+            match found {
+                Some(other) => {
+                    // the oldest seeker comes first in the pool
+                    let second = self.pool.remove(other);
+                    let first = self.pool.remove(index);
+                    pairings.push(Pairing::Players(first.session_id, second.session_id));
+                }
+                None => index += 1,
+            }
+        }
+        if let Some(deadline) = self.bot_deadline {
+            let (expired, waiting): (Vec<Seeker>, Vec<Seeker>) = self.pool
+                .drain(..)
+                .partition(|seeker| now.duration_since(seeker.since) >= deadline);
+            self.pool = waiting;
+            pairings.extend(expired.into_iter().map(|seeker| Pairing::Bot(seeker.session_id)));
+        }
+        pairings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use wscommand::ClockMode;
+    use std::rc::Rc;
+
+    /// A clock that only moves when told to
+    #[derive(Clone)]
+    struct FakeClock {
+        now: Rc<Cell<Instant>>,
+    }
+
+    impl FakeClock {
+        fn new() -> FakeClock {
+            FakeClock {
+                now: Rc::new(Cell::new(Instant::now())),
+            }
+        }
+
+        fn advance(&self, secs: u64) {
+            self.now.set(self.now.get() + Duration::from_secs(secs));
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+    }
+
+    fn matchmaker(bot_deadline: Option<u64>) -> (FakeClock, Matchmaker<FakeClock>) {
+        let clock = FakeClock::new();
+        let matchmaker = Matchmaker::new(clock.clone(), bot_deadline.map(Duration::from_secs));
+        (clock, matchmaker)
+    }
+
+    fn players(first: &str, second: &str) -> Pairing {
+        Pairing::Players(first.to_string(), second.to_string())
+    }
+
+    #[test]
+    fn test_seek_close_rating() {
+        let (_, mut matchmaker) = matchmaker(None);
+        assert_eq!(matchmaker.seek("alice", 1500., None), None);
+        assert_eq!(matchmaker.seek("bob", 1580., None), Some(players("alice", "bob")));
+        assert!(matchmaker.pool.is_empty());
+    }
+
+    #[test]
+    fn test_seek_closest_rating() {
+        let (_, mut matchmaker) = matchmaker(None);
+        matchmaker.seek("alice", 1450., None);
+        matchmaker.seek("bob", 1700., None);
+        matchmaker.seek("carol", 1530., None);
+        // alice and carol were already close enough
+        assert_eq!(matchmaker.pool.len(), 1);
+        assert_eq!(matchmaker.seek("dave", 1650., None), Some(players("bob", "dave")));
+    }
+
+    #[test]
+    fn test_seek_time_control() {
+        let (_, mut matchmaker) = matchmaker(None);
+        let blitz = Some(TimeControl {
+            minutes: 3,
+            increment: 2,
+            mode: ClockMode::Fischer,
+        });
+        matchmaker.seek("alice", 1500., blitz);
+        assert_eq!(matchmaker.seek("bob", 1500., None), None);
+        assert_eq!(matchmaker.seek("carol", 1500., blitz), Some(players("alice", "carol")));
+    }
+
+    #[test]
+    fn test_widen_gap() {
+        let (clock, mut matchmaker) = matchmaker(None);
+        matchmaker.seek("alice", 1500., None);
+        clock.advance(10);
+        // alice accepts 200 points now, but bob only 100
+        assert_eq!(matchmaker.seek("bob", 1750., None), None);
+        assert!(matchmaker.tick().is_empty());
+        clock.advance(15);
+        assert_eq!(matchmaker.tick(), vec![players("alice", "bob")]);
+        assert!(matchmaker.pool.is_empty());
+    }
+
+    #[test]
+    fn test_max_gap() {
+        let (clock, mut matchmaker) = matchmaker(None);
+        matchmaker.seek("alice", 1000., None);
+        matchmaker.seek("bob", 2000., None);
+        clock.advance(3600);
+        assert!(matchmaker.tick().is_empty());
+        assert_eq!(matchmaker.pool.len(), 2);
+    }
+
+    #[test]
+    fn test_bot_deadline() {
+        let (clock, mut matchmaker) = matchmaker(Some(30));
+        matchmaker.seek("alice", 1000., None);
+        clock.advance(20);
+        matchmaker.seek("bob", 2000., None);
+        assert!(matchmaker.tick().is_empty());
+        clock.advance(10);
+        assert_eq!(matchmaker.tick(), vec![Pairing::Bot("alice".to_string())]);
+        clock.advance(20);
+        assert_eq!(matchmaker.tick(), vec![Pairing::Bot("bob".to_string())]);
+        assert!(matchmaker.tick().is_empty());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let (clock, mut matchmaker) = matchmaker(Some(30));
+        matchmaker.seek("alice", 1500., None);
+        assert!(matchmaker.cancel("alice"));
+        assert!(!matchmaker.cancel("alice"));
+        assert_eq!(matchmaker.seek("bob", 1500., None), None);
+        clock.advance(30);
+        assert_eq!(matchmaker.tick(), vec![Pairing::Bot("bob".to_string())]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let (clock, mut matchmaker) = matchmaker(Some(30));
+        matchmaker.seek("alice", 1500., None);
+        matchmaker.seek("bob", 2000., None);
+        // alice has left, carol is not paired with her but waits
+        matchmaker.retain(|session_id| session_id != "alice");
+        assert_eq!(matchmaker.seek("carol", 1500., None), None);
+        assert_eq!(matchmaker.pool.len(), 2);
+        clock.advance(30);
+        assert_eq!(
+            matchmaker.tick(),
+            vec![
+                Pairing::Bot("bob".to_string()),
+                Pairing::Bot("carol".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_seek_again() {
+        let (clock, mut matchmaker) = matchmaker(Some(30));
+        matchmaker.seek("alice", 1500., None);
+        clock.advance(20);
+        // seeking again restarts the wait
+        assert_eq!(matchmaker.seek("alice", 1500., None), None);
+        assert_eq!(matchmaker.pool.len(), 1);
+        clock.advance(20);
+        assert!(matchmaker.tick().is_empty());
+    }
+}
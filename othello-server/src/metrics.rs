@@ -0,0 +1,276 @@
+//! Prometheus metrics, served as text on `GET /metrics`.
+//!
+//! The counters and the histograms are kept by `Metrics`, shared by the
+//! server and the websocket sessions. The gauges are read from the state of
+//! the server when the metrics are scraped. The moves per second are the
+//! `rate()` of `othello_moves_total`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use actix_web::{AsyncResponder, FutureResponse, HttpRequest, HttpResponse};
+use futures::future::Future;
+
+use server::GetMetrics;
+use wscommand::{GameOverReason, WsErrorCode};
+use AppState;
+
+/// The media type of the text format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+/// The upper bounds of the game durations, in seconds
+const DURATION_BUCKETS: &[f64] = &[30., 60., 120., 300., 600., 1200., 1800., 3600.];
+/// The upper bounds of the send latencies, in seconds
+const LATENCY_BUCKETS: &[f64] = &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5];
+
+fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+/// The observations counted in buckets, as many as `bounds` plus one
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let index = self.bounds
+            .iter()
+            .position(|&bound| value <= bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[index] += 1;
+        self.sum += value;
+    }
+
+    /// The buckets are cumulative in the text format
+    fn write(&self, out: &mut String, name: &str) {
+        let mut count = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.counts) {
+            count += bucket;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+        }
+        count += self.counts[self.bounds.len()];
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+struct Counters {
+    moves: u64,
+    /// the finished games by reason
+    games: BTreeMap<String, u64>,
+    /// the refused requests by error code
+    errors: BTreeMap<String, u64>,
+    evictions: u64,
+    game_durations: Histogram,
+    send_latencies: Histogram,
+}
+
+/// The state of the server when the metrics are scraped
+#[derive(Debug, Default, PartialEq)]
+pub struct Gauges {
+    /// the sessions with a websocket, not those waiting to be resumed
+    pub sessions: usize,
+    /// the boards waiting for an opponent who joins them
+    pub open_boards: usize,
+    /// the boards of the quick games waiting for a pairing
+    pub waiting_boards: usize,
+    pub playing_boards: usize,
+}
+
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            counters: Mutex::new(Counters {
+                moves: 0,
+                games: BTreeMap::new(),
+                errors: BTreeMap::new(),
+                evictions: 0,
+                game_durations: Histogram::new(DURATION_BUCKETS),
+                send_latencies: Histogram::new(LATENCY_BUCKETS),
+            }),
+        }
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+impl Metrics {
+    /// The counters are still consistent if a thread panicked holding them
+    fn counters(&self) -> MutexGuard<'_, Counters> {
+        self.counters.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// A move has been played, by a player or by the bot
+    pub fn count_move(&self) {
+        self.counters().moves += 1;
+    }
+
+    /// The game is over, `duration` since the board has been created
+    pub fn count_game(&self, reason: GameOverReason, duration: Duration) {
+        let mut counters = self.counters();
+        *counters.games.entry(format!("{:?}", reason)).or_insert(0) += 1;
+        // an aborted game is not played, its duration says nothing
+        if reason != GameOverReason::Aborted {
+            counters.game_durations.observe(secs(duration));
+        }
+    }
+
+    /// A request has been refused, unreadable or not
+    pub fn count_error(&self, code: WsErrorCode) {
+        *self.counters().errors.entry(format!("{:?}", code)).or_insert(0) += 1;
+    }
+
+    /// A silent client has been disconnected
+    pub fn count_eviction(&self) {
+        self.counters().evictions += 1;
+    }
+
+    /// A response waited `latency` before being written on its websocket
+    pub fn observe_send_latency(&self, latency: Duration) {
+        self.counters().send_latencies.observe(secs(latency));
+    }
+
+    /// The metrics in the text format of Prometheus
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let counters = self.counters();
+        let mut out = String::new();
+
+        header(&mut out, "othello_sessions", "gauge", "Connected sessions");
+        let _ = writeln!(out, "othello_sessions {}", gauges.sessions);
+        header(&mut out, "othello_boards", "gauge", "Boards by state");
+        let boards = [
+            ("open", gauges.open_boards),
+            ("waiting", gauges.waiting_boards),
+            ("playing", gauges.playing_boards),
+        ];
+        for &(state, count) in &boards {
+            let _ = writeln!(out, "othello_boards{{state=\"{}\"}} {}", state, count);
+        }
+
+        header(&mut out, "othello_games_finished_total", "counter", "Finished games by reason");
+        for (reason, count) in &counters.games {
+            let _ = writeln!(out, "othello_games_finished_total{{reason=\"{}\"}} {}", reason, count);
+        }
+        header(&mut out, "othello_moves_total", "counter", "Moves played");
+        let _ = writeln!(out, "othello_moves_total {}", counters.moves);
+        header(
+            &mut out,
+            "othello_ws_errors_total",
+            "counter",
+            "Refused websocket requests by code, InvalidRequest when unreadable",
+        );
+        for (code, count) in &counters.errors {
+            let _ = writeln!(out, "othello_ws_errors_total{{code=\"{}\"}} {}", code, count);
+        }
+        header(
+            &mut out,
+            "othello_heartbeat_evictions_total",
+            "counter",
+            "Sessions disconnected after a heartbeat timeout",
+        );
+        let _ = writeln!(out, "othello_heartbeat_evictions_total {}", counters.evictions);
+
+        header(
+            &mut out,
+            "othello_game_duration_seconds",
+            "histogram",
+            "Duration of the finished games, from the creation of the board",
+        );
+        counters.game_durations.write(&mut out, "othello_game_duration_seconds");
+        header(
+            &mut out,
+            "othello_ws_send_latency_seconds",
+            "histogram",
+            "Time from the server to the websocket of the session",
+        );
+        counters.send_latencies.write(&mut out, "othello_ws_send_latency_seconds");
+        out
+    }
+}
+
+/// `GET /metrics`
+pub fn scrape(req: HttpRequest<AppState>) -> FutureResponse<HttpResponse> {
+    req.state()
+        .addr
+        .send(GetMetrics)
+        .from_err()
+        .and_then(|metrics| {
+            Ok(HttpResponse::Ok()
+                .content_type(CONTENT_TYPE)
+                .body(metrics))
+        })
+        .responder()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(&[1., 10.]);
+        histogram.observe(0.5);
+        histogram.observe(1.);
+        histogram.observe(5.);
+        histogram.observe(20.);
+        let mut out = String::new();
+        histogram.write(&mut out, "h");
+        assert_eq!(
+            out,
+            "h_bucket{le=\"1\"} 2\n\
+             h_bucket{le=\"10\"} 3\n\
+             h_bucket{le=\"+Inf\"} 4\n\
+             h_sum 26.5\n\
+             h_count 4\n"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.count_move();
+        metrics.count_move();
+        metrics.count_game(GameOverReason::Normal, Duration::from_secs(90));
+        metrics.count_game(GameOverReason::Aborted, Duration::from_secs(5));
+        metrics.count_error(WsErrorCode::InvalidRequest);
+        metrics.count_eviction();
+        metrics.observe_send_latency(Duration::from_millis(2));
+        let gauges = Gauges {
+            sessions: 3,
+            open_boards: 1,
+            waiting_boards: 0,
+            playing_boards: 1,
+        };
+        let out = metrics.render(&gauges);
+        assert!(out.contains("# TYPE othello_sessions gauge\nothello_sessions 3\n"));
+        assert!(out.contains("othello_boards{state=\"open\"} 1\n"));
+        assert!(out.contains("othello_moves_total 2\n"));
+        assert!(out.contains("othello_games_finished_total{reason=\"Normal\"} 1\n"));
+        assert!(out.contains("othello_games_finished_total{reason=\"Aborted\"} 1\n"));
+        assert!(out.contains("othello_ws_errors_total{code=\"InvalidRequest\"} 1\n"));
+        assert!(out.contains("othello_heartbeat_evictions_total 1\n"));
+        // the aborted game has no duration
+        assert!(out.contains("othello_game_duration_seconds_count 1\n"));
+        assert!(out.contains("othello_ws_send_latency_seconds_bucket{le=\"0.005\"} 1\n"));
+    }
+}
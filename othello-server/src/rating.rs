@@ -0,0 +1,198 @@
+//! Player ratings, computed with the Glicko-2 system.
+//!
+//! Every rated game is its own rating period: both players are rated right
+//! after the game, against the rating of their opponent before the game.
+//! See <http://www.glicko.net/glicko/glicko2.pdf>.
+
+use std::f64::consts::PI;
+
+use wscommand::Color;
+
+/// Converts the ratings from and to the Glicko-2 scale
+const SCALE: f64 = 173.7178;
+/// Constrains the change of the volatility over time
+const TAU: f64 = 0.5;
+/// The precision of the volatility
+const EPSILON: f64 = 0.000_001;
+
+const DEFAULT_RATING: f64 = 1500.;
+/// The deviation of a new player, never exceeded
+const MAX_DEVIATION: f64 = 350.;
+const DEFAULT_VOLATILITY: f64 = 0.06;
+
+/// Above this deviation, the rating of a player is not reliable yet
+pub const PROVISIONAL_DEVIATION: f64 = 110.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    /// the uncertainty of the rating, it shrinks as the player plays
+    pub deviation: f64,
+    /// how erratic the results of the player are
+    pub volatility: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating {
+            rating: DEFAULT_RATING,
+            deviation: MAX_DEVIATION,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1. / (1. + 3. * phi * phi / (PI * PI)).sqrt()
+}
+
+/// The expected score against an opponent
+fn expected(mu: f64, opponent_mu: f64, opponent_phi: f64) -> f64 {
+    1. / (1. + (-g(opponent_phi) * (mu - opponent_mu)).exp())
+}
+
+impl Rating {
+    /// The new rating after the games of a rating period, `results` are the
+    /// ratings of the opponents and the scores, 1 for a win, 0.5 for a draw
+    /// and 0 for a loss.
+    pub fn update(&self, results: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - DEFAULT_RATING) / SCALE;
+        let phi = self.deviation / SCALE;
+        let sigma = self.volatility;
+        if results.is_empty() {
+            // only the uncertainty grows
+            return Rating {
+                deviation: ((phi * phi + sigma * sigma).sqrt() * SCALE).min(MAX_DEVIATION),
+                ..*self
+            };
+        }
+
+        let mut v_inv = 0.;
+        let mut improvement = 0.;
+        for &(ref opponent, score) in results {
+            let opponent_mu = (opponent.rating - DEFAULT_RATING) / SCALE;
+            let opponent_phi = opponent.deviation / SCALE;
+            let e = expected(mu, opponent_mu, opponent_phi);
+            v_inv += g(opponent_phi).powi(2) * e * (1. - e);
+            improvement += g(opponent_phi) * (score - e);
+        }
+        let v = 1. / v_inv;
+        let delta = v * improvement;
+
+        let sigma = new_volatility(phi, sigma, v, delta);
+        let phi_star = (phi * phi + sigma * sigma).sqrt();
+        let phi = 1. / (1. / (phi_star * phi_star) + 1. / v).sqrt();
+        let mu = mu + phi * phi * improvement;
+        Rating {
+            rating: mu * SCALE + DEFAULT_RATING,
+            deviation: (phi * SCALE).min(MAX_DEVIATION),
+            volatility: sigma,
+        }
+    }
+
+    /// The rating is not reliable yet
+    pub fn is_provisional(&self) -> bool {
+        self.deviation > PROVISIONAL_DEVIATION
+    }
+}
+
+/// Find the new volatility with the Illinois algorithm
+fn new_volatility(phi: f64, sigma: f64, v: f64, delta: f64) -> f64 {
+    let a = (sigma * sigma).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        let d = phi * phi + v + ex;
+        ex * (delta * delta - phi * phi - v - ex) / (2. * d * d) - (x - a) / (TAU * TAU)
+    };
+
+    let mut lower = a;
+    let mut upper = if delta * delta > phi * phi + v {
+        (delta * delta - phi * phi - v).ln()
+    } else {
+        let mut k = 1.;
+        while f(a - k * TAU) < 0. {
+            k += 1.;
+        }
+        a - k * TAU
+    };
+    let mut f_lower = f(lower);
+    let mut f_upper = f(upper);
+    while (upper - lower).abs() > EPSILON {
+        let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+        let f_c = f(c);
+        if f_c * f_upper <= 0. {
+            lower = upper;
+            f_lower = f_upper;
+        } else {
+            f_lower /= 2.;
+        }
+        upper = c;
+        f_upper = f_c;
+    }
+    (lower / 2.).exp()
+}
+
+/// The new ratings of the black and the white players after a game
+pub fn rate_game(black: &Rating, white: &Rating, winner: Option<Color>) -> (Rating, Rating) {
+    let score = match winner {
+        Some(Color::Black) => 1.,
+        Some(Color::White) => 0.,
+        None => 0.5,
+    };
+    (
+        black.update(&[(*white, score)]),
+        white.update(&[(*black, 1. - score)]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        Rating {
+            rating,
+            deviation,
+            ..Rating::default()
+        }
+    }
+
+    #[test]
+    fn test_glicko2_example() {
+        // the example of the paper of Mark Glickman
+        let player = rating(1500., 200.);
+        let updated = player.update(&[
+            (rating(1400., 30.), 1.),
+            (rating(1550., 100.), 0.),
+            (rating(1700., 300.), 0.),
+        ]);
+        assert!((updated.rating - 1464.06).abs() < 0.01, "{:?}", updated);
+        assert!((updated.deviation - 151.52).abs() < 0.01, "{:?}", updated);
+        assert!((updated.volatility - 0.05999).abs() < 0.00001, "{:?}", updated);
+    }
+
+    #[test]
+    fn test_no_game() {
+        let player = rating(1700., 50.);
+        let updated = player.update(&[]);
+        assert_eq!(updated.rating, 1700.);
+        assert!(updated.deviation > 50.);
+        assert_eq!(Rating::default().update(&[]).deviation, MAX_DEVIATION);
+    }
+
+    #[test]
+    fn test_rate_game() {
+        let (black, white) = rate_game(&Rating::default(), &Rating::default(), Some(Color::Black));
+        assert!(black.rating > DEFAULT_RATING);
+        assert!(white.rating < DEFAULT_RATING);
+        assert!((black.rating - DEFAULT_RATING - (DEFAULT_RATING - white.rating)).abs() < 1e-6);
+        assert!(black.deviation < MAX_DEVIATION);
+        assert!(black.is_provisional());
+
+        // a draw moves the ratings toward each other
+        let (strong, weak) = rate_game(&rating(1800., 60.), &rating(1400., 60.), None);
+        assert!(strong.rating < 1800.);
+        assert!(weak.rating > 1400.);
+        assert!(!strong.is_provisional());
+    }
+}
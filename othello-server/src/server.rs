@@ -1,74 +1,1838 @@
 //! `OthelloActor` maintains list of connection client session.
+//!
+//! It is the registry of the boards: it seats the players, pairs them and
+//! forwards them the requests about a game to its `BoardActor`. The board
+//! actors run on a few arbiters of their own, the server only keeps their
+//! seats and the last summary they sent.
 
+use std::cmp::Reverse;
 use std::iter;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use rand::{self, Rng, ThreadRng};
 use rand::distributions::Alphanumeric;
+use actix::fut;
+use actix::msgs::StartActor;
 use actix::prelude::*;
 
-use wscommand::{Color, WsConnectedParam, WsJoinedBoard, WsOpponentDisconnected,
-                WsOpponentJoinedBoard, WsPlayBoard, WsRequest, WsResponse};
+use account::{self, AccountError, AccountExecutor, Login, Register};
+use board::{self, BoardActor, BoardData, Member, Player, Seats};
+use bot::{BOT_ID, BOT_NAME};
+use chat::{self, ChatHistory};
+use config::Config;
+use game;
+use matchmaking::{Matchmaker, Pairing, SystemClock};
+use metrics::{Gauges, Metrics};
+use engine::model::Cell;
+use rating::Rating;
+use storage::{DbExecutor, GameRecord, GetPlayer, ListAccountNames, PlayerRecord, SaveGame,
+              STANDARD_VARIANT};
+use tournament::Tournament;
+use validation::RateLimit;
+use wscommand::{Color, ColorPreference, GameOutcome, GameOverReason, TimeControl, TournamentStatus,
+                WsBoardCancelled, WsBoardSettings, WsBoardState, WsChat, WsChatFailed,
+                WsChatHistory, WsChatMessage, WsConnectedParam, WsCreateTournament, WsCredentials,
+                WsGameAction, WsGameActionFailed, WsGameResult, WsJoinFailed, WsLiveBoard,
+                WsLoggedIn, WsLoginFailed, WsMuteOpponent, WsOpenBoard, WsRating, WsRejectedMove,
+                WsRematch, WsRequest, WsResponse, WsResumeFailed, WsResumed, WsSendChat,
+                WsTournament, WsTournamentAction, WsTournamentFailed, WsTournamentState,
+                WsWatchBoard, WsWatchFailed, WsWithdraw};
+
+/// The longest time control of a board, in minutes
+const MAX_MINUTES: u32 = 180;
+
+/// The characters of the invite codes, without those easily confused
+const INVITE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const INVITE_CODE_LEN: usize = 6;
+
+/// Milliseconds between two rounds of matchmaking
+const MATCHMAKING_INTERVAL: u64 = 1000;
+/// Milliseconds between two looks for the boards whose arbiter failed
+const SWEEP_INTERVAL: u64 = 5000;
+/// Milliseconds between the end of a round and the pairings of the next one,
+/// for the players to see their result
+const ROUND_DELAY: u64 = 5000;
+/// New tournaments are refused above this number of unfinished tournaments
+const MAX_TOURNAMENTS: usize = 100;
 
 /// Message for Othello server communications
 
-/// New Othello session is created on connection received
-#[derive(Message)]
-#[rtype(String)]
-pub struct Connect {
-    pub addr: Recipient<Syn, WsResponse>,
-}
+/// New Othello session is created on connection received
+///
+/// Return `None` when the server is full.
+#[derive(Message)]
+#[rtype(result = "Option<String>")]
+pub struct Connect {
+    pub addr: Recipient<Syn, Outgoing>,
+}
+
+/// A response for the websocket of a session, `queued_at` measures how long
+/// it waits before being sent
+#[derive(Message)]
+pub struct Outgoing {
+    pub resp: WsResponse,
+    pub queued_at: Instant,
+}
+
+impl Outgoing {
+    pub fn new(resp: WsResponse) -> Self {
+        Outgoing {
+            resp,
+            queued_at: Instant::now(),
+        }
+    }
+}
+
+/// Session is disconnected
+#[derive(Message)]
+pub struct Disconnect {
+    pub id: String,
+}
+
+#[derive(Message)]
+pub struct ClientMessage {
+    pub id: String,
+    pub request: WsRequest,
+}
+
+/// A new websocket takes over a disconnected session.
+///
+/// Return the id of the resumed session, that replaces the one received
+/// on `Connect`.
+#[derive(Message)]
+#[rtype(result = "Option<String>")]
+pub struct Resume {
+    pub id: String,
+    pub resume_token: String,
+}
+
+/// The boards in progress and the boards waiting for an opponent, optionally
+/// only those of a player.
+#[derive(Message)]
+#[rtype(result = "Vec<BoardSummary>")]
+pub struct ListBoards {
+    pub player: Option<String>,
+}
+
+/// An account has been registered from the API, its name is reserved
+#[derive(Message)]
+pub struct AccountRegistered(pub String);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum BoardStatus {
+    /// waiting for an opponent
+    Open,
+    Playing,
+}
+
+/// A live board, as listed by the API
+#[derive(Serialize, Debug, Clone)]
+pub struct BoardSummary {
+    pub board_id: String,
+    pub status: BoardStatus,
+    /// nickname of the black player, `None` while the seat is free
+    pub black: Option<String>,
+    /// nickname of the white player, `None` while the seat is free
+    pub white: Option<String>,
+    pub state: WsBoardState,
+    /// (black score, white score)
+    pub score: (usize, usize),
+    /// number of moves played
+    pub moves: usize,
+    pub spectators: usize,
+    /// milliseconds since the unix epoch
+    pub started_at: i64,
+}
+
+/// The tournaments, by name
+#[derive(Message)]
+#[rtype(result = "Vec<WsTournamentState>")]
+pub struct ListTournaments;
+
+/// The metrics of the server, in the text format of Prometheus
+#[derive(Message)]
+#[rtype(result = "String")]
+pub struct GetMetrics;
+
+/// The standings and the current round of a tournament
+#[derive(Message)]
+#[rtype(result = "Option<WsTournamentState>")]
+pub struct GetTournament {
+    pub tournament_id: String,
+}
+
+/// A board actor tells its game changed: a move, a player or a spectator
+#[derive(Message)]
+pub struct BoardChanged(pub BoardSummary);
+
+/// A board actor tells its game is over, the players and the spectators are
+/// sent the result
+#[derive(Message)]
+pub struct GameOver {
+    pub board_id: String,
+    pub winner: Cell,
+    pub reason: GameOverReason,
+    pub record: GameRecord,
+}
+
+pub struct SessionData {
+    // where the user is joinable
+    addr: Recipient<Syn, Outgoing>,
+    // the nickname received in the ConnectionParam, or the name of the account
+    nickname: Option<String>,
+    // the user has not logged in to an account
+    guest: bool,
+    // a board where the user is actually
+    board_id: Option<String>,
+    // the secret to resume the session from another websocket
+    resume_token: String,
+    // the websocket is closed, the session expires unless it is resumed
+    away: Option<SpawnHandle>,
+    // a board the user is watching
+    watching: Option<String>,
+    // the user is sent the open boards each time they change
+    in_lobby: bool,
+    // the rating of the nickname, loaded from the database
+    rating: Option<Rating>,
+    // the chat messages recently sent by the user
+    chat_limit: RateLimit,
+    // the session ids of the opponents whose messages the user does not receive
+    muted: Vec<String>,
+}
+
+impl SessionData {
+    /// Who the user is beyond the nickname: the account they are logged in
+    /// to, or the resume token of a guest, that no other session has
+    fn identity(&self) -> Option<String> {
+        let nickname = self.nickname.as_ref()?;
+        Some(if self.guest {
+            format!("guest:{}", self.resume_token)
+        } else {
+            format!("account:{}", account::name_key(nickname))
+        })
+    }
+}
+
+/// A board as the server knows it, its game is run by its actor
+struct BoardEntry {
+    // `None` until the actor has started on its arbiter
+    addr: Option<Addr<Syn, BoardActor>>,
+    // the players, their colors and the settings of the game
+    seats: Seats,
+    // the game as last told by the board actor
+    summary: BoardSummary,
+}
+
+/// A finished game its players may play again, the colors swapped
+struct Rematch {
+    // the session ids of the players of the finished game
+    black: String,
+    white: String,
+    settings: WsBoardSettings,
+    // the session who offered the rematch, waiting for the other one
+    offered_by: Option<String>,
+}
+
+impl Rematch {
+    /// The session id of the other player, `None` if the session did not play
+    fn opponent(&self, session_id: &str) -> Option<&str> {
+        if self.black == session_id {
+            Some(&self.white)
+        } else if self.white == session_id {
+            Some(&self.black)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tell the player their resignation, abort or rematch is refused
+fn game_action_failed(sess_id: &str, param: &WsGameAction, reason: &str) -> Option<WsResponse> {
+    Some(WsResponse::GameActionFailed(WsGameActionFailed {
+        session_id: sess_id.to_string(),
+        board_id: param.board_id.clone(),
+        reason: reason.to_string(),
+    }))
+}
+
+/// Tell the session its tournament request is refused
+fn tournament_failed(sess_id: &str, reason: &str) -> Option<WsResponse> {
+    Some(WsResponse::TournamentFailed(WsTournamentFailed {
+        session_id: sess_id.to_string(),
+        reason: reason.to_string(),
+    }))
+}
+
+/// Tell the session its registration, login or nickname is refused
+fn login_failed(sess_id: &str, reason: &str) -> Option<WsResponse> {
+    Some(WsResponse::LoginFailed(WsLoginFailed {
+        session_id: sess_id.to_string(),
+        reason: reason.to_string(),
+    }))
+}
+
+/// Refuse the settings of a game the server cannot play
+fn check_settings(settings: &WsBoardSettings) -> Result<(), String> {
+    if settings.variant != STANDARD_VARIANT {
+        return Err(format!("Unknown variant {:?}", settings.variant));
+    }
+    match settings.time_control {
+        Some(tc) if tc.minutes == 0 => Err("The time control gives no time to play".to_string()),
+        Some(tc) if tc.minutes > MAX_MINUTES => Err(format!(
+            "The time control cannot exceed {} minutes",
+            MAX_MINUTES
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The open board where the session waits for an opponent
+fn waiting_board(
+    sessions: &HashMap<String, SessionData>,
+    boards: &HashMap<String, BoardEntry>,
+    sess_id: &str,
+) -> Option<String> {
+    let board_id = sessions.get(sess_id)?.board_id.as_ref()?;
+    match boards.get(board_id) {
+        Some(entry) if entry.seats.is_open() => Some(board_id.clone()),
+        _ => None,
+    }
+}
+
+pub struct OthelloActor {
+    /// session_id to session address
+    sessions: HashMap<String, SessionData>,
+    /// boards by board id
+    boards: HashMap<String, BoardEntry>,
+    /// the list of public boards waiting for a partner
+    boarding: Vec<String>,
+    /// the private boards waiting for a partner, by invite code
+    invites: HashMap<String, String>,
+    rng: RefCell<ThreadRng>,
+    /// connections are refused above this number of sessions
+    max_sessions: usize,
+    /// new boards are refused above this number of boards
+    max_boards: usize,
+    /// the threads running the board actors, started as needed
+    arbiters: Vec<Addr<Syn, Arbiter>>,
+    board_arbiters: usize,
+    /// the arbiter of the next board
+    next_arbiter: usize,
+    /// how long the seat of a disconnected player is held during a game
+    resume_grace: Duration,
+    /// where the finished games are saved, if anywhere
+    db: Option<Addr<Syn, DbExecutor>>,
+    /// hashes and checks the passwords, with the database
+    passwords: Option<Addr<Syn, AccountExecutor>>,
+    /// the players waiting for a quick game, each on a board of their own
+    matchmaker: Matchmaker<SystemClock>,
+    /// the last messages of the lobby
+    lobby_chat: ChatHistory,
+    /// the finished games whose players may play again, by board id
+    rematches: HashMap<String, Rematch>,
+    /// the names of the accounts, as `account::name_key`, not to be taken by
+    /// guests
+    accounts: HashSet<String>,
+    /// tournaments by tournament id
+    tournaments: HashMap<String, Tournament>,
+    /// the counters shared with the websocket sessions
+    metrics: Arc<Metrics>,
+}
+
+impl OthelloActor {
+    pub fn new(
+        config: &Config,
+        db: Option<Addr<Syn, DbExecutor>>,
+        passwords: Option<Addr<Syn, AccountExecutor>>,
+        metrics: Arc<Metrics>,
+    ) -> OthelloActor {
+        OthelloActor {
+            sessions: HashMap::new(),
+            boards: HashMap::new(),
+            boarding: Vec::new(),
+            invites: HashMap::new(),
+            rng: RefCell::new(rand::thread_rng()),
+            max_sessions: config.max_sessions,
+            max_boards: config.max_boards,
+            arbiters: Vec::new(),
+            board_arbiters: config.board_arbiters,
+            next_arbiter: 0,
+            resume_grace: config.resume_grace(),
+            db,
+            passwords,
+            matchmaker: Matchmaker::new(SystemClock, config.bot_deadline()),
+            lobby_chat: ChatHistory::default(),
+            rematches: HashMap::new(),
+            accounts: HashSet::new(),
+            tournaments: HashMap::new(),
+            metrics,
+        }
+    }
+
+    /// Send message to all users in the room
+    fn send_message(&self, resp: WsResponse, dest_id: &str) {
+        if dest_id == BOT_ID {
+            return;
+        }
+        if let Some(ref sess) = self.sessions.get(dest_id) {
+            let _ = sess.addr.do_send(Outgoing::new(resp));
+        } else {
+            warn!("Receive a message to an invalid id");
+        }
+    }
+
+    /// Remove the session, the opponent wins the game in progress
+    fn close_session(&mut self, id: &str) {
+        self.stop_watching(id);
+        // the board is closed if the session was waiting for an opponent
+        if self.leave_open_board(id).is_err() {
+            let board_id = self.sessions.get(id).and_then(|sess| sess.board_id.clone());
+            if let Some(board_id) = board_id {
+                let leaver = self.boards
+                    .get(&board_id)
+                    .map_or(Cell::Empty, |entry| entry.seats.player(id));
+                if leaver != Cell::Empty {
+                    self.end_game(&board_id, leaver.opposite(), GameOverReason::Disconnect);
+                }
+            }
+        }
+        self.forget_rematches(id);
+        if self.sessions.remove(id).is_some() {
+            info!("Session {} closed", id);
+        } else {
+            error!("Unregistered session has disconnect");
+        }
+    }
+
+    /// The address of the actor of the board
+    fn board_addr(&self, board_id: &str) -> Option<Addr<Syn, BoardActor>> {
+        self.boards.get(board_id).and_then(|entry| entry.addr.clone())
+    }
+
+    /// Ask the board actor to end the game, it tells the result
+    fn end_game(&self, board_id: &str, winner: Cell, reason: GameOverReason) {
+        if let Some(addr) = self.board_addr(board_id) {
+            addr.do_send(board::EndGame { winner, reason });
+        }
+    }
+
+    /// Retire the board of the finished game and record its result
+    fn game_over(&mut self, msg: GameOver, ctx: &mut Context<Self>) {
+        let GameOver {
+            board_id,
+            winner,
+            reason,
+            record,
+        } = msg;
+        let entry = match self.boards.remove(&board_id) {
+            Some(entry) => entry,
+            None => {
+                error!("Cannot end the unknown board {}", board_id);
+                return;
+            }
+        };
+        if let Some(addr) = entry.addr {
+            addr.do_send(board::Close);
+        }
+        let duration = record.ended_at - record.started_at;
+        self.metrics
+            .count_game(reason, Duration::from_millis(duration.max(0) as u64));
+        self.boarding.retain(|b| *b != board_id);
+        match self.db {
+            // an aborted game is not recorded
+            Some(ref db) if reason != GameOverReason::Aborted => {
+                // a rated game changes the ratings of the players
+                db.send(SaveGame(record))
+                    .into_actor(self)
+                    .then(|res, act, _| {
+                        if let Ok(Ok(saved)) = res {
+                            act.update_ratings(&saved.players);
+                        }
+                        fut::ok(())
+                    })
+                    .spawn(ctx);
+            }
+            _ => {}
+        }
+        let seats = entry.seats;
+        let present = |sess_id: &str| sess_id == BOT_ID || self.sessions.contains_key(sess_id);
+        // the players of a tournament game meet again only if paired again
+        if seats.tournament.is_none() && present(&seats.black) && present(&seats.white) {
+            self.rematches.insert(
+                board_id.clone(),
+                Rematch {
+                    black: seats.black.clone(),
+                    white: seats.white.clone(),
+                    settings: seats.settings.clone(),
+                    offered_by: None,
+                },
+            );
+        }
+        self.free_board_sessions(&board_id);
+        if let Some(tournament_id) = seats.tournament {
+            self.record_tournament_game(&tournament_id, &board_id, winner, ctx);
+        }
+    }
+
+    /// The players and the spectators of the board are not at it anymore
+    fn free_board_sessions(&mut self, board_id: &str) {
+        let at_board = |id: &Option<String>| id.as_ref().map(|id| id.as_str()) == Some(board_id);
+        for sess in self.sessions.values_mut() {
+            if at_board(&sess.board_id) {
+                sess.board_id = None;
+            }
+            if at_board(&sess.watching) {
+                sess.watching = None;
+            }
+        }
+    }
+
+    /// Forget the boards whose actor is gone with its arbiter, their games
+    /// are lost
+    fn sweep_boards(&mut self, ctx: &mut Context<Self>) {
+        let lost: Vec<String> = self.boards
+            .iter()
+            .filter(|&(_, entry)| match entry.addr {
+                Some(ref addr) => !addr.connected(),
+                None => false,
+            })
+            .map(|(board_id, _)| board_id.clone())
+            .collect();
+        let listed = lost.iter().any(|board_id| self.boarding.contains(board_id));
+        for board_id in lost {
+            let entry = match self.boards.remove(&board_id) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            error!("The actor of the board {} is gone", board_id);
+            let seats = entry.seats;
+            if let Some(code) = seats.invite_code {
+                self.invites.remove(&code);
+            }
+            self.boarding.retain(|b| *b != board_id);
+            for sess_id in &[&seats.black, &seats.white] {
+                self.matchmaker.cancel(sess_id);
+            }
+            for (sess_id, sess) in &self.sessions {
+                if sess.board_id.as_ref() == Some(&board_id) || sess.watching.as_ref() == Some(&board_id) {
+                    let result = WsResponse::GameResult(WsGameResult {
+                        session_id: sess_id.clone(),
+                        board_id: board_id.clone(),
+                        winner: None,
+                        score: entry.summary.score,
+                        reason: GameOverReason::Aborted,
+                    });
+                    let _ = sess.addr.do_send(Outgoing::new(result));
+                }
+            }
+            self.free_board_sessions(&board_id);
+            if let Some(tournament_id) = seats.tournament {
+                self.record_tournament_game(&tournament_id, &board_id, Cell::Empty, ctx);
+            }
+        }
+        if listed {
+            self.send_open_boards();
+        }
+    }
+
+    fn schedule_sweep(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(Duration::from_millis(SWEEP_INTERVAL), |act, ctx| {
+            act.sweep_boards(ctx);
+            act.schedule_sweep(ctx);
+        });
+    }
+
+    /// Fetch the rating of the nickname of the session
+    fn load_rating(&self, sess_id: &str, nickname: &str, ctx: &mut Context<Self>) {
+        let db = match self.db {
+            Some(ref db) => db,
+            None => return,
+        };
+        let sess_id = sess_id.to_string();
+        let nickname = nickname.to_string();
+        db.send(GetPlayer(nickname.clone()))
+            .into_actor(self)
+            .then(move |res, act, _| {
+                match res {
+                    Ok(Ok(player)) => {
+                        let rating = player.map(|player| player.rating).unwrap_or_default();
+                        if let Some(sess) = act.sessions.get_mut(&sess_id) {
+                            // the nickname may have changed meanwhile
+                            if sess.nickname.as_ref() == Some(&nickname) {
+                                sess.rating = Some(rating);
+                            }
+                        }
+                    }
+                    Ok(Err(err)) => error!("Cannot read the rating of {}: {}", nickname, err),
+                    Err(err) => error!("Cannot read the rating of {}: {}", nickname, err),
+                }
+                fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Reserve the names of the accounts
+    fn load_accounts(&self, ctx: &mut Context<Self>) {
+        let db = match self.db {
+            Some(ref db) => db,
+            None => return,
+        };
+        db.send(ListAccountNames)
+            .into_actor(self)
+            .then(|res, act, _| {
+                match res {
+                    Ok(Ok(names)) => {
+                        info!("{} accounts", names.len());
+                        act.accounts
+                            .extend(names.iter().map(|name| account::name_key(name)));
+                    }
+                    Ok(Err(err)) => error!("Cannot read the accounts: {}", err),
+                    Err(err) => error!("Cannot read the accounts: {}", err),
+                }
+                fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Whether the player of the session has no account, the bot has one
+    fn is_guest(&self, sess_id: &str) -> bool {
+        sess_id != BOT_ID && self.sessions.get(sess_id).map(|sess| sess.guest).unwrap_or(true)
+    }
+
+    /// Refuse the nickname of a guest if it is the name of an account, or of
+    /// another user.
+    ///
+    /// An away session keeps its nickname until it expires, its player may
+    /// resume it.
+    fn check_nickname(&self, sess_id: &str, nickname: &str) -> Result<(), String> {
+        let key = account::name_key(nickname);
+        if key == account::name_key(BOT_NAME) || self.accounts.contains(&key) {
+            return Err(format!("{} is the name of an account, log in to use it", nickname));
+        }
+        let taken = self.sessions.iter().any(|(id, sess)| {
+            id != sess_id && sess.nickname.iter().any(|name| account::name_key(name) == key)
+        });
+        if taken {
+            return Err(format!("The nickname {} is already used", nickname));
+        }
+        Ok(())
+    }
+
+    /// Create an account, the session is logged in to it
+    fn register(
+        &mut self,
+        sess_id: &str,
+        param: &WsCredentials,
+        ctx: &mut Context<Self>,
+    ) -> Option<WsResponse> {
+        if param.session_id != sess_id {
+            return login_failed(sess_id, "unknown session");
+        }
+        let refused = account::check_name(&param.name)
+            .and_then(|()| account::check_password(&param.password))
+            .err()
+            .or_else(|| {
+                if self.accounts.contains(&account::name_key(&param.name)) {
+                    Some(AccountError::NameTaken.to_string())
+                } else {
+                    None
+                }
+            });
+        if let Some(reason) = refused {
+            return login_failed(sess_id, &reason);
+        }
+        let passwords = match self.passwords {
+            Some(ref passwords) => passwords,
+            None => return login_failed(sess_id, "This server has no accounts"),
+        };
+        let sess_id = sess_id.to_string();
+        passwords.send(Register {
+            name: param.name.clone(),
+            password: param.password.clone(),
+        }).into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(Ok(account)) => {
+                        act.accounts.insert(account::name_key(&account.name));
+                        act.log_in(&sess_id, &account.name, ctx);
+                    }
+                    Ok(Err(err)) => {
+                        if let Some(back) = login_failed(&sess_id, &err.to_string()) {
+                            act.send_message(back, &sess_id);
+                        }
+                    }
+                    Err(err) => error!("Cannot register an account: {}", err),
+                }
+                fut::ok(())
+            })
+            .spawn(ctx);
+        None
+    }
+
+    /// Check the password of the account, the session is logged in to it
+    fn login(
+        &mut self,
+        sess_id: &str,
+        param: &WsCredentials,
+        ctx: &mut Context<Self>,
+    ) -> Option<WsResponse> {
+        if param.session_id != sess_id {
+            return login_failed(sess_id, "unknown session");
+        }
+        let passwords = match self.passwords {
+            Some(ref passwords) => passwords,
+            None => return login_failed(sess_id, "This server has no accounts"),
+        };
+        let sess_id = sess_id.to_string();
+        passwords.send(Login {
+            name: param.name.clone(),
+            password: param.password.clone(),
+        }).into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(Ok(account)) => act.log_in(&sess_id, &account.name, ctx),
+                    Ok(Err(err)) => {
+                        warn!("Session {} cannot log in: {}", sess_id, err);
+                        if let Some(back) = login_failed(&sess_id, &err.to_string()) {
+                            act.send_message(back, &sess_id);
+                        }
+                    }
+                    Err(err) => error!("Cannot log in: {}", err),
+                }
+                fut::ok(())
+            })
+            .spawn(ctx);
+        None
+    }
+
+    /// The nickname of the session becomes the name of the account, its
+    /// rating follows
+    fn log_in(&mut self, sess_id: &str, name: &str, ctx: &mut Context<Self>) {
+        let resume_token = match self.sessions.get_mut(sess_id) {
+            Some(sess) => {
+                sess.nickname = Some(name.to_string());
+                sess.guest = false;
+                sess.rating = None;
+                sess.resume_token.clone()
+            }
+            // the session closed meanwhile
+            None => return,
+        };
+        info!("Session {} logged in to {}", sess_id, name);
+        let back = WsResponse::LoggedIn(WsLoggedIn {
+            session_id: sess_id.to_string(),
+            name: name.to_string(),
+            resume_token,
+        });
+        self.send_message(back, sess_id);
+        self.load_rating(sess_id, name, ctx);
+    }
+
+    /// Refresh the ratings of the sessions of the rated players
+    fn update_ratings(&mut self, players: &[PlayerRecord]) {
+        for player in players {
+            info!("{} is now rated {:.0}", player.name, player.rating.rating);
+            for sess in self.sessions.values_mut() {
+                if !sess.guest && sess.nickname.as_ref() == Some(&player.name) {
+                    sess.rating = Some(player.rating);
+                }
+            }
+        }
+    }
+
+    /// The rating of the session, `None` if the players are not rated or
+    /// the user is a guest
+    fn rating(&self, sess_id: &str) -> Option<WsRating> {
+        self.db.as_ref()?;
+        self.sessions.get(sess_id).filter(|sess| !sess.guest).map(|sess| {
+            let rating = sess.rating.unwrap_or_default();
+            WsRating::from(&rating)
+        })
+    }
+
+    /// The boards waiting for an opponent, the oldest first
+    fn open_boards(&self) -> Vec<WsOpenBoard> {
+        self.boarding
+            .iter()
+            .filter_map(|board_id| {
+                self.boards.get(board_id).and_then(|entry| {
+                    let seats = &entry.seats;
+                    seats.open_board(board_id, self.is_guest(seats.creator()))
+                })
+            })
+            .collect()
+    }
+
+    /// Send the open boards to the sessions in the lobby
+    fn send_open_boards(&self) {
+        let boards = self.open_boards();
+        for sess in self.sessions.values().filter(|sess| sess.in_lobby) {
+            let _ = sess.addr.do_send(Outgoing::new(WsResponse::OpenBoards(boards.clone())));
+        }
+    }
+
+    /// The session as a player of a board actor
+    fn board_player(&self, sess_id: &str) -> Option<Player> {
+        if sess_id == BOT_ID {
+            return Some(Player::bot());
+        }
+        let sess = self.sessions.get(sess_id)?;
+        Some(Player {
+            sess_id: sess_id.to_string(),
+            nickname: sess.nickname.clone().unwrap_or_default(),
+            guest: sess.guest,
+            rating: self.rating(sess_id),
+            member: Some(Member {
+                addr: sess.addr.clone(),
+                muted: sess.muted.clone(),
+            }),
+        })
+    }
+
+    /// The arbiters take the new boards in turn
+    fn board_arbiter(&mut self) -> Addr<Syn, Arbiter> {
+        let index = self.next_arbiter;
+        self.next_arbiter = (index + 1) % self.board_arbiters;
+        if index == self.arbiters.len() {
+            self.arbiters.push(Arbiter::new(format!("boards-{}", index)));
+        }
+        self.arbiters[index].clone()
+    }
+
+    /// Start the actor of a new board on an arbiter, the seated `players`
+    /// are told they joined it.
+    ///
+    /// The server waits for the address of the actor before handling another
+    /// message.
+    fn start_board(
+        &mut self,
+        board_id: &str,
+        seats: Seats,
+        players: Vec<Player>,
+        ctx: &mut Context<Self>,
+    ) {
+        let brd = BoardData::new(seats.clone());
+        self.boards.insert(
+            board_id.to_string(),
+            BoardEntry {
+                addr: None,
+                seats,
+                summary: brd.summary(board_id, 0),
+            },
+        );
+        let server: Addr<Syn, _> = ctx.address();
+        let metrics = self.metrics.clone();
+        let actor_board_id = board_id.to_string();
+        let board_id = board_id.to_string();
+        self.board_arbiter()
+            .send(StartActor::new(move |_| {
+                BoardActor::new(actor_board_id, brd, players, server, metrics)
+            }))
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(addr) => {
+                        if let Some(entry) = act.boards.get_mut(&board_id) {
+                            entry.addr = Some(addr);
+                        }
+                    }
+                    Err(err) => {
+                        error!("Cannot start the board {}: {}", board_id, err);
+                        act.sweep_boards(ctx);
+                    }
+                }
+                fut::ok(())
+            })
+            .wait(ctx);
+    }
+
+    /// Seat the session on a new board, until an opponent joins it.
+    ///
+    /// A public board is `listed` in the lobby, unless it waits for a quick
+    /// game.
+    fn create_board(
+        &mut self,
+        sess_id: &str,
+        settings: WsBoardSettings,
+        listed: bool,
+        ctx: &mut Context<Self>,
+    ) -> Option<WsResponse> {
+        let failed = |reason: &str| {
+            Some(WsResponse::JoinFailed(WsJoinFailed {
+                session_id: sess_id.to_string(),
+                reason: reason.to_string(),
+            }))
+        };
+        if let Err(reason) = check_settings(&settings) {
+            return failed(&reason);
+        }
+        if let Err(reason) = self.leave_open_board(sess_id) {
+            return failed(reason);
+        }
+        if self.boards.len() >= self.max_boards {
+            warn!("Too many boards, {} cannot create one", sess_id);
+            return failed("All the tables are taken, try again later");
+        }
+        let player = match self.board_player(sess_id) {
+            Some(player) => player,
+            None => {
+                error!("Unknown session id receided to create a board");
+                return failed("unknown session");
+            }
+        };
+        let color = match settings.color {
+            ColorPreference::Black => Color::Black,
+            ColorPreference::White => Color::White,
+            ColorPreference::Random => if self.rng.borrow_mut().gen() {
+                Color::Black
+            } else {
+                Color::White
+            },
+        };
+        let board_id = self.new_board_id();
+        info!("Session {} creates the board {} {:?}", sess_id, board_id, settings);
+        let mut seats = Seats::new(sess_id.to_string(), player.nickname.clone(), color, settings);
+        if seats.settings.private {
+            let code = self.new_invite_code();
+            self.invites.insert(code.clone(), board_id.clone());
+            seats.invite_code = Some(code);
+        }
+        let private = seats.invite_code.is_some();
+        self.start_board(&board_id, seats, vec![player], ctx);
+        self.forget_rematches(sess_id);
+        if let Some(sess) = self.sessions.get_mut(sess_id) {
+            sess.board_id = Some(board_id.clone());
+            sess.in_lobby = false;
+        }
+        if listed && !private {
+            self.boarding.push(board_id);
+            self.send_open_boards();
+        }
+        None
+    }
+
+    fn new_board_id(&self) -> String {
+        iter::repeat(())
+            .map(|()| self.rng.borrow_mut().sample(Alphanumeric))
+            .take(12)
+            .collect()
+    }
+
+    /// A code no other private board uses
+    fn new_invite_code(&self) -> String {
+        loop {
+            let code: String = iter::repeat(())
+                .map(|()| {
+                    let index = self.rng.borrow_mut().gen_range(0, INVITE_ALPHABET.len());
+                    INVITE_ALPHABET[index] as char
+                })
+                .take(INVITE_CODE_LEN)
+                .collect();
+            if !self.invites.contains_key(&code) {
+                return code;
+            }
+        }
+    }
+
+    /// Seat the session on the private board of the code
+    fn join_by_invite(&mut self, sess_id: &str, invite_code: &str) -> Option<WsResponse> {
+        let code = invite_code.trim().to_uppercase();
+        match self.invites.get(&code).cloned() {
+            Some(board_id) => self.join_board(sess_id, &board_id, true),
+            None => {
+                warn!("Session {} sent an unknown invite code", sess_id);
+                Some(WsResponse::JoinFailed(WsJoinFailed {
+                    session_id: sess_id.to_string(),
+                    reason: "unknown invite code".to_string(),
+                }))
+            }
+        }
+    }
+
+    /// Seat the session on an open board, the game starts.
+    ///
+    /// A private board is only joined with its invite code.
+    fn join_board(&mut self, sess_id: &str, board_id: &str, invited: bool) -> Option<WsResponse> {
+        let failed = |reason: &str| {
+            Some(WsResponse::JoinFailed(WsJoinFailed {
+                session_id: sess_id.to_string(),
+                reason: reason.to_string(),
+            }))
+        };
+        let player = match self.board_player(sess_id) {
+            Some(player) => player,
+            None => {
+                error!("Unknown session id receided to join the board");
+                return failed("unknown session");
+            }
+        };
+        let addr = match self.boards.get(board_id) {
+            None => Err("unknown board"),
+            Some(entry) if entry.seats.settings.private && !invited => Err("unknown board"),
+            Some(entry) if !entry.seats.is_open() => Err("the game has already started"),
+            Some(entry) if entry.seats.creator() == sess_id => Err("you are waiting on this board"),
+            // the seat is only taken once the board actor can be told
+            Some(entry) => entry.addr.clone().ok_or("the board is not ready, try again"),
+        };
+        let addr = match addr {
+            Ok(addr) => addr,
+            Err(reason) => return failed(reason),
+        };
+        if let Err(reason) = self.leave_open_board(sess_id) {
+            return failed(reason);
+        }
+
+        let seats = match self.boards.get_mut(board_id) {
+            Some(entry) => {
+                if entry.seats.take(sess_id.to_string(), player.nickname.clone()).is_none() {
+                    return failed("the game has already started");
+                }
+                // the invite code cannot be used twice
+                if let Some(code) = entry.seats.invite_code.take() {
+                    self.invites.remove(&code);
+                }
+                entry.seats.clone()
+            }
+            None => return failed("unknown board"),
+        };
+        info!("Session {} joins the board {}", sess_id, board_id);
+        self.boarding.retain(|b| b != board_id);
+        self.forget_rematches(sess_id);
+        if let Some(sess) = self.sessions.get_mut(sess_id) {
+            sess.board_id = Some(board_id.to_string());
+            sess.in_lobby = false;
+        }
+        // the board tells both players the game starts
+        addr.do_send(board::Join { player, seats });
+        self.send_open_boards();
+        None
+    }
+
+    /// Join a player of similar strength waiting for a quick game, or wait
+    /// for one on a new board
+    fn quick_join(
+        &mut self,
+        sess_id: &str,
+        time_control: Option<TimeControl>,
+        ctx: &mut Context<Self>,
+    ) -> Option<WsResponse> {
+        let failed = |reason: &str| {
+            Some(WsResponse::JoinFailed(WsJoinFailed {
+                session_id: sess_id.to_string(),
+                reason: reason.to_string(),
+            }))
+        };
+        let settings = WsBoardSettings {
+            color: ColorPreference::Random,
+            time_control,
+            ..WsBoardSettings::default()
+        };
+        if let Err(reason) = check_settings(&settings) {
+            return failed(&reason);
+        }
+        if let Err(reason) = self.leave_open_board(sess_id) {
+            return failed(reason);
+        }
+        let rating = match self.sessions.get(sess_id) {
+            Some(sess) => sess.rating.unwrap_or_default(),
+            None => return failed("unknown session"),
+        };
+        // a partner is always waiting on their board, the joiner waits in the
+        // pool until someone joins them
+        self.forget_gone_seekers();
+        let waiting_board = match self.matchmaker.seek(sess_id, rating.rating, time_control) {
+            Some(Pairing::Players(waiting_id, _)) => {
+                waiting_board(&self.sessions, &self.boards, &waiting_id)
+            }
+            _ => None,
+        };
+        match waiting_board {
+            Some(board_id) => self.join_board(sess_id, &board_id, false),
+            None => {
+                let resp = self.create_board(sess_id, settings, false, ctx);
+                if resp.is_some() {
+                    self.matchmaker.cancel(sess_id);
+                }
+                resp
+            }
+        }
+    }
+
+    /// Remove from the pool the sessions that no longer wait on an open
+    /// board, nobody could join them
+    fn forget_gone_seekers(&mut self) {
+        let sessions = &self.sessions;
+        let boards = &self.boards;
+        self.matchmaker
+            .retain(|sess_id| waiting_board(sessions, boards, sess_id).is_some());
+    }
+
+    /// Seat the players paired since the last round, and the bot in front of
+    /// those who waited too long
+    fn run_matchmaking(&mut self) {
+        self.forget_gone_seekers();
+        for pairing in self.matchmaker.tick() {
+            match pairing {
+                Pairing::Players(waiting_id, joining_id) => {
+                    if let Some(board_id) = waiting_board(&self.sessions, &self.boards, &waiting_id) {
+                        if let Some(resp) = self.join_board(&joining_id, &board_id, false) {
+                            self.send_message(resp, &joining_id);
+                        }
+                    }
+                }
+                Pairing::Bot(sess_id) => self.seat_bot(&sess_id),
+            }
+        }
+    }
+
+    fn schedule_matchmaking(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(Duration::from_millis(MATCHMAKING_INTERVAL), |act, ctx| {
+            act.run_matchmaking();
+            act.schedule_matchmaking(ctx);
+        });
+    }
+
+    /// The bot takes the free seat of the board where the session waits
+    fn seat_bot(&mut self, sess_id: &str) {
+        let board_id = match self.sessions.get(sess_id).and_then(|sess| sess.board_id.clone()) {
+            Some(board_id) => board_id,
+            None => return,
+        };
+        let (addr, seats) = match self.boards.get_mut(&board_id) {
+            Some(entry) => match entry.addr.clone() {
+                Some(addr) => match entry.seats.take(BOT_ID.to_string(), BOT_NAME.to_string()) {
+                    Some(_) => (addr, entry.seats.clone()),
+                    None => return,
+                },
+                None => return,
+            },
+            None => return,
+        };
+        info!("The bot joins the board {} of {}", board_id, sess_id);
+        addr.do_send(board::Join {
+            player: Player::bot(),
+            seats,
+        });
+    }
+
+    /// Close the board where the session waits for an opponent, if any.
+    ///
+    /// A game in progress cannot be left this way.
+    fn leave_open_board(&mut self, sess_id: &str) -> Result<(), &'static str> {
+        let board_id = match self.sessions.get(sess_id).and_then(|sess| sess.board_id.clone()) {
+            Some(board_id) => board_id,
+            None => return Ok(()),
+        };
+        if let Some(entry) = self.boards.get(&board_id) {
+            if !entry.seats.is_open() {
+                return Err("you are already playing a game");
+            }
+        }
+        info!("Closing board {}", board_id);
+        self.matchmaker.cancel(sess_id);
+        if let Some(entry) = self.boards.remove(&board_id) {
+            if let Some(code) = entry.seats.invite_code {
+                self.invites.remove(&code);
+            }
+            if let Some(addr) = entry.addr {
+                addr.do_send(board::Close);
+            }
+        }
+        self.boarding.retain(|b| *b != board_id);
+        if let Some(sess) = self.sessions.get_mut(sess_id) {
+            sess.board_id = None;
+        }
+        self.send_open_boards();
+        Ok(())
+    }
+
+    /// Subscribe the session to the moves of a game in progress
+    fn watch_board(&mut self, sess_id: &str, param: &WsWatchBoard) {
+        let refused = if param.session_id != sess_id {
+            Some("unknown session")
+        } else if self.sessions
+            .get(sess_id)
+            .and_then(|sess| sess.board_id.as_ref())
+            .is_some()
+        {
+            Some("you cannot watch a game while seated at a board")
+        } else {
+            match self.boards.get(&param.board_id) {
+                None => Some("unknown board"),
+                Some(entry) if entry.seats.settings.private => Some("unknown board"),
+                Some(entry) if entry.seats.is_open() => Some("the game has not started"),
+                Some(_) => None,
+            }
+        };
+        if let Some(reason) = refused {
+            warn!("Session {} cannot watch {}: {}", sess_id, param.board_id, reason);
+            let back = WsResponse::WatchFailed(WsWatchFailed {
+                session_id: sess_id.to_string(),
+                board_id: param.board_id.clone(),
+                reason: reason.to_string(),
+            });
+            self.send_message(back, sess_id);
+            return;
+        }
+
+        self.stop_watching(sess_id);
+        let member = match self.sessions.get_mut(sess_id) {
+            Some(sess) => {
+                sess.watching = Some(param.board_id.clone());
+                Member {
+                    addr: sess.addr.clone(),
+                    muted: sess.muted.clone(),
+                }
+            }
+            None => return,
+        };
+        // the board sends the game and its chat
+        if let Some(addr) = self.board_addr(&param.board_id) {
+            addr.do_send(board::Watch {
+                sess_id: sess_id.to_string(),
+                member,
+            });
+        }
+    }
+
+    /// Unsubscribe the session from the game it is watching, if any
+    fn stop_watching(&mut self, sess_id: &str) {
+        let board_id = match self.sessions
+            .get_mut(sess_id)
+            .and_then(|sess| sess.watching.take())
+        {
+            Some(board_id) => board_id,
+            None => return,
+        };
+        if let Some(addr) = self.board_addr(&board_id) {
+            addr.do_send(board::Unwatch {
+                sess_id: sess_id.to_string(),
+            });
+        }
+    }
+
+    /// Send a message to the players and the spectators of the board, or to
+    /// the sessions in the lobby
+    fn send_chat(&mut self, sess_id: &str, param: &WsSendChat) -> Option<WsResponse> {
+        let failed = |reason: &str| {
+            Some(WsResponse::ChatFailed(WsChatFailed {
+                session_id: sess_id.to_string(),
+                reason: reason.to_string(),
+            }))
+        };
+        if param.session_id != sess_id {
+            return failed("unknown session");
+        }
+        let text = match chat::check_text(&param.text) {
+            Ok(text) => text,
+            Err(reason) => return failed(reason),
+        };
+        let author = match self.sessions.get_mut(sess_id) {
+            Some(sess) => match sess.nickname.clone() {
+                Some(_) if param.board_id.is_none() && !sess.in_lobby => {
+                    return failed("you are not in the lobby");
+                }
+                Some(_) if !sess.chat_limit.allow(Instant::now()) => {
+                    return failed("you are sending messages too fast");
+                }
+                Some(nickname) => nickname,
+                None => return failed("unknown session"),
+            },
+            None => return failed("unknown session"),
+        };
+        let message = WsChatMessage {
+            author,
+            text,
+            sent_at: game::timestamp(),
+        };
+        if let Some(ref board_id) = param.board_id {
+            let watching = self.sessions
+                .get(sess_id)
+                .and_then(|sess| sess.watching.as_ref())
+                == Some(board_id);
+            let addr = match self.boards.get(board_id) {
+                Some(entry) if watching || entry.seats.player(sess_id) != Cell::Empty => {
+                    entry.addr.clone()
+                }
+                _ => None,
+            };
+            match addr {
+                Some(addr) => addr.do_send(board::Chat {
+                    sess_id: sess_id.to_string(),
+                    message,
+                }),
+                None => return failed("you are not at this board"),
+            }
+            return None;
+        }
+
+        self.lobby_chat.push(sess_id, message.clone());
+        for (dest_id, sess) in &self.sessions {
+            if !sess.in_lobby || sess.muted.iter().any(|id| id == sess_id) {
+                continue;
+            }
+            let back = WsResponse::Chat(WsChat {
+                session_id: dest_id.clone(),
+                board_id: None,
+                message: message.clone(),
+            });
+            let _ = sess.addr.do_send(Outgoing::new(back));
+        }
+        None
+    }
+
+    /// Stop forwarding the messages of the opponent to the session, or
+    /// forward them again
+    fn mute_opponent(&mut self, sess_id: &str, param: &WsMuteOpponent) {
+        let (opponent_id, addr) = match self.boards.get(&param.board_id) {
+            Some(entry) if param.session_id == sess_id && entry.seats.player(sess_id) != Cell::Empty => {
+                (entry.seats.opponent(sess_id).to_string(), entry.addr.clone())
+            }
+            _ => {
+                warn!("Session {} cannot mute the opponent on {}", sess_id, param.board_id);
+                return;
+            }
+        };
+        if opponent_id.is_empty() {
+            return;
+        }
+        let muted = match self.sessions.get_mut(sess_id) {
+            Some(sess) => {
+                sess.muted.retain(|id| *id != opponent_id);
+                if param.muted {
+                    sess.muted.push(opponent_id);
+                }
+                sess.muted.clone()
+            }
+            None => return,
+        };
+        if let Some(addr) = addr {
+            addr.do_send(board::Mute {
+                sess_id: sess_id.to_string(),
+                muted,
+            });
+        }
+    }
+
+    /// The address of the board where the session plays, for its resignation
+    /// or its abort
+    fn seated_board(&self, sess_id: &str, param: &WsGameAction) -> Option<Addr<Syn, BoardActor>> {
+        match self.boards.get(&param.board_id) {
+            Some(entry) if param.session_id == sess_id && entry.seats.player(sess_id) != Cell::Empty => {
+                entry.addr.clone()
+            }
+            _ => None,
+        }
+    }
+
+    /// The opponent of the session wins the game in progress
+    fn resign(&self, sess_id: &str, param: &WsGameAction) -> Option<WsResponse> {
+        match self.seated_board(sess_id, param) {
+            Some(addr) => {
+                addr.do_send(board::Resign {
+                    sess_id: sess_id.to_string(),
+                });
+                None
+            }
+            None => game_action_failed(sess_id, param, "you are not playing on this board"),
+        }
+    }
+
+    /// End the game without winner, before both players have moved
+    fn abort(&self, sess_id: &str, param: &WsGameAction) -> Option<WsResponse> {
+        match self.seated_board(sess_id, param) {
+            Some(addr) => {
+                addr.do_send(board::Abort {
+                    sess_id: sess_id.to_string(),
+                });
+                None
+            }
+            None => game_action_failed(sess_id, param, "you are not playing on this board"),
+        }
+    }
+
+    /// Offer the opponent of the finished game to play again, the rematch
+    /// starts if they offered it too
+    fn offer_rematch(
+        &mut self,
+        sess_id: &str,
+        param: &WsGameAction,
+        ctx: &mut Context<Self>,
+    ) -> Option<WsResponse> {
+        let (opponent_id, accepted) = match self.rematches.get_mut(&param.board_id) {
+            Some(ref mut rematch) if param.session_id == sess_id => {
+                let opponent_id = match rematch.opponent(sess_id) {
+                    Some(opponent_id) => opponent_id.to_string(),
+                    None => return game_action_failed(sess_id, param, "unknown game"),
+                };
+                // the bot always plays again
+                let accepted =
+                    opponent_id == BOT_ID || rematch.offered_by.as_ref() == Some(&opponent_id);
+                rematch.offered_by = Some(sess_id.to_string());
+                (opponent_id, accepted)
+            }
+            _ => return game_action_failed(sess_id, param, "the rematch is not possible anymore"),
+        };
+        if accepted {
+            if let Err(reason) = self.start_rematch(&param.board_id, ctx) {
+                return game_action_failed(sess_id, param, reason);
+            }
+        } else {
+            info!("Session {} offers a rematch of {}", sess_id, param.board_id);
+            let back = WsResponse::RematchOffered(WsRematch {
+                session_id: opponent_id.clone(),
+                board_id: param.board_id.clone(),
+            });
+            self.send_message(back, &opponent_id);
+        }
+        None
+    }
+
+    /// Refuse the rematch offered by the opponent, or withdraw the offer
+    fn decline_rematch(&mut self, sess_id: &str, param: &WsGameAction) -> Option<WsResponse> {
+        let player = param.session_id == sess_id
+            && self.rematches
+                .get(&param.board_id)
+                .and_then(|rematch| rematch.opponent(sess_id))
+                .is_some();
+        if !player {
+            return game_action_failed(sess_id, param, "the rematch is not possible anymore");
+        }
+        self.cancel_rematch(&param.board_id, sess_id);
+        None
+    }
 
-/// Session is disconnected
-#[derive(Message)]
-pub struct Disconnect {
-    pub id: String,
-}
+    /// Seat the players of the finished game on a new board, the colors
+    /// swapped, the game starts
+    fn start_rematch(&mut self, finished_id: &str, ctx: &mut Context<Self>) -> Result<(), &'static str> {
+        let (black, white, settings) = match self.rematches.get(finished_id) {
+            Some(rematch) => (
+                rematch.white.clone(),
+                rematch.black.clone(),
+                rematch.settings.clone(),
+            ),
+            None => return Err("the rematch is not possible anymore"),
+        };
+        let board_id = self.start_game(&black, &white, settings, None, ctx)?;
+        self.rematches.remove(finished_id);
+        info!("Rematch of {} on the board {}", finished_id, board_id);
+        Ok(())
+    }
 
-#[derive(Message)]
-pub struct ClientMessage {
-    pub id: String,
-    pub request: WsRequest,
-}
+    /// Seat both players on a new board, the game starts
+    fn start_game(
+        &mut self,
+        black: &str,
+        white: &str,
+        settings: WsBoardSettings,
+        tournament: Option<String>,
+        ctx: &mut Context<Self>,
+    ) -> Result<String, &'static str> {
+        if self.boards.len() >= self.max_boards {
+            warn!("Too many boards, the game of {} and {} cannot start", black, white);
+            return Err("All the tables are taken, try again later");
+        }
+        let players: Vec<Player> = [black, white]
+            .iter()
+            .filter_map(|sess_id| self.board_player(sess_id))
+            .collect();
+        let nickname = |sess_id: &str| {
+            players
+                .iter()
+                .find(|player| player.sess_id == sess_id)
+                .map(|player| player.nickname.clone())
+                .unwrap_or_default()
+        };
+        let board_id = self.new_board_id();
+        let mut seats = Seats::new(black.to_string(), nickname(black), Color::Black, settings);
+        seats.take(white.to_string(), nickname(white));
+        seats.tournament = tournament;
 
-pub struct SessionData {
-    // where the user is joinable
-    addr: Recipient<Syn, WsResponse>,
-    // the nickname received in the ConnectionParam
-    nickname: Option<String>,
-    // a board where the user is actually
-    board_id: Option<String>,
-}
+        for sess_id in &[black, white] {
+            self.stop_watching(sess_id);
+            if let Some(sess) = self.sessions.get_mut(*sess_id) {
+                sess.board_id = Some(board_id.clone());
+                sess.in_lobby = false;
+            }
+        }
+        // the board tells the players they are seated and starts the clocks
+        self.start_board(&board_id, seats, players, ctx);
+        Ok(board_id)
+    }
 
-pub struct OthelloActor {
-    /// session_id to session address
-    sessions: HashMap<String, SessionData>,
-    /// boards (black session_id, white session_id or empty string)
-    boards: HashMap<String, (String, String)>,
-    /// the list of boards waiting for a partner
-    boarding: Vec<String>,
-    rng: RefCell<ThreadRng>,
-}
+    /// Drop the rematch of the finished game, the other player is told
+    fn cancel_rematch(&mut self, finished_id: &str, sess_id: &str) {
+        let opponent_id = match self.rematches.remove(finished_id) {
+            Some(rematch) => match rematch.opponent(sess_id) {
+                Some(opponent_id) => opponent_id.to_string(),
+                None => return,
+            },
+            None => return,
+        };
+        info!("No rematch of {}", finished_id);
+        let back = WsResponse::RematchDeclined(WsRematch {
+            session_id: opponent_id.clone(),
+            board_id: finished_id.to_string(),
+        });
+        self.send_message(back, &opponent_id);
+    }
 
-impl Default for OthelloActor {
-    fn default() -> OthelloActor {
-        OthelloActor {
-            sessions: HashMap::new(),
-            boards: HashMap::new(),
-            boarding: Vec::new(),
-            rng: RefCell::new(rand::thread_rng()),
+    /// The session leaves or starts another game, its last game cannot be
+    /// played again
+    fn forget_rematches(&mut self, sess_id: &str) {
+        let finished: Vec<String> = self.rematches
+            .iter()
+            .filter(|(_, rematch)| rematch.opponent(sess_id).is_some())
+            .map(|(finished_id, _)| finished_id.clone())
+            .collect();
+        for finished_id in finished {
+            self.cancel_rematch(&finished_id, sess_id);
         }
     }
-}
 
-impl OthelloActor {
-    /// Send message to all users in the room
-    fn send_message(&self, resp: WsResponse, dest_id: &str) {
-        if let Some(ref sess) = self.sessions.get(dest_id) {
-            let _ = sess.addr.do_send(resp);
-        } else {
-            warn!("Receive a message to an invalid id");
+    /// Send the last messages of the lobby to the session
+    fn send_chat_history(&self, sess_id: &str) {
+        let muted = match self.sessions.get(sess_id) {
+            Some(sess) => &sess.muted,
+            None => return,
+        };
+        let back = WsResponse::ChatHistory(WsChatHistory {
+            session_id: sess_id.to_string(),
+            board_id: None,
+            messages: self.lobby_chat.messages(muted),
+        });
+        self.send_message(back, sess_id);
+    }
+
+    fn nickname(&self, sess_id: &str) -> Option<&String> {
+        self.sessions
+            .get(sess_id)
+            .and_then(|sess| sess.nickname.as_ref())
+    }
+
+    fn identity(&self, sess_id: &str) -> Option<String> {
+        self.sessions.get(sess_id).and_then(SessionData::identity)
+    }
+
+    /// Who the user of the nickname is: the account of the name, or the
+    /// session of the guest, `None` if nobody uses it
+    fn identity_of(&self, nickname: &str) -> Option<String> {
+        let key = account::name_key(nickname);
+        if self.accounts.contains(&key) {
+            return Some(format!("account:{}", key));
+        }
+        self.sessions
+            .values()
+            .find(|sess| sess.nickname.iter().any(|name| name == nickname))
+            .and_then(SessionData::identity)
+    }
+
+    /// A session of the nickname able to sit at a tournament game: connected,
+    /// not playing another game and of `identity` if the player is known
+    fn free_session(&self, nickname: &str, identity: &Option<String>) -> Option<String> {
+        self.sessions
+            .iter()
+            .find(|&(_, sess)| {
+                sess.nickname.iter().any(|name| name == nickname)
+                    && sess.away.is_none()
+                    && match *identity {
+                        Some(ref identity) => sess.identity().as_ref() == Some(identity),
+                        None => true,
+                    }
+                    && match sess.board_id {
+                        Some(ref board_id) => self.boards
+                            .get(board_id)
+                            .map(|entry| entry.seats.is_open())
+                            .unwrap_or(true),
+                        None => true,
+                    }
+            })
+            .map(|(sess_id, _)| sess_id.clone())
+    }
+
+    /// Create a tournament organized by the session, waiting to be started
+    fn create_tournament(&mut self, sess_id: &str, param: &WsCreateTournament) -> Option<WsResponse> {
+        let organizer = (self.nickname(sess_id), self.identity(sess_id));
+        let (organizer, organizer_identity) = match organizer {
+            (Some(nickname), Some(identity)) if param.session_id == sess_id => {
+                (nickname.clone(), identity)
+            }
+            _ => return tournament_failed(sess_id, "connect with a nickname first"),
+        };
+        let unfinished = self.tournaments
+            .values()
+            .filter(|tournament| tournament.status() != TournamentStatus::Finished)
+            .count();
+        if unfinished >= MAX_TOURNAMENTS {
+            warn!("Too many tournaments, refusing a new one");
+            return tournament_failed(sess_id, "Too many tournaments, try again later");
+        }
+        let board_settings = WsBoardSettings {
+            time_control: param.settings.time_control,
+            ..WsBoardSettings::default()
+        };
+        let mut tournament = match check_settings(&board_settings)
+            .and_then(|()| Tournament::new(param.settings.clone(), organizer, organizer_identity))
+        {
+            Ok(tournament) => tournament,
+            Err(reason) => return tournament_failed(sess_id, &reason),
+        };
+        // the nicknames in use are those of the players
+        for player in &param.settings.players {
+            if let Some(identity) = self.identity_of(player) {
+                tournament.claim(player, identity);
+            }
+        }
+        let tournament_id = self.new_board_id();
+        info!(
+            "Session {} creates the tournament {} of {} players",
+            sess_id,
+            tournament_id,
+            param.settings.players.len()
+        );
+        self.tournaments.insert(tournament_id.clone(), tournament);
+        self.send_tournament(&tournament_id);
+        None
+    }
+
+    /// The organizer starts the first round
+    fn start_tournament(
+        &mut self,
+        sess_id: &str,
+        param: &WsTournamentAction,
+        ctx: &mut Context<Self>,
+    ) -> Option<WsResponse> {
+        let refused = match self.tournaments.get(&param.tournament_id) {
+            Some(tournament)
+                if param.session_id == sess_id
+                    && self.identity(sess_id)
+                        .iter()
+                        .any(|identity| tournament.is_organizer(identity)) =>
+            {
+                if tournament.status() != TournamentStatus::Pending {
+                    Some("the tournament has already started")
+                } else {
+                    None
+                }
+            }
+            Some(_) => Some("only the organizer starts the tournament"),
+            None => Some("unknown tournament"),
+        };
+        if let Some(reason) = refused {
+            return tournament_failed(sess_id, reason);
+        }
+        info!("Tournament {} starts", param.tournament_id);
+        self.next_round(&param.tournament_id, ctx);
+        let started = self.tournaments
+            .get(&param.tournament_id)
+            .map(|tournament| tournament.status() != TournamentStatus::Pending)
+            .unwrap_or(false);
+        if !started {
+            return tournament_failed(sess_id, "not enough players left to start");
+        }
+        None
+    }
+
+    fn get_tournament(&self, sess_id: &str, param: &WsTournamentAction) -> Option<WsResponse> {
+        match self.tournaments.get(&param.tournament_id) {
+            Some(tournament) => Some(WsResponse::Tournament(WsTournament {
+                session_id: sess_id.to_string(),
+                tournament: tournament.state(&param.tournament_id),
+            })),
+            None => tournament_failed(sess_id, "unknown tournament"),
+        }
+    }
+
+    /// The player leaves the tournament, by themselves or by the organizer.
+    /// Their game in progress is lost.
+    fn withdraw(&mut self, sess_id: &str, param: &WsWithdraw) -> Option<WsResponse> {
+        let nickname = self.nickname(sess_id).cloned();
+        let identity = self.identity(sess_id);
+        let withdrawn = match self.tournaments.get_mut(&param.tournament_id) {
+            Some(tournament) => {
+                let allowed = match (nickname, identity) {
+                    (Some(nickname), Some(identity)) => {
+                        param.session_id == sess_id
+                            && (tournament.is_organizer(&identity)
+                                || (nickname == param.player
+                                    && tournament.claim(&param.player, identity)))
+                    }
+                    _ => false,
+                };
+                if !allowed {
+                    return tournament_failed(sess_id, "only the player or the organizer withdraws a player");
+                }
+                tournament.withdraw(&param.player)
+            }
+            None => return tournament_failed(sess_id, "unknown tournament"),
+        };
+        let board_id = match withdrawn {
+            Ok(board_id) => board_id,
+            Err(reason) => return tournament_failed(sess_id, reason),
+        };
+        info!("{} withdraws from the tournament {}", param.player, param.tournament_id);
+        let leaver = board_id.as_ref().and_then(|board_id| {
+            self.boards.get(board_id).map(|entry| {
+                if entry.seats.black_name == param.player {
+                    Cell::Black
+                } else {
+                    Cell::White
+                }
+            })
+        });
+        match (board_id, leaver) {
+            (Some(board_id), Some(leaver)) => {
+                // the result is recorded, the tournament is sent
+                self.end_game(&board_id, leaver.opposite(), GameOverReason::Resignation);
+            }
+            _ => self.send_tournament(&param.tournament_id),
+        }
+        None
+    }
+
+    /// Record the result of the tournament game, the next round is paired
+    /// once all the games of the round are over
+    fn record_tournament_game(
+        &mut self,
+        tournament_id: &str,
+        board_id: &str,
+        winner: Cell,
+        ctx: &mut Context<Self>,
+    ) {
+        let outcome = match winner {
+            Cell::Black => GameOutcome::BlackWins,
+            Cell::White => GameOutcome::WhiteWins,
+            Cell::Empty => GameOutcome::Draw,
+        };
+        let round_complete = match self.tournaments.get_mut(tournament_id) {
+            Some(tournament) => {
+                if let Err(reason) = tournament.record(board_id, outcome) {
+                    error!("Cannot record the game {} of {}: {}", board_id, tournament_id, reason);
+                }
+                tournament.round_complete()
+            }
+            None => return,
+        };
+        self.send_tournament(tournament_id);
+        if round_complete {
+            let tournament_id = tournament_id.to_string();
+            ctx.run_later(Duration::from_millis(ROUND_DELAY), move |act, ctx| {
+                act.next_round(&tournament_id, ctx);
+            });
+        }
+    }
+
+    /// Pair the next round of the tournament and start its games, if any
+    fn next_round(&mut self, tournament_id: &str, ctx: &mut Context<Self>) {
+        loop {
+            let paired = match self.tournaments.get_mut(tournament_id) {
+                Some(tournament) => tournament.pair_next_round(),
+                None => return,
+            };
+            if !paired {
+                break;
+            }
+            self.start_round(tournament_id, ctx);
+            // a round of forfeits only is over at once
+            let round_complete = self.tournaments
+                .get(tournament_id)
+                .map(|tournament| tournament.round_complete())
+                .unwrap_or(false);
+            if !round_complete {
+                break;
+            }
+        }
+        self.send_tournament(tournament_id);
+    }
+
+    /// Start the games of the round, a player without a free session
+    /// forfeits their game
+    fn start_round(&mut self, tournament_id: &str, ctx: &mut Context<Self>) {
+        let (games, settings) = match self.tournaments.get(tournament_id) {
+            Some(tournament) => (
+                tournament.games_to_start(),
+                WsBoardSettings {
+                    time_control: tournament.time_control,
+                    rated: tournament.rated,
+                    ..WsBoardSettings::default()
+                },
+            ),
+            None => return,
+        };
+        for (index, black, white) in games {
+            let (black_identity, white_identity) = match self.tournaments.get(tournament_id) {
+                Some(tournament) => (tournament.identity(&black), tournament.identity(&white)),
+                None => return,
+            };
+            let black_id = self.free_session(&black, &black_identity);
+            let white_id = self.free_session(&white, &white_identity);
+            // the players are who sat at their first game
+            let seated = [
+                (&black, black_id.as_ref().and_then(|id| self.identity(id))),
+                (&white, white_id.as_ref().and_then(|id| self.identity(id))),
+            ];
+            let started = match (black_id, white_id) {
+                (Some(black_id), Some(white_id)) => {
+                    for sess_id in &[&black_id, &white_id] {
+                        let _ = self.leave_open_board(sess_id);
+                        self.forget_rematches(sess_id);
+                    }
+                    self.start_game(
+                        &black_id,
+                        &white_id,
+                        settings.clone(),
+                        Some(tournament_id.to_string()),
+                        ctx,
+                    ).map_err(|reason| {
+                        error!("The game of {} and {} cannot start: {}", black, white, reason);
+                        (true, true)
+                    })
+                }
+                (black_id, white_id) => Err((black_id.is_some(), white_id.is_some())),
+            };
+            if let Some(tournament) = self.tournaments.get_mut(tournament_id) {
+                match started {
+                    Ok(board_id) => {
+                        tournament.set_board(index, &board_id);
+                        for &(nickname, ref identity) in &seated {
+                            if let Some(ref identity) = *identity {
+                                tournament.claim(nickname, identity.clone());
+                            }
+                        }
+                    }
+                    Err((black_present, white_present)) => {
+                        info!("Forfeit of {} against {}", black, white);
+                        tournament.forfeit(index, black_present, white_present);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send the tournament to the sessions of the organizer and the players
+    fn send_tournament(&self, tournament_id: &str) {
+        let tournament = match self.tournaments.get(tournament_id) {
+            Some(tournament) => tournament,
+            None => return,
+        };
+        let state = tournament.state(tournament_id);
+        for (sess_id, sess) in &self.sessions {
+            let concerned = match (sess.nickname.as_ref(), sess.identity()) {
+                (Some(nickname), Some(identity)) => {
+                    tournament.is_organizer(&identity) || tournament.is_player(nickname, &identity)
+                }
+                _ => false,
+            };
+            if concerned {
+                let back = WsResponse::Tournament(WsTournament {
+                    session_id: sess_id.clone(),
+                    tournament: state.clone(),
+                });
+                self.send_message(back, sess_id);
+            }
         }
     }
 }
@@ -78,30 +1842,52 @@ impl Actor for OthelloActor {
     /// We are going to use simple Context, we just need ability to communicate
     /// with other actors.
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.schedule_matchmaking(ctx);
+        self.schedule_sweep(ctx);
+        self.load_accounts(ctx);
+    }
 }
 
 /// Handler for Connect message.
 ///
 /// Register new session and assign unique id to this session
 impl Handler<Connect> for OthelloActor {
-    type Result = String;
+    type Result = MessageResult<Connect>;
 
     fn handle(&mut self, msg: Connect, _: &mut Context<Self>) -> Self::Result {
+        if self.sessions.len() >= self.max_sessions {
+            warn!("Too many sessions, connection refused");
+            return MessageResult(None);
+        }
         // register session with random id
         let id: String = iter::repeat(())
             .map(|()| self.rng.borrow_mut().sample(Alphanumeric))
             .take(40)
             .collect();
+        let resume_token: String = iter::repeat(())
+            .map(|()| self.rng.borrow_mut().sample(Alphanumeric))
+            .take(40)
+            .collect();
         self.sessions.insert(
             id.clone(),
             SessionData {
                 addr: msg.addr,
                 nickname: None,
+                guest: true,
                 board_id: None,
+                resume_token,
+                away: None,
+                watching: None,
+                in_lobby: false,
+                rating: None,
+                chat_limit: chat::rate_limit(),
+                muted: Vec::new(),
             },
         );
         // send id back
-        id
+        MessageResult(Some(id))
     }
 }
 
@@ -109,40 +1895,199 @@ impl Handler<Connect> for OthelloActor {
 impl Handler<Disconnect> for OthelloActor {
     type Result = ();
 
-    fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
-        let boarding = self.boarding.clone(); // cannot move out of borrowed content
-        if let Some(session) = self.sessions.remove(&msg.id) {
-            info!("Closing session {}", msg.id);
-            if let Some(ref board_id) = session.board_id {
-                if let Some(brd) = self.boards.remove(board_id) {
-                    info!("Closing board {}", board_id);
-                    // if the board where waiing for someone
-                    self.boarding = boarding.into_iter().filter(|b| b != board_id).collect();
-                    if brd.1 == msg.id {
-                        let opponent_sess = self.sessions.get(&brd.0);
-                        if let Some(opp_sess) = opponent_sess {
-                            let back = WsResponse::OpponentDisconnected(WsOpponentDisconnected {
-                                session_id: brd.0.clone(),
-                                board_id: board_id.clone(),
-                            });
-                            let _ = opp_sess.addr.do_send(back);
-                        }
-                    } else if brd.0 == msg.id {
-                        let opponent_sess = self.sessions.get(&brd.1);
-                        if let Some(opp_sess) = opponent_sess {
-                            let back = WsResponse::OpponentDisconnected(WsOpponentDisconnected {
-                                session_id: brd.1.clone(),
-                                board_id: board_id.clone(),
-                            });
-                            let _ = opp_sess.addr.do_send(back);
-                        }
-                    }
-                }
+    fn handle(&mut self, msg: Disconnect, ctx: &mut Context<Self>) {
+        let in_game = match self.sessions.get(&msg.id) {
+            Some(&SessionData {
+                board_id: Some(ref board_id),
+                ..
+            }) => self.boards
+                .get(board_id)
+                .filter(|entry| !entry.seats.opponent(&msg.id).is_empty())
+                .and_then(|entry| entry.addr.clone())
+                .map(|addr| (board_id.clone(), addr)),
+            _ => None,
+        };
+        if let Some((board_id, addr)) = in_game {
+            // hold the seat, the player may resume the session
+            info!("Session {} is away from board {}", msg.id, board_id);
+            let id = msg.id.clone();
+            let handle = ctx.run_later(self.resume_grace, move |act, _| {
+                info!("Session {} did not come back", id);
+                act.close_session(&id);
+            });
+            if let Some(sess) = self.sessions.get_mut(&msg.id) {
+                sess.away = Some(handle);
             }
-            info!("Session {} closed", msg.id);
+            // the opponent and the spectators are told
+            addr.do_send(board::Away { sess_id: msg.id });
         } else {
-            error!("Unregistered session has disconnect");
+            self.close_session(&msg.id);
+        }
+    }
+}
+
+/// Handler for ListBoards message.
+impl Handler<ListBoards> for OthelloActor {
+    type Result = MessageResult<ListBoards>;
+
+    fn handle(&mut self, msg: ListBoards, _: &mut Context<Self>) -> Self::Result {
+        let mut boards: Vec<BoardSummary> = self.boards
+            .values()
+            .filter(|entry| !entry.seats.settings.private)
+            .filter(|entry| match msg.player {
+                Some(ref player) => entry.seats.black_name == *player || entry.seats.white_name == *player,
+                None => true,
+            })
+            .map(|entry| entry.summary.clone())
+            .collect();
+        boards.sort_by_key(|board| Reverse(board.started_at));
+        MessageResult(boards)
+    }
+}
+
+/// Handler for AccountRegistered message.
+impl Handler<AccountRegistered> for OthelloActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: AccountRegistered, _: &mut Context<Self>) {
+        self.accounts.insert(account::name_key(&msg.0));
+    }
+}
+
+/// Handler for GetMetrics message.
+impl Handler<GetMetrics> for OthelloActor {
+    type Result = MessageResult<GetMetrics>;
+
+    fn handle(&mut self, _: GetMetrics, _: &mut Context<Self>) -> Self::Result {
+        let mut gauges = Gauges {
+            sessions: self.sessions
+                .values()
+                .filter(|sess| sess.away.is_none())
+                .count(),
+            ..Gauges::default()
+        };
+        for (board_id, entry) in &self.boards {
+            if !entry.seats.is_open() {
+                gauges.playing_boards += 1;
+            } else if entry.seats.settings.private || self.boarding.contains(board_id) {
+                gauges.open_boards += 1;
+            } else {
+                // the board of a quick game is not listed
+                gauges.waiting_boards += 1;
+            }
+        }
+        MessageResult(self.metrics.render(&gauges))
+    }
+}
+
+/// Handler for ListTournaments message.
+impl Handler<ListTournaments> for OthelloActor {
+    type Result = MessageResult<ListTournaments>;
+
+    fn handle(&mut self, _: ListTournaments, _: &mut Context<Self>) -> Self::Result {
+        let mut tournaments: Vec<WsTournamentState> = self.tournaments
+            .iter()
+            .map(|(tournament_id, tournament)| tournament.state(tournament_id))
+            .collect();
+        tournaments.sort_by(|a, b| a.name.cmp(&b.name));
+        MessageResult(tournaments)
+    }
+}
+
+/// Handler for GetTournament message.
+impl Handler<GetTournament> for OthelloActor {
+    type Result = MessageResult<GetTournament>;
+
+    fn handle(&mut self, msg: GetTournament, _: &mut Context<Self>) -> Self::Result {
+        MessageResult(
+            self.tournaments
+                .get(&msg.tournament_id)
+                .map(|tournament| tournament.state(&msg.tournament_id)),
+        )
+    }
+}
+
+/// Handler for Resume message.
+impl Handler<Resume> for OthelloActor {
+    type Result = MessageResult<Resume>;
+
+    fn handle(&mut self, msg: Resume, ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.resume_session(msg, ctx))
+    }
+}
+
+impl OthelloActor {
+    fn resume_session(&mut self, msg: Resume, ctx: &mut Context<Self>) -> Option<String> {
+        let resumed_id = self.sessions
+            .iter()
+            .find(|&(id, sess)| *id != msg.id && sess.resume_token == msg.resume_token)
+            .map(|(id, sess)| (id.clone(), sess.away.is_some()));
+        let reason = match resumed_id {
+            Some((_, false)) => Some("the session is still connected"),
+            None => Some("unknown session"),
+            Some((_, true)) => None,
+        };
+        if let Some(reason) = reason {
+            warn!("Session {} cannot be resumed: {}", msg.id, reason);
+            let back = WsResponse::ResumeFailed(WsResumeFailed {
+                reason: reason.to_string(),
+            });
+            self.send_message(back, &msg.id);
+            return None;
         }
+        let resumed_id = resumed_id.unwrap().0;
+        let new_sess = self.sessions.remove(&msg.id)?;
+        let (nickname, board_id) = {
+            let sess = self.sessions.get_mut(&resumed_id)?;
+            sess.addr = new_sess.addr.clone();
+            if let Some(handle) = sess.away.take() {
+                ctx.cancel_future(handle);
+            }
+            (sess.nickname.clone(), sess.board_id.clone())
+        };
+        info!("Session {} resumed", resumed_id);
+
+        let resumed = WsResumed {
+            session_id: resumed_id.clone(),
+            nickname,
+            users_count: self.sessions.len(),
+            board: None,
+        };
+        let addr = board_id
+            .and_then(|board_id| self.board_addr(&board_id))
+            .filter(|addr| addr.connected());
+        match addr {
+            // the board sends the game with the session, its chat, and tells
+            // the opponent
+            Some(addr) => addr.do_send(board::Back {
+                sess_id: resumed_id.clone(),
+                addr: new_sess.addr,
+                resumed,
+            }),
+            None => self.send_message(WsResponse::Resumed(resumed), &resumed_id),
+        }
+        Some(resumed_id)
+    }
+}
+
+/// Handler for BoardChanged message.
+impl Handler<BoardChanged> for OthelloActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: BoardChanged, _: &mut Context<Self>) {
+        let summary = msg.0;
+        if let Some(entry) = self.boards.get_mut(&summary.board_id) {
+            entry.summary = summary;
+        }
+    }
+}
+
+/// Handler for GameOver message.
+impl Handler<GameOver> for OthelloActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: GameOver, ctx: &mut Context<Self>) {
+        self.game_over(msg, ctx);
     }
 }
 
@@ -150,210 +2095,147 @@ impl Handler<Disconnect> for OthelloActor {
 impl Handler<ClientMessage> for OthelloActor {
     type Result = ();
 
-    fn handle(&mut self, msg: ClientMessage, _: &mut Context<Self>) {
+    fn handle(&mut self, msg: ClientMessage, ctx: &mut Context<Self>) {
         let req = msg.request;
         let resp = {
             match req {
                 WsRequest::ConnectingParam(ref param) => {
                     let users_count = { self.sessions.len() };
-                    let session = self.sessions.get_mut(&msg.id);
-                    if session.is_none() {
+                    if !self.sessions.contains_key(&msg.id) {
                         error!("Receiving an invalid session id {}", msg.id);
                         return;
                     }
-                    let session = session.unwrap();
-                    session.nickname = Some(param.nickname.clone());
-                    let resp = WsResponse::ConnectedParam(WsConnectedParam {
-                        session_id: msg.id.clone(),
-                        users_count: users_count,
-                    });
-                    Some(resp)
+                    if let Err(reason) = self.check_nickname(&msg.id, &param.nickname) {
+                        info!("Nickname of {} refused: {}", msg.id, reason);
+                        login_failed(&msg.id, &reason)
+                    } else {
+                        let session = self.sessions.get_mut(&msg.id).unwrap();
+                        session.nickname = Some(param.nickname.clone());
+                        session.guest = true;
+                        session.rating = None;
+                        Some(WsResponse::ConnectedParam(WsConnectedParam {
+                            session_id: msg.id.clone(),
+                            users_count: users_count,
+                            resume_token: session.resume_token.clone(),
+                        }))
+                    }
                 }
                 WsRequest::JoinBoard(ref param) => {
+                    // a player cannot watch another game
+                    self.stop_watching(&msg.id);
                     info!("Boarding: {:?}", self.boarding);
-                    if self.boarding.len() > 0 {
-                        // join the board as a white player
-                        let board_id = self.boarding.remove(0);
-
-                        let board = self.boards.get_mut(&board_id);
-                        let opponent = if let Some(brd) = board {
-                            let opponent_sess = self.sessions.get(&brd.0);
-
-                            brd.1 = param.session_id.clone();
-                            if let Some(ref opp_sess) = opponent_sess {
-                                if let Some(ref name) = opp_sess.nickname {
-                                    // notify the first user of the board he can play
-                                    let self_sess = self.sessions.get(&param.session_id);
-                                    if let Some(ref sess) = self_sess {
-                                        let self_nick = sess.nickname.as_ref().unwrap().as_str();
-                                        let back = WsResponse::OpponentJoinedBoard(
-                                            WsOpponentJoinedBoard {
-                                                session_id: brd.0.clone(),
-                                                board_id: board_id.clone(),
-                                                opponent: self_nick.to_string(),
-                                            },
-                                        );
-                                        info!("Sending back message");
-                                        let _ = opp_sess.addr.do_send(back);
-                                    }
-
-                                    Some(name.to_owned())
-                                } else {
-                                    error!("A user cannot join a board without a nick");
-                                    None
-                                }
-                            } else {
-                                error!(
-                                    "No session on white user, the board should have been cleeaned"
-                                );
-                                None
-                            }
-                        } else {
-                            error!(
-                                "Invalid board id {} retrieved in the boarding queue",
-                                board_id
-                            );
-                            None
-                        };
-
-                        // register the board_id
-                        if opponent.is_some() {
-                            let mut self_sess = self.sessions.get_mut(&param.session_id);
-                            if let Some(ref mut sess) = self_sess {
-                                sess.board_id = Some(board_id.clone());
-                            }
-                        }
-
-                        Some(WsResponse::JoinedBoard(WsJoinedBoard {
-                            session_id: param.session_id.clone(),
-                            board_id: board_id,
-                            color: Color::White,
-                            opponent: opponent,
+                    match (param.board_id.as_ref(), param.invite_code.as_ref()) {
+                        (_, Some(code)) => self.join_by_invite(&msg.id, code),
+                        (Some(board_id), None) => self.join_board(&msg.id, board_id, false),
+                        (None, None) => self.quick_join(&msg.id, param.time_control, ctx),
+                    }
+                }
+                WsRequest::CreateBoard(ref param) => {
+                    self.stop_watching(&msg.id);
+                    let listed = !param.settings.private;
+                    self.create_board(&msg.id, param.settings.clone(), listed, ctx)
+                }
+                WsRequest::CancelBoard(ref param) => {
+                    let waiting = self.sessions
+                        .get(&msg.id)
+                        .and_then(|sess| sess.board_id.as_ref())
+                        == Some(&param.board_id);
+                    let cancelled = param.session_id == msg.id
+                        && waiting
+                        && self.leave_open_board(&msg.id).is_ok();
+                    if cancelled {
+                        Some(WsResponse::BoardCancelled(WsBoardCancelled {
+                            session_id: msg.id.clone(),
+                            board_id: param.board_id.clone(),
                         }))
                     } else {
-                        let board_id: String = iter::repeat(())
-                            .map(|()| self.rng.borrow_mut().sample(Alphanumeric))
-                            .take(12)
-                            .collect();
-                        let self_sess = self.sessions.get_mut(&param.session_id);
-                        if let Some(sess) = self_sess {
-                            // create the board and join it as a black player
-                            self.boards.insert(
-                                board_id.clone(),
-                                (param.session_id.clone(), "".to_owned()),
-                            );
-                            self.boarding.push(board_id.clone());
-                            // register the user on the created board
-                            sess.board_id = Some(board_id.clone());
-
-                            Some(WsResponse::JoinedBoard(WsJoinedBoard {
-                                session_id: param.session_id.clone(),
-                                board_id: board_id,
-                                color: Color::Black,
-                                opponent: None,
-                            }))
-                        } else {
-                            error!("Unknown session id receided to join the board");
-                            None
-                        }
+                        Some(WsResponse::JoinFailed(WsJoinFailed {
+                            session_id: msg.id.clone(),
+                            reason: "the board cannot be cancelled".to_string(),
+                        }))
                     }
                 }
-                WsRequest::PlayBoard(ref param) => {
-                    let sess_id = param.session_id.as_str();
-                    let board = self.boards.get(&param.board_id);
-                    if let Some(ref brd) = board {
-                        let opponent_msg = if brd.0.as_str() == sess_id {
-                            // black played, send the move to the white
-                            let opponent_sess = self.sessions.get(&brd.1);
-                            if let Some(ref opp_sess) = opponent_sess {
-                                Some((
-                                    &opp_sess.addr,
-                                    WsResponse::PlayedBoard(WsPlayBoard {
-                                        session_id: brd.1.clone(),
-                                        board_id: param.board_id.clone(),
-                                        pos: param.pos.clone(),
-                                    }),
-                                ))
-                            } else {
-                                // Should not happen
-                                // the session is in error, should drop the board
-                                None
-                            }
-                        } else if brd.1.as_str() == sess_id {
-                            // white played, send the move to the black
-                            let opponent_sess = self.sessions.get(&brd.0);
-                            if let Some(ref opp_sess) = opponent_sess {
-                                Some((
-                                    &opp_sess.addr,
-                                    WsResponse::PlayedBoard(WsPlayBoard {
-                                        session_id: brd.0.clone(),
-                                        board_id: param.board_id.clone(),
-                                        pos: param.pos.clone(),
-                                    }),
-                                ))
-                            } else {
-                                // Should not happen
-                                // the session is in error, should drop the board
-                                None
-                            }
-                        } else {
-                            // Should not happen
-                            // DROP THE BOARD
-                            // Send End Board to players
-                            None
-                        };
-                        if let Some((addr, msg)) = opponent_msg {
-                            info!("Forwarding the move");
-                            let _ = addr.do_send(msg);
-                        }
-                        None
-                    } else {
-                        None
+                WsRequest::ListOpenBoards => {
+                    if let Some(sess) = self.sessions.get_mut(&msg.id) {
+                        sess.in_lobby = true;
                     }
+                    self.send_chat_history(&msg.id);
+                    Some(WsResponse::OpenBoards(self.open_boards()))
                 }
-                WsRequest::GameOver(ref param) => {
-
-                    let self_sess = self.sessions.get(&param.session_id);
-                    if let Some(sess) = self_sess {
-                        let del_board = if sess.board_id.as_ref() == Some(&param.board_id) {
-                            let mut board = self.boards.get_mut(&param.board_id);
-                            if let Some(ref mut brd) = board {
-                                //
-                                let sess_id = param.session_id.as_str();
-                                if brd.0.as_str() == sess_id {
-                                    info!("Closing black board");
-                                    brd.0 = "".to_string();
-                                } else if brd.1.as_str() == sess_id {
-                                    info!("Closing white board");
-                                    brd.0 = "".to_string();
-                                }
-                                if brd.0.len() == 0 && brd.1.len() == 0 {
-                                    Some(&param.board_id)
-                                }
-                                else {
-                                    None
-                                }
-                            }
-                            else {
-                                error!(
-                                    "User {} is pointing to an deleted board id",
-                                    sess.nickname.as_ref().unwrap_or(&"UNKNWOWN".to_string()));
-                                None
-                            }
-                        }
-                        else {
-                            error!(
-                                "User {} is hijacking a board id",
-                                sess.nickname.as_ref().unwrap_or(&"UNKNWOWN".to_string()));
-                            None
-                        };
-                        if let Some(ref board_id) = del_board {
-                            info!("Removing the board {}", board_id);
-                            let _ = self.boards.remove(*board_id);
-                        }
+                WsRequest::LeaveLobby => {
+                    if let Some(sess) = self.sessions.get_mut(&msg.id) {
+                        sess.in_lobby = false;
                     }
                     None
                 }
+                WsRequest::ListLiveBoards => {
+                    let mut boards: Vec<(&String, &BoardEntry)> = self.boards
+                        .iter()
+                        .filter(|&(_, entry)| !entry.seats.is_open() && !entry.seats.settings.private)
+                        .collect();
+                    boards.sort_by_key(|&(_, entry)| Reverse(entry.summary.started_at));
+                    let boards = boards
+                        .into_iter()
+                        .map(|(board_id, entry)| WsLiveBoard {
+                            board_id: board_id.clone(),
+                            black: entry.seats.black_name.clone(),
+                            white: entry.seats.white_name.clone(),
+                            score: entry.summary.score,
+                            spectators: entry.summary.spectators,
+                        })
+                        .collect();
+                    Some(WsResponse::LiveBoards(boards))
+                }
+                WsRequest::WatchBoard(ref param) => {
+                    self.watch_board(&msg.id, param);
+                    None
+                }
+                WsRequest::StopWatching(ref param) => {
+                    if param.session_id == msg.id {
+                        self.stop_watching(&msg.id);
+                    }
+                    None
+                }
+                WsRequest::Resume(_) => {
+                    error!("Resume must be sent as a `Resume` message");
+                    None
+                }
+                WsRequest::KeepAlive => {
+                    error!("KeepAlive must be answered by the websocket session");
+                    None
+                }
+                WsRequest::SendChat(ref param) => self.send_chat(&msg.id, param),
+                WsRequest::MuteOpponent(ref param) => {
+                    self.mute_opponent(&msg.id, param);
+                    None
+                }
+                WsRequest::Resign(ref param) => self.resign(&msg.id, param),
+                WsRequest::Abort(ref param) => self.abort(&msg.id, param),
+                WsRequest::OfferRematch(ref param) => self.offer_rematch(&msg.id, param, ctx),
+                WsRequest::DeclineRematch(ref param) => self.decline_rematch(&msg.id, param),
+                WsRequest::CreateTournament(ref param) => self.create_tournament(&msg.id, param),
+                WsRequest::StartTournament(ref param) => self.start_tournament(&msg.id, param, ctx),
+                WsRequest::GetTournament(ref param) => self.get_tournament(&msg.id, param),
+                WsRequest::WithdrawTournament(ref param) => self.withdraw(&msg.id, param),
+                WsRequest::Register(ref param) => self.register(&msg.id, param, ctx),
+                WsRequest::Login(ref param) => self.login(&msg.id, param, ctx),
+                WsRequest::PlayBoard(ref param) => match self.board_addr(&param.board_id) {
+                    // the websocket session checked the session id of the payload
+                    Some(addr) => {
+                        addr.do_send(board::Play {
+                            sess_id: msg.id.clone(),
+                            param: param.clone(),
+                        });
+                        None
+                    }
+                    None => Some(WsResponse::RejectedMove(WsRejectedMove {
+                        session_id: msg.id.clone(),
+                        board_id: param.board_id.clone(),
+                        pos: param.pos,
+                        reason: "unknown board".to_string(),
+                        board: None,
+                    })),
+                },
             }
         };
         if let Some(r) = resp {
@@ -361,3 +2243,127 @@ impl Handler<ClientMessage> for OthelloActor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wscommand::{ClockMode, TimeControl};
+
+    /// A websocket session that drops what it is sent
+    struct Sink;
+
+    impl Actor for Sink {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<Outgoing> for Sink {
+        type Result = ();
+
+        fn handle(&mut self, _: Outgoing, _: &mut Context<Self>) {}
+    }
+
+    /// A connected session of a guest, an actix system has to be running
+    fn session(nickname: &str) -> SessionData {
+        let sink: Addr<Syn, _> = Sink.start();
+        SessionData {
+            addr: sink.recipient(),
+            nickname: Some(nickname.to_string()),
+            guest: true,
+            board_id: None,
+            resume_token: format!("{}-token", nickname),
+            away: None,
+            watching: None,
+            in_lobby: false,
+            rating: None,
+            chat_limit: chat::rate_limit(),
+            muted: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_check_settings() {
+        assert!(check_settings(&WsBoardSettings::default()).is_ok());
+        let blitz = WsBoardSettings {
+            color: ColorPreference::Random,
+            time_control: Some(TimeControl {
+                minutes: 3,
+                increment: 2,
+                mode: ClockMode::Fischer,
+            }),
+            rated: true,
+            ..WsBoardSettings::default()
+        };
+        assert!(check_settings(&blitz).is_ok());
+
+        let variant = WsBoardSettings {
+            variant: "reversi".to_string(),
+            ..WsBoardSettings::default()
+        };
+        assert!(check_settings(&variant).is_err());
+        for &(minutes, increment) in &[(0, 0), (0, 5), (MAX_MINUTES + 1, 0)] {
+            let clock = WsBoardSettings {
+                time_control: Some(TimeControl {
+                    minutes,
+                    increment,
+                    mode: ClockMode::Fischer,
+                }),
+                ..WsBoardSettings::default()
+            };
+            assert!(check_settings(&clock).is_err());
+        }
+    }
+
+    #[test]
+    fn test_invite_code() {
+        let actor = OthelloActor::new(&Config::default(), None, None, Arc::default());
+        let code = actor.new_invite_code();
+        assert_eq!(code.len(), INVITE_CODE_LEN);
+        assert!(code.bytes().all(|c| INVITE_ALPHABET.contains(&c)));
+    }
+
+    #[test]
+    fn test_check_nickname() {
+        let _sys = System::new("test");
+        let mut actor = OthelloActor::new(&Config::default(), None, None, Arc::default());
+        actor.sessions.insert("alice-id".into(), session("alice"));
+        actor.accounts.insert("carol".into());
+        assert!(actor.check_nickname("bob-id", "bob").is_ok());
+        assert!(actor.check_nickname("bob-id", "Alice").is_err());
+        assert!(actor.check_nickname("alice-id", "alice").is_ok());
+        assert!(actor.check_nickname("bob-id", "carol").is_err());
+        assert!(actor.check_nickname("bob-id", "bot").is_err());
+
+        // the seat of alice is held, and so is the nickname
+        actor.sessions.get_mut("alice-id").unwrap().away = Some(SpawnHandle::default());
+        assert!(actor.check_nickname("bob-id", "alice").is_err());
+        actor.sessions.remove("alice-id");
+        assert!(actor.check_nickname("bob-id", "alice").is_ok());
+    }
+
+    #[test]
+    fn test_tournament_identities() {
+        let _sys = System::new("test");
+        let mut actor = OthelloActor::new(&Config::default(), None, None, Arc::default());
+        actor.sessions.insert("alice-id".into(), session("alice"));
+        let mut carol = session("carol");
+        carol.guest = false;
+        actor.sessions.insert("carol-id".into(), carol);
+        actor.accounts.insert("dave".into());
+        let alice = actor.identity("alice-id").unwrap();
+        assert_eq!(actor.identity_of("alice"), Some(alice.clone()));
+        assert_eq!(actor.identity_of("carol"), Some("account:carol".to_string()));
+        assert_eq!(actor.identity_of("Dave"), Some("account:dave".to_string()));
+        assert_eq!(actor.identity_of("erin"), None);
+        let known = Some(alice.clone());
+        assert_eq!(actor.free_session("alice", &known), Some("alice-id".to_string()));
+
+        // another guest took the nickname once alice left
+        actor.sessions.remove("alice-id");
+        let mut mallory = session("alice");
+        mallory.resume_token = "mallory-token".to_string();
+        actor.sessions.insert("mallory-id".into(), mallory);
+        assert_ne!(actor.identity("mallory-id"), known);
+        assert_eq!(actor.free_session("alice", &known), None);
+        assert_eq!(actor.free_session("alice", &None), Some("mallory-id".to_string()));
+    }
+}
@@ -0,0 +1,676 @@
+//! A `GameStore` in a SQLite database.
+//!
+//! The schema is upgraded on open: every migration is applied once, in order,
+//! and the version reached is kept in `PRAGMA user_version`.
+
+use rusqlite::types::ToSql;
+use rusqlite::{Connection, Row};
+
+use rating::Rating;
+use storage::{
+    color_name, parse_color, parse_reason, reason_name, AccountRecord, GameFilter, GameRecord,
+    GameStore, MoveRecord, PlayerRecord, RatingChange, ResultFilter, StoreError,
+};
+
+const GAME_COLUMNS: &str = "id, board_id, black, white, winner, black_score, white_score, \
+                            reason, variant, time_control, started_at, ended_at, rated, \
+                            black_guest, white_guest";
+
+/// The schema of the version `n` is built by the `n` first migrations
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE games (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        board_id TEXT NOT NULL,
+        black TEXT NOT NULL,
+        white TEXT NOT NULL,
+        winner TEXT,
+        black_score INTEGER NOT NULL,
+        white_score INTEGER NOT NULL,
+        reason TEXT NOT NULL,
+        variant TEXT NOT NULL,
+        time_control TEXT,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER NOT NULL
+    );
+    CREATE INDEX games_black ON games (black);
+    CREATE INDEX games_white ON games (white);
+    CREATE INDEX games_ended_at ON games (ended_at);
+
+    CREATE TABLE moves (
+        game_id INTEGER NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+        ply INTEGER NOT NULL,
+        color TEXT NOT NULL,
+        pos INTEGER NOT NULL,
+        played_at INTEGER NOT NULL,
+        PRIMARY KEY (game_id, ply)
+    );
+    "#,
+    r#"
+    ALTER TABLE games ADD COLUMN rated INTEGER NOT NULL DEFAULT 0;
+    "#,
+    r#"
+    CREATE TABLE players (
+        name TEXT PRIMARY KEY,
+        rating REAL NOT NULL,
+        deviation REAL NOT NULL,
+        volatility REAL NOT NULL,
+        games INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE ratings (
+        player TEXT NOT NULL REFERENCES players (name) ON DELETE CASCADE,
+        game_id INTEGER NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+        rating REAL NOT NULL,
+        deviation REAL NOT NULL,
+        volatility REAL NOT NULL,
+        rated_at INTEGER NOT NULL,
+        PRIMARY KEY (player, game_id)
+    );
+    CREATE INDEX ratings_rated_at ON ratings (player, rated_at);
+    "#,
+    r#"
+    CREATE TABLE accounts (
+        name TEXT PRIMARY KEY COLLATE NOCASE,
+        password_hash TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+
+    ALTER TABLE games ADD COLUMN black_guest INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE games ADD COLUMN white_guest INTEGER NOT NULL DEFAULT 0;
+    -- the games played before the accounts are those of guests
+    UPDATE games SET black_guest = 1, white_guest = 1;
+    "#,
+];
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Open the database at `path`, created if missing, and migrate it
+    pub fn open(path: &str) -> Result<SqliteStore, StoreError> {
+        SqliteStore::with_connection(Connection::open(path)?)
+    }
+
+    /// A database that lives as long as the store, for the tests
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<SqliteStore, StoreError> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<SqliteStore, StoreError> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        migrate(&mut conn)?;
+        Ok(SqliteStore { conn })
+    }
+
+    /// The version of the schema
+    #[cfg(test)]
+    pub fn version(&self) -> Result<usize, StoreError> {
+        schema_version(&self.conn)
+    }
+}
+
+fn schema_version(conn: &Connection) -> Result<usize, StoreError> {
+    let version: i64 = conn.query_row("PRAGMA user_version", &[], |row| row.get(0))?;
+    Ok(version as usize)
+}
+
+/// Apply the missing migrations
+fn migrate(conn: &mut Connection) -> Result<(), StoreError> {
+    let version = schema_version(conn)?;
+    if version > MIGRATIONS.len() {
+        return Err(StoreError::Corrupted(format!(
+            "the schema version {} is newer than this server",
+            version
+        )));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        info!("Migrating the database to the version {}", index + 1);
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        // PRAGMA does not accept bound parameters
+        tx.execute_batch(&format!("PRAGMA user_version = {};", index + 1))?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn game_from_row(row: &Row) -> Result<GameRecord, StoreError> {
+    let winner: Option<String> = row.get(4);
+    let reason: String = row.get(7);
+    let black_score: i64 = row.get(5);
+    let white_score: i64 = row.get(6);
+    Ok(GameRecord {
+        id: Some(row.get(0)),
+        board_id: row.get(1),
+        black: row.get(2),
+        white: row.get(3),
+        black_guest: row.get(13),
+        white_guest: row.get(14),
+        moves: Vec::new(),
+        winner: match winner {
+            Some(name) => Some(parse_color(&name)?),
+            None => None,
+        },
+        score: (black_score as usize, white_score as usize),
+        reason: parse_reason(&reason)?,
+        variant: row.get(8),
+        time_control: row.get(9),
+        started_at: row.get(10),
+        ended_at: row.get(11),
+        rated: row.get(12),
+    })
+}
+
+fn rating_from_row(row: &Row, first: i32) -> Rating {
+    Rating {
+        rating: row.get(first),
+        deviation: row.get(first + 1),
+        volatility: row.get(first + 2),
+    }
+}
+
+/// Insert the game and its moves, in the transaction of the caller
+fn insert_game(conn: &Connection, game: &GameRecord) -> Result<i64, StoreError> {
+    conn.execute(
+        "INSERT INTO games (board_id, black, white, winner, black_score, white_score,
+                           reason, variant, time_control, started_at, ended_at, rated,
+                           black_guest, white_guest)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        &[
+            &game.board_id,
+            &game.black,
+            &game.white,
+            &game.winner.map(color_name),
+            &(game.score.0 as i64),
+            &(game.score.1 as i64),
+            &reason_name(game.reason),
+            &game.variant,
+            &game.time_control,
+            &game.started_at,
+            &game.ended_at,
+            &game.rated,
+            &game.black_guest,
+            &game.white_guest,
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    for (ply, mv) in game.moves.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO moves (game_id, ply, color, pos, played_at) VALUES (?, ?, ?, ?, ?)",
+            &[
+                &id,
+                &(ply as i64),
+                &color_name(mv.color),
+                &(mv.pos as i64),
+                &mv.played_at,
+            ],
+        )?;
+    }
+    Ok(id)
+}
+
+/// Save the new rating of a player, in the transaction of the caller
+fn rate_player(conn: &Connection, player: &PlayerRecord, game_id: i64) -> Result<(), StoreError> {
+    let params: &[&dyn ToSql] = &[
+        &player.rating.rating,
+        &player.rating.deviation,
+        &player.rating.volatility,
+        &(player.games as i64),
+        &player.updated_at,
+        &player.name,
+    ];
+    // a replaced row would delete the history of the player
+    let updated = conn.execute(
+        "UPDATE players SET rating = ?, deviation = ?, volatility = ?, games = ?, updated_at = ?
+         WHERE name = ?",
+        params,
+    )?;
+    if updated == 0 {
+        conn.execute(
+            "INSERT INTO players (rating, deviation, volatility, games, updated_at, name)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params,
+        )?;
+    }
+    conn.execute(
+        "INSERT INTO ratings (player, game_id, rating, deviation, volatility, rated_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+        &[
+            &player.name,
+            &game_id,
+            &player.rating.rating,
+            &player.rating.deviation,
+            &player.rating.volatility,
+            &player.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+impl GameStore for SqliteStore {
+    fn save_game(&mut self, game: &GameRecord) -> Result<i64, StoreError> {
+        let tx = self.conn.transaction()?;
+        let id = insert_game(&tx, game)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    fn save_rated_game(&mut self, game: &GameRecord, players: &[PlayerRecord]) -> Result<i64, StoreError> {
+        let tx = self.conn.transaction()?;
+        let id = insert_game(&tx, game)?;
+        for player in players {
+            rate_player(&tx, player, id)?;
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    fn game(&self, id: i64) -> Result<Option<GameRecord>, StoreError> {
+        let mut stmt = self.conn
+            .prepare(&format!("SELECT {} FROM games WHERE id = ?", GAME_COLUMNS))?;
+        let mut rows = stmt.query(&[&id])?;
+        let mut game = match rows.next() {
+            Some(row) => game_from_row(&row?)?,
+            None => return Ok(None),
+        };
+
+        let mut stmt = self.conn.prepare(
+            "SELECT color, pos, played_at FROM moves WHERE game_id = ? ORDER BY ply",
+        )?;
+        let mut rows = stmt.query(&[&id])?;
+        while let Some(row) = rows.next() {
+            let row = row?;
+            let color: String = row.get(0);
+            let pos: i64 = row.get(1);
+            game.moves.push(MoveRecord {
+                color: parse_color(&color)?,
+                pos: pos as usize,
+                played_at: row.get(2),
+            });
+        }
+        Ok(Some(game))
+    }
+
+    fn games(&self, filter: &GameFilter) -> Result<Vec<GameRecord>, StoreError> {
+        let mut clauses = Vec::new();
+        let mut params: Vec<&dyn ToSql> = Vec::new();
+        // a guest with the name of the player is somebody else
+        if let Some(ref player) = filter.player {
+            clauses.push("((black = ? AND NOT black_guest) OR (white = ? AND NOT white_guest))");
+            params.push(player);
+            params.push(player);
+        }
+        match (filter.result, filter.player.as_ref()) {
+            (None, _) => {}
+            (Some(ResultFilter::Black), _) => clauses.push("winner = 'black'"),
+            (Some(ResultFilter::White), _) => clauses.push("winner = 'white'"),
+            (Some(ResultFilter::Draw), _) => clauses.push("winner IS NULL"),
+            (Some(ResultFilter::Win), Some(player)) => {
+                clauses.push(
+                    "((black = ? AND NOT black_guest AND winner = 'black') \
+                     OR (white = ? AND NOT white_guest AND winner = 'white'))",
+                );
+                params.push(player);
+                params.push(player);
+            }
+            (Some(ResultFilter::Loss), Some(player)) => {
+                clauses.push(
+                    "((black = ? AND NOT black_guest AND winner = 'white') \
+                     OR (white = ? AND NOT white_guest AND winner = 'black'))",
+                );
+                params.push(player);
+                params.push(player);
+            }
+            // win and loss make no sense without a player
+            (Some(_), None) => clauses.push("0"),
+        }
+        if let Some(ref since) = filter.since {
+            clauses.push("ended_at >= ?");
+            params.push(since);
+        }
+        if let Some(ref until) = filter.until {
+            clauses.push("ended_at < ?");
+            params.push(until);
+        }
+        let limit = filter.limit as i64;
+        let offset = filter.offset as i64;
+        params.push(&limit);
+        params.push(&offset);
+
+        let query = format!(
+            "SELECT {} FROM games {} {} ORDER BY ended_at DESC, id DESC LIMIT ? OFFSET ?",
+            GAME_COLUMNS,
+            if clauses.is_empty() { "" } else { "WHERE" },
+            clauses.join(" AND ")
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+        let mut rows = stmt.query(&params)?;
+        let mut games = Vec::new();
+        while let Some(row) = rows.next() {
+            games.push(game_from_row(&row?)?);
+        }
+        Ok(games)
+    }
+
+    fn player(&self, name: &str) -> Result<Option<PlayerRecord>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, rating, deviation, volatility, games, updated_at
+             FROM players WHERE name = ?",
+        )?;
+        let mut rows = stmt.query(&[&name])?;
+        let row = match rows.next() {
+            Some(row) => row?,
+            None => return Ok(None),
+        };
+        let games: i64 = row.get(4);
+        Ok(Some(PlayerRecord {
+            name: row.get(0),
+            rating: rating_from_row(&row, 1),
+            games: games as u32,
+            updated_at: row.get(5),
+        }))
+    }
+
+    fn rating_history(&self, name: &str, limit: usize) -> Result<Vec<RatingChange>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT game_id, rating, deviation, volatility, rated_at FROM ratings
+             WHERE player = ? ORDER BY rated_at DESC, game_id DESC LIMIT ?",
+        )?;
+        let limit = limit as i64;
+        let mut rows = stmt.query(&[&name, &limit])?;
+        let mut history = Vec::new();
+        while let Some(row) = rows.next() {
+            let row = row?;
+            history.push(RatingChange {
+                game_id: row.get(0),
+                rating: rating_from_row(&row, 1),
+                rated_at: row.get(4),
+            });
+        }
+        Ok(history)
+    }
+
+    fn account(&self, name: &str) -> Result<Option<AccountRecord>, StoreError> {
+        let mut stmt = self.conn
+            .prepare("SELECT name, password_hash, created_at FROM accounts WHERE name = ?")?;
+        let mut rows = stmt.query(&[&name])?;
+        let row = match rows.next() {
+            Some(row) => row?,
+            None => return Ok(None),
+        };
+        Ok(Some(AccountRecord {
+            name: row.get(0),
+            password_hash: row.get(1),
+            created_at: row.get(2),
+        }))
+    }
+
+    fn create_account(&mut self, account: &AccountRecord) -> Result<bool, StoreError> {
+        // the name is compared without its case
+        let created = self.conn.execute(
+            "INSERT OR IGNORE INTO accounts (name, password_hash, created_at) VALUES (?, ?, ?)",
+            &[&account.name, &account.password_hash, &account.created_at],
+        )?;
+        Ok(created == 1)
+    }
+
+    fn account_names(&self) -> Result<Vec<String>, StoreError> {
+        let mut stmt = self.conn.prepare("SELECT name FROM accounts")?;
+        let mut rows = stmt.query(&[])?;
+        let mut names = Vec::new();
+        while let Some(row) = rows.next() {
+            names.push(row?.get(0));
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::tests::game_record;
+    use wscommand::{Color, GameOverReason};
+
+    #[test]
+    fn test_migrate() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        assert_eq!(store.version().unwrap(), MIGRATIONS.len());
+
+        // migrating twice is harmless
+        let mut conn = store.conn;
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_migrate_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA user_version = 1000;").unwrap();
+        assert!(migrate(&mut conn).is_err());
+    }
+
+    #[test]
+    fn test_migrate_existing_games() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.execute_batch("PRAGMA user_version = 1;").unwrap();
+        conn.execute(
+            "INSERT INTO games (board_id, black, white, winner, black_score, white_score,
+                               reason, variant, time_control, started_at, ended_at)
+             VALUES ('board', 'alice', 'bob', 'black', 40, 24, 'normal', 'standard', NULL, 1, 2)",
+            &[],
+        ).unwrap();
+        migrate(&mut conn).unwrap();
+        let store = SqliteStore { conn };
+        let game = store.games(&GameFilter::default()).unwrap().remove(0);
+        assert_eq!(game.winner, Some(Color::Black));
+        assert!(!game.rated);
+    }
+
+    #[test]
+    fn test_migrate_guest_games() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..3] {
+            conn.execute_batch(migration).unwrap();
+        }
+        conn.execute_batch("PRAGMA user_version = 3;").unwrap();
+        conn.execute(
+            "INSERT INTO games (board_id, black, white, winner, black_score, white_score,
+                               reason, variant, time_control, started_at, ended_at, rated)
+             VALUES ('board', 'alice', 'bob', 'black', 40, 24, 'normal', 'standard', NULL, 1, 2, 1)",
+            &[],
+        ).unwrap();
+        migrate(&mut conn).unwrap();
+        let mut store = SqliteStore { conn };
+        let game = store.games(&GameFilter::default()).unwrap().remove(0);
+        assert!(game.black_guest);
+        assert!(game.white_guest);
+
+        // an account named alice does not get the game of the guest
+        let alice = GameFilter {
+            player: Some("alice".to_string()),
+            ..GameFilter::default()
+        };
+        assert!(store.games(&alice).unwrap().is_empty());
+        store.save_game(&game_record("account")).unwrap();
+        assert_eq!(store.games(&alice).unwrap()[0].board_id, "account");
+    }
+
+    #[test]
+    fn test_save_game() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let mut record = game_record("board");
+        let id = store.save_game(&record).unwrap();
+        record.id = Some(id);
+        assert_eq!(store.game(id).unwrap(), Some(record));
+        assert_eq!(store.game(id + 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_games() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let mut records = Vec::new();
+        for (index, &(black, white, winner)) in [
+            ("alice", "bob", Some(Color::White)),
+            ("bob", "carol", None),
+            ("carol", "alice", Some(Color::White)),
+        ].iter()
+            .enumerate()
+        {
+            let mut record = game_record(&format!("board{}", index));
+            record.black = black.to_string();
+            record.white = white.to_string();
+            record.winner = winner;
+            record.ended_at += index as i64;
+            record.id = Some(store.save_game(&record).unwrap());
+            record.moves = Vec::new();
+            records.push(record);
+        }
+        let board_ids = |filter: GameFilter| -> Vec<String> {
+            let games = store.games(&filter).unwrap();
+            games.into_iter().map(|game| game.board_id).collect()
+        };
+        let player = |name: &str, result| GameFilter {
+            player: Some(name.to_string()),
+            result,
+            ..GameFilter::default()
+        };
+
+        assert_eq!(store.games(&GameFilter::default()).unwrap()[2], records[0]);
+        assert_eq!(board_ids(GameFilter::default()), vec!["board2", "board1", "board0"]);
+        assert_eq!(board_ids(player("alice", None)), vec!["board2", "board0"]);
+        assert_eq!(board_ids(player("alice", Some(ResultFilter::Win))), vec!["board2"]);
+        assert_eq!(board_ids(player("alice", Some(ResultFilter::Loss))), vec!["board0"]);
+        assert_eq!(board_ids(player("bob", Some(ResultFilter::Draw))), vec!["board1"]);
+        let result = |result| GameFilter {
+            result: Some(result),
+            ..GameFilter::default()
+        };
+        assert_eq!(board_ids(result(ResultFilter::Black)), Vec::<String>::new());
+        assert_eq!(board_ids(result(ResultFilter::White)), vec!["board2", "board0"]);
+        assert_eq!(board_ids(result(ResultFilter::Win)), Vec::<String>::new());
+        let dates = GameFilter {
+            since: Some(records[1].ended_at),
+            until: Some(records[2].ended_at),
+            ..GameFilter::default()
+        };
+        assert_eq!(board_ids(dates), vec!["board1"]);
+        let page = GameFilter {
+            limit: 2,
+            offset: 1,
+            ..GameFilter::default()
+        };
+        assert_eq!(board_ids(page), vec!["board1", "board0"]);
+    }
+
+    #[test]
+    fn test_guest_games() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let mut guest = game_record("guest");
+        guest.white_guest = true;
+        let id = store.save_game(&guest).unwrap();
+        assert!(store.game(id).unwrap().unwrap().white_guest);
+        // a guest named bob is not the account bob
+        let player = |name: &str, result| GameFilter {
+            player: Some(name.to_string()),
+            result,
+            ..GameFilter::default()
+        };
+        assert!(store.games(&player("bob", None)).unwrap().is_empty());
+        assert!(store.games(&player("bob", Some(ResultFilter::Win))).unwrap().is_empty());
+        assert_eq!(store.games(&player("alice", Some(ResultFilter::Loss))).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_players() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        assert_eq!(store.player("alice").unwrap(), None);
+        let mut alice = PlayerRecord::new("alice");
+        alice.games = 1;
+        alice.updated_at = 1000;
+        store.save_rated_game(&game_record("first"), &[alice.clone()]).unwrap();
+        alice.rating.rating = 1600.;
+        alice.games = 2;
+        alice.updated_at = 2000;
+        let second = store.save_rated_game(&game_record("second"), &[alice.clone()]).unwrap();
+        assert_eq!(store.player("alice").unwrap(), Some(alice.clone()));
+
+        let history = store.rating_history("alice", 10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].game_id, second);
+        assert_eq!(history[0].rating, alice.rating);
+        assert_eq!(history[1].rating, Rating::default());
+        assert_eq!(history[1].rated_at, 1000);
+        assert_eq!(store.rating_history("alice", 1).unwrap().len(), 1);
+        assert!(store.rating_history("bob", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_save_rated_game() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let mut rated = game_record("rated");
+        rated.rated = true;
+        let mut alice = PlayerRecord::new("alice");
+        alice.games = 1;
+        let bob = PlayerRecord::new("bob");
+        let id = store.save_rated_game(&rated, &[alice.clone(), bob]).unwrap();
+        assert_eq!(store.player("alice").unwrap(), Some(alice.clone()));
+        assert_eq!(store.rating_history("bob", 10).unwrap()[0].game_id, id);
+
+        // the second rating of alice for the same game fails, the game and
+        // the first rating are not saved either
+        let mut again = alice.clone();
+        again.games = 2;
+        let failed = game_record("failed");
+        assert!(store.save_rated_game(&failed, &[again.clone(), again]).is_err());
+        let games = store.games(&GameFilter::default()).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].board_id, "rated");
+        assert_eq!(store.player("alice").unwrap(), Some(alice));
+        assert_eq!(store.rating_history("alice", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_save_draw() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let mut record = game_record("draw");
+        record.winner = None;
+        record.reason = GameOverReason::Normal;
+        record.time_control = Some("5+3".to_string());
+        record.rated = true;
+        let first = store.save_game(&game_record("first")).unwrap();
+        let id = store.save_game(&record).unwrap();
+        assert!(id > first);
+        let game = store.game(id).unwrap().unwrap();
+        assert_eq!(game.winner, None);
+        assert_eq!(game.reason, GameOverReason::Normal);
+        assert_eq!(game.time_control, Some("5+3".to_string()));
+        assert!(game.rated);
+        assert_eq!(game.moves[0].color, Color::Black);
+    }
+
+    #[test]
+    fn test_accounts() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let account = AccountRecord {
+            name: "Alice".to_string(),
+            password_hash: "$argon2id$hash".to_string(),
+            created_at: 1000,
+        };
+        assert!(store.create_account(&account).unwrap());
+        assert_eq!(store.account("alice").unwrap(), Some(account.clone()));
+        assert_eq!(store.account("bob").unwrap(), None);
+
+        // the names are not case sensitive
+        let other = AccountRecord {
+            name: "ALICE".to_string(),
+            ..account
+        };
+        assert!(!store.create_account(&other).unwrap());
+        assert_eq!(store.account_names().unwrap(), vec!["Alice"]);
+    }
+}
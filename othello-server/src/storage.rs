@@ -0,0 +1,706 @@
+//! Finished games and accounts are kept in a `GameStore`.
+//!
+//! The server writes to the store through `DbExecutor`, an actor running on
+//! its own thread, so a slow disk never blocks the boards.
+
+use std::error::Error;
+use std::fmt;
+
+use actix::prelude::*;
+use rusqlite;
+
+use account::{self, AccountError};
+use rating::{self, Rating};
+use wscommand::{Color, GameOverReason};
+
+/// The variant of all the games played for now
+pub const STANDARD_VARIANT: &str = "standard";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveRecord {
+    pub color: Color,
+    /// the played square, `x + y * 8`
+    pub pos: usize,
+    /// milliseconds since the unix epoch
+    pub played_at: i64,
+}
+
+/// A finished game
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    /// the id given by the store, `None` until the game is saved
+    pub id: Option<i64>,
+    pub board_id: String,
+    /// nickname of the black player
+    pub black: String,
+    /// nickname of the white player
+    pub white: String,
+    /// the black player has no account
+    pub black_guest: bool,
+    /// the white player has no account
+    pub white_guest: bool,
+    pub moves: Vec<MoveRecord>,
+    /// `None` for a draw
+    pub winner: Option<Color>,
+    /// (black discs, white discs)
+    pub score: (usize, usize),
+    pub reason: GameOverReason,
+    pub variant: String,
+    /// `None` for games without clock
+    pub time_control: Option<String>,
+    /// the result changes the ratings of the players
+    pub rated: bool,
+    /// when the board was created, in milliseconds since the unix epoch
+    pub started_at: i64,
+    pub ended_at: i64,
+}
+
+/// A player of rated games
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerRecord {
+    /// the nickname of the player
+    pub name: String,
+    pub rating: Rating,
+    /// number of rated games played
+    pub games: u32,
+    /// milliseconds since the unix epoch, when the rating last changed
+    pub updated_at: i64,
+}
+
+impl PlayerRecord {
+    /// A player who has not played a rated game yet
+    pub fn new(name: &str) -> PlayerRecord {
+        PlayerRecord {
+            name: name.to_string(),
+            rating: Rating::default(),
+            games: 0,
+            updated_at: 0,
+        }
+    }
+
+    /// Count a rated game, that changed the rating
+    fn rated(&mut self, rating: Rating, at: i64) {
+        self.rating = rating;
+        self.games += 1;
+        self.updated_at = at;
+    }
+}
+
+/// A registered account
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountRecord {
+    /// the nickname reserved by the account, as registered
+    pub name: String,
+    /// the argon2 hash of the password, in the PHC string format
+    pub password_hash: String,
+    /// milliseconds since the unix epoch
+    pub created_at: i64,
+}
+
+/// The rating of a player after a rated game
+#[derive(Clone, Debug, PartialEq)]
+pub struct RatingChange {
+    pub game_id: i64,
+    pub rating: Rating,
+    /// milliseconds since the unix epoch
+    pub rated_at: i64,
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    /// the stored data cannot be read back
+    Corrupted(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StoreError::Sqlite(ref err) => write!(f, "sqlite error: {}", err),
+            StoreError::Corrupted(ref reason) => write!(f, "corrupted data: {}", reason),
+        }
+    }
+}
+
+impl Error for StoreError {
+    fn description(&self) -> &str {
+        match *self {
+            StoreError::Sqlite(_) => "sqlite error",
+            StoreError::Corrupted(_) => "corrupted data",
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError::Sqlite(err)
+    }
+}
+
+/// Filter the games on their result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResultFilter {
+    Black,
+    White,
+    Draw,
+    /// won by the player of the filter
+    Win,
+    /// lost by the player of the filter
+    Loss,
+}
+
+impl ResultFilter {
+    pub fn parse(name: &str) -> Option<ResultFilter> {
+        match name {
+            "black" => Some(ResultFilter::Black),
+            "white" => Some(ResultFilter::White),
+            "draw" => Some(ResultFilter::Draw),
+            "win" => Some(ResultFilter::Win),
+            "loss" => Some(ResultFilter::Loss),
+            _ => None,
+        }
+    }
+}
+
+/// Select finished games, the most recent first
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameFilter {
+    /// nickname of one of the players
+    pub player: Option<String>,
+    pub result: Option<ResultFilter>,
+    /// games ended at or after this timestamp
+    pub since: Option<i64>,
+    /// games ended before this timestamp
+    pub until: Option<i64>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl Default for GameFilter {
+    fn default() -> GameFilter {
+        GameFilter {
+            player: None,
+            result: None,
+            since: None,
+            until: None,
+            limit: 50,
+            offset: 0,
+        }
+    }
+}
+
+impl GameFilter {
+    /// Whether the game is selected, regardless of the limit and the offset
+    #[cfg(test)]
+    pub fn matches(&self, game: &GameRecord) -> bool {
+        let player = self.player.as_ref();
+        // a guest with the name of the player is somebody else
+        let black = |player: &String| game.black == *player && !game.black_guest;
+        let white = |player: &String| game.white == *player && !game.white_guest;
+        if let Some(player) = player {
+            if !black(player) && !white(player) {
+                return false;
+            }
+        }
+        let player_color = player.map(|player| {
+            if black(player) {
+                Color::Black
+            } else {
+                Color::White
+            }
+        });
+        let result = match self.result {
+            None => true,
+            Some(ResultFilter::Black) => game.winner == Some(Color::Black),
+            Some(ResultFilter::White) => game.winner == Some(Color::White),
+            Some(ResultFilter::Draw) => game.winner.is_none(),
+            // win and loss make no sense without a player
+            Some(ResultFilter::Win) => game.winner.is_some() && game.winner == player_color,
+            Some(ResultFilter::Loss) => {
+                game.winner.is_some() && player_color.is_some() && game.winner != player_color
+            }
+        };
+        if let Some(since) = self.since {
+            if game.ended_at < since {
+                return false;
+            }
+        }
+        if let Some(until) = self.until {
+            if game.ended_at >= until {
+                return false;
+            }
+        }
+        result
+    }
+}
+
+pub trait GameStore {
+    /// Save a finished game and return its id
+    fn save_game(&mut self, game: &GameRecord) -> Result<i64, StoreError>;
+
+    /// A saved game, `None` if the id is unknown
+    fn game(&self, id: i64) -> Result<Option<GameRecord>, StoreError>;
+
+    /// The games selected by the filter, the most recent first, without
+    /// their moves
+    fn games(&self, filter: &GameFilter) -> Result<Vec<GameRecord>, StoreError>;
+
+    /// A player of rated games, `None` if the name is unknown
+    fn player(&self, name: &str) -> Result<Option<PlayerRecord>, StoreError>;
+
+    /// Save a rated game with the new ratings of its players and return its
+    /// id, nothing is saved if any of them fails
+    fn save_rated_game(&mut self, game: &GameRecord, players: &[PlayerRecord]) -> Result<i64, StoreError>;
+
+    /// The ratings of a player, the most recent first
+    fn rating_history(&self, name: &str, limit: usize) -> Result<Vec<RatingChange>, StoreError>;
+
+    /// The account of the name, whatever its case, `None` if unknown
+    fn account(&self, name: &str) -> Result<Option<AccountRecord>, StoreError>;
+
+    /// Save a new account, `false` if the name is already taken
+    fn create_account(&mut self, account: &AccountRecord) -> Result<bool, StoreError>;
+
+    /// The names of all the accounts
+    fn account_names(&self) -> Result<Vec<String>, StoreError>;
+}
+
+/// The result of `SaveGame`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedGame {
+    pub id: i64,
+    /// the players with their new rating, empty for an unrated game
+    pub players: Vec<PlayerRecord>,
+}
+
+/// Save a finished game, with the new ratings of its players if the game is
+/// rated.
+///
+/// A player cannot be rated against themselves.
+pub fn save_game(store: &mut dyn GameStore, game: &GameRecord) -> Result<SavedGame, StoreError> {
+    if !game.rated || game.black == game.white {
+        return Ok(SavedGame {
+            id: store.save_game(game)?,
+            players: Vec::new(),
+        });
+    }
+    let mut black = store
+        .player(&game.black)?
+        .unwrap_or_else(|| PlayerRecord::new(&game.black));
+    let mut white = store
+        .player(&game.white)?
+        .unwrap_or_else(|| PlayerRecord::new(&game.white));
+    let (black_rating, white_rating) = rating::rate_game(&black.rating, &white.rating, game.winner);
+    black.rated(black_rating, game.ended_at);
+    white.rated(white_rating, game.ended_at);
+    let players = vec![black, white];
+    let id = store.save_rated_game(game, &players)?;
+    Ok(SavedGame { id, players })
+}
+
+/// A store that forgets everything on restart, for the tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    games: Vec<GameRecord>,
+    players: Vec<PlayerRecord>,
+    /// (player name, rating), in the order they were saved
+    ratings: Vec<(String, RatingChange)>,
+    accounts: Vec<AccountRecord>,
+}
+
+#[cfg(test)]
+impl GameStore for MemoryStore {
+    fn save_game(&mut self, game: &GameRecord) -> Result<i64, StoreError> {
+        let id = self.games.len() as i64 + 1;
+        let mut game = game.clone();
+        game.id = Some(id);
+        self.games.push(game);
+        Ok(id)
+    }
+
+    fn game(&self, id: i64) -> Result<Option<GameRecord>, StoreError> {
+        Ok(self.games.iter().find(|game| game.id == Some(id)).cloned())
+    }
+
+    fn games(&self, filter: &GameFilter) -> Result<Vec<GameRecord>, StoreError> {
+        Ok(self.games
+            .iter()
+            .rev()
+            .filter(|game| filter.matches(game))
+            .skip(filter.offset)
+            .take(filter.limit)
+            .map(|game| GameRecord {
+                moves: Vec::new(),
+                ..game.clone()
+            })
+            .collect())
+    }
+
+    fn player(&self, name: &str) -> Result<Option<PlayerRecord>, StoreError> {
+        Ok(self.players.iter().find(|player| player.name == name).cloned())
+    }
+
+    fn save_rated_game(&mut self, game: &GameRecord, players: &[PlayerRecord]) -> Result<i64, StoreError> {
+        let id = self.save_game(game)?;
+        for player in players {
+            self.players.retain(|known| known.name != player.name);
+            self.players.push(player.clone());
+            let change = RatingChange {
+                game_id: id,
+                rating: player.rating,
+                rated_at: player.updated_at,
+            };
+            self.ratings.push((player.name.clone(), change));
+        }
+        Ok(id)
+    }
+
+    fn rating_history(&self, name: &str, limit: usize) -> Result<Vec<RatingChange>, StoreError> {
+        Ok(self.ratings
+            .iter()
+            .rev()
+            .filter(|(player, _)| player == name)
+            .take(limit)
+            .map(|(_, change)| change.clone())
+            .collect())
+    }
+
+    fn account(&self, name: &str) -> Result<Option<AccountRecord>, StoreError> {
+        let key = account::name_key(name);
+        Ok(self.accounts
+            .iter()
+            .find(|account| account::name_key(&account.name) == key)
+            .cloned())
+    }
+
+    fn create_account(&mut self, account: &AccountRecord) -> Result<bool, StoreError> {
+        if self.account(&account.name)?.is_some() {
+            return Ok(false);
+        }
+        self.accounts.push(account.clone());
+        Ok(true)
+    }
+
+    fn account_names(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self.accounts.iter().map(|account| account.name.clone()).collect())
+    }
+}
+
+/// The name of a color in the store
+pub fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::Black => "black",
+        Color::White => "white",
+    }
+}
+
+pub fn parse_color(name: &str) -> Result<Color, StoreError> {
+    match name {
+        "black" => Ok(Color::Black),
+        "white" => Ok(Color::White),
+        _ => Err(StoreError::Corrupted(format!("unknown color {:?}", name))),
+    }
+}
+
+/// The name of a reason in the store
+pub fn reason_name(reason: GameOverReason) -> &'static str {
+    match reason {
+        GameOverReason::Normal => "normal",
+        GameOverReason::Resignation => "resignation",
+        GameOverReason::Timeout => "timeout",
+        GameOverReason::Disconnect => "disconnect",
+        GameOverReason::Aborted => "aborted",
+    }
+}
+
+pub fn parse_reason(name: &str) -> Result<GameOverReason, StoreError> {
+    match name {
+        "normal" => Ok(GameOverReason::Normal),
+        "resignation" => Ok(GameOverReason::Resignation),
+        "timeout" => Ok(GameOverReason::Timeout),
+        "disconnect" => Ok(GameOverReason::Disconnect),
+        "aborted" => Ok(GameOverReason::Aborted),
+        _ => Err(StoreError::Corrupted(format!("unknown reason {:?}", name))),
+    }
+}
+
+/// Run the store on a `SyncArbiter` thread
+pub struct DbExecutor(pub Box<dyn GameStore + Send>);
+
+impl Actor for DbExecutor {
+    type Context = SyncContext<Self>;
+}
+
+/// Save a finished game, and the new ratings of its players
+#[derive(Message)]
+#[rtype(result = "Result<SavedGame, StoreError>")]
+pub struct SaveGame(pub GameRecord);
+
+/// Fetch a game with its moves
+#[derive(Message)]
+#[rtype(result = "Result<Option<GameRecord>, StoreError>")]
+pub struct GetGame(pub i64);
+
+/// Fetch the games selected by the filter
+#[derive(Message)]
+#[rtype(result = "Result<Vec<GameRecord>, StoreError>")]
+pub struct ListGames(pub GameFilter);
+
+/// Fetch a player of rated games
+#[derive(Message)]
+#[rtype(result = "Result<Option<PlayerRecord>, StoreError>")]
+pub struct GetPlayer(pub String);
+
+/// Fetch the last ratings of a player
+#[derive(Message)]
+#[rtype(result = "Result<Vec<RatingChange>, StoreError>")]
+pub struct RatingHistory {
+    pub player: String,
+    pub limit: usize,
+}
+
+/// Save a new account, its password already hashed
+#[derive(Message)]
+#[rtype(result = "Result<AccountRecord, AccountError>")]
+pub struct CreateAccount(pub AccountRecord);
+
+/// Fetch the account of a name
+#[derive(Message)]
+#[rtype(result = "Result<Option<AccountRecord>, StoreError>")]
+pub struct GetAccount(pub String);
+
+/// Fetch the names of all the accounts
+#[derive(Message)]
+#[rtype(result = "Result<Vec<String>, StoreError>")]
+pub struct ListAccountNames;
+
+impl Handler<SaveGame> for DbExecutor {
+    type Result = Result<SavedGame, StoreError>;
+
+    fn handle(&mut self, msg: SaveGame, _: &mut Self::Context) -> Self::Result {
+        let saved = save_game(&mut *self.0, &msg.0);
+        match saved {
+            Ok(ref saved) => info!("Game {} saved as {}", msg.0.board_id, saved.id),
+            Err(ref err) => error!("Cannot save the game {}: {}", msg.0.board_id, err),
+        }
+        saved
+    }
+}
+
+impl Handler<GetGame> for DbExecutor {
+    type Result = Result<Option<GameRecord>, StoreError>;
+
+    fn handle(&mut self, msg: GetGame, _: &mut Self::Context) -> Self::Result {
+        self.0.game(msg.0)
+    }
+}
+
+impl Handler<ListGames> for DbExecutor {
+    type Result = Result<Vec<GameRecord>, StoreError>;
+
+    fn handle(&mut self, msg: ListGames, _: &mut Self::Context) -> Self::Result {
+        self.0.games(&msg.0)
+    }
+}
+
+impl Handler<GetPlayer> for DbExecutor {
+    type Result = Result<Option<PlayerRecord>, StoreError>;
+
+    fn handle(&mut self, msg: GetPlayer, _: &mut Self::Context) -> Self::Result {
+        self.0.player(&msg.0)
+    }
+}
+
+impl Handler<RatingHistory> for DbExecutor {
+    type Result = Result<Vec<RatingChange>, StoreError>;
+
+    fn handle(&mut self, msg: RatingHistory, _: &mut Self::Context) -> Self::Result {
+        self.0.rating_history(&msg.player, msg.limit)
+    }
+}
+
+impl Handler<CreateAccount> for DbExecutor {
+    type Result = Result<AccountRecord, AccountError>;
+
+    fn handle(&mut self, msg: CreateAccount, _: &mut Self::Context) -> Self::Result {
+        let name = msg.0.name.clone();
+        let registered = account::create(&mut *self.0, msg.0);
+        match registered {
+            Ok(ref account) => info!("Account {} registered", account.name),
+            Err(AccountError::Store(ref err)) => {
+                error!("Cannot register the account {}: {}", name, err)
+            }
+            Err(_) => {}
+        }
+        registered
+    }
+}
+
+impl Handler<GetAccount> for DbExecutor {
+    type Result = Result<Option<AccountRecord>, StoreError>;
+
+    fn handle(&mut self, msg: GetAccount, _: &mut Self::Context) -> Self::Result {
+        self.0.account(&msg.0)
+    }
+}
+
+impl Handler<ListAccountNames> for DbExecutor {
+    type Result = Result<Vec<String>, StoreError>;
+
+    fn handle(&mut self, _: ListAccountNames, _: &mut Self::Context) -> Self::Result {
+        self.0.account_names()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// A game record for the tests
+    pub fn game_record(board_id: &str) -> GameRecord {
+        GameRecord {
+            id: None,
+            board_id: board_id.to_string(),
+            black: "alice".to_string(),
+            white: "bob".to_string(),
+            black_guest: false,
+            white_guest: false,
+            moves: vec![
+                MoveRecord {
+                    color: Color::Black,
+                    pos: 20,
+                    played_at: 1_500_000_001_000,
+                },
+                MoveRecord {
+                    color: Color::White,
+                    pos: 19,
+                    played_at: 1_500_000_002_000,
+                },
+            ],
+            winner: Some(Color::White),
+            score: (3, 3),
+            reason: GameOverReason::Disconnect,
+            variant: STANDARD_VARIANT.to_string(),
+            time_control: None,
+            rated: false,
+            started_at: 1_500_000_000_000,
+            ended_at: 1_500_000_003_000,
+        }
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let mut store = MemoryStore::default();
+        let id = store.save_game(&game_record("board")).unwrap();
+        let game = store.game(id).unwrap().unwrap();
+        assert_eq!(game.id, Some(id));
+        assert_eq!(game.board_id, "board");
+        assert_eq!(store.game(id + 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_memory_store_games() {
+        let mut store = MemoryStore::default();
+        for board_id in &["first", "second", "third"] {
+            store.save_game(&game_record(board_id)).unwrap();
+        }
+        let games = store.games(&GameFilter::default()).unwrap();
+        let board_ids: Vec<&str> = games.iter().map(|game| game.board_id.as_str()).collect();
+        assert_eq!(board_ids, vec!["third", "second", "first"]);
+        assert!(games[0].moves.is_empty());
+
+        let filter = GameFilter {
+            limit: 1,
+            offset: 1,
+            ..GameFilter::default()
+        };
+        assert_eq!(store.games(&filter).unwrap()[0].board_id, "second");
+    }
+
+    #[test]
+    fn test_save_rated_game() {
+        let mut store = MemoryStore::default();
+        let saved = save_game(&mut store, &game_record("casual")).unwrap();
+        assert!(saved.players.is_empty());
+        assert_eq!(store.player("alice").unwrap(), None);
+
+        let mut rated = game_record("rated");
+        rated.rated = true;
+        let saved = save_game(&mut store, &rated).unwrap();
+        let (alice, bob) = (&saved.players[0], &saved.players[1]);
+        assert_eq!(alice.name, "alice");
+        assert!(alice.rating.rating < bob.rating.rating);
+        assert_eq!(bob.games, 1);
+        assert_eq!(bob.updated_at, rated.ended_at);
+        assert_eq!(store.player("bob").unwrap().as_ref(), Some(bob));
+
+        let again = save_game(&mut store, &rated).unwrap();
+        assert_eq!(again.players[1].games, 2);
+        let history = store.rating_history("bob", 10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].game_id, again.id);
+        assert_eq!(history[1].rating, bob.rating);
+        assert_eq!(store.rating_history("bob", 1).unwrap().len(), 1);
+
+        // nobody is rated against themselves
+        rated.white = "alice".to_string();
+        assert!(save_game(&mut store, &rated).unwrap().players.is_empty());
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let game = game_record("board");
+        let filter = |player: Option<&str>, result| GameFilter {
+            player: player.map(|player| player.to_string()),
+            result,
+            ..GameFilter::default()
+        };
+        assert!(filter(None, None).matches(&game));
+        assert!(filter(Some("alice"), None).matches(&game));
+        assert!(!filter(Some("carol"), None).matches(&game));
+        assert!(filter(None, Some(ResultFilter::White)).matches(&game));
+        assert!(!filter(None, Some(ResultFilter::Black)).matches(&game));
+        assert!(!filter(None, Some(ResultFilter::Draw)).matches(&game));
+        assert!(filter(Some("bob"), Some(ResultFilter::Win)).matches(&game));
+        assert!(filter(Some("alice"), Some(ResultFilter::Loss)).matches(&game));
+        assert!(!filter(Some("alice"), Some(ResultFilter::Win)).matches(&game));
+        assert!(!filter(None, Some(ResultFilter::Win)).matches(&game));
+        assert!(!filter(None, Some(ResultFilter::Loss)).matches(&game));
+
+        let dates = |since, until| GameFilter {
+            since,
+            until,
+            ..GameFilter::default()
+        };
+        assert!(dates(Some(game.ended_at), None).matches(&game));
+        assert!(!dates(Some(game.ended_at + 1), None).matches(&game));
+        assert!(!dates(None, Some(game.ended_at)).matches(&game));
+        assert!(dates(None, Some(game.ended_at + 1)).matches(&game));
+
+        // the games of a guest are not those of the account
+        let guest = GameRecord {
+            white_guest: true,
+            ..game
+        };
+        assert!(!filter(Some("bob"), None).matches(&guest));
+        assert!(filter(Some("alice"), Some(ResultFilter::Loss)).matches(&guest));
+    }
+
+    #[test]
+    fn test_names() {
+        for &color in &[Color::Black, Color::White] {
+            assert_eq!(parse_color(color_name(color)).unwrap(), color);
+        }
+        for &reason in &[
+            GameOverReason::Normal,
+            GameOverReason::Resignation,
+            GameOverReason::Timeout,
+            GameOverReason::Disconnect,
+            GameOverReason::Aborted,
+        ] {
+            assert_eq!(parse_reason(reason_name(reason)).unwrap(), reason);
+        }
+        assert!(parse_color("red").is_err());
+    }
+}
@@ -0,0 +1,778 @@
+//! Tournaments played on the boards of the server, round-robin or Swiss.
+//!
+//! The players of a round are paired once the games of the previous round
+//! are over. A player left without opponent gets a bye, worth a win. The
+//! standings are ordered by points, then by Buchholz (the points of the
+//! opponents) and Sonneborn-Berger (the points of the beaten opponents, and
+//! half those of the drawn ones), then by seed.
+//!
+//! The organizer and the players are listed by nickname, but a guest can
+//! take the nickname of a guest who left. The tournament keeps who they are,
+//! an identity given by the server: the organizer from the start, a player
+//! from the first session seen playing as them.
+
+use std::cmp::{Ordering, Reverse};
+
+use wscommand::{GameOutcome, TimeControl, TournamentFormat, TournamentStatus, WsPairing,
+                WsStanding, WsTournamentSettings, WsTournamentState};
+
+/// The most players of a tournament
+pub const MAX_PLAYERS: usize = 64;
+/// The longest name of a tournament, in characters
+const MAX_NAME_LEN: usize = 50;
+
+/// A game of a round, the players by their index in the seeding
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pairing {
+    pub black: usize,
+    pub white: usize,
+    /// the board of the game, once started
+    pub board_id: Option<String>,
+    /// `None` while the game is played
+    pub outcome: Option<GameOutcome>,
+}
+
+impl Pairing {
+    fn new(black: usize, white: usize) -> Pairing {
+        Pairing {
+            black,
+            white,
+            board_id: None,
+            outcome: None,
+        }
+    }
+
+    fn opponent(&self, player: usize) -> Option<usize> {
+        if self.black == player {
+            Some(self.white)
+        } else if self.white == player {
+            Some(self.black)
+        } else {
+            None
+        }
+    }
+
+    /// The half points scored by the player, once the game is over
+    fn half_points(&self, player: usize) -> Option<u32> {
+        let black = if self.black == player {
+            true
+        } else if self.white == player {
+            false
+        } else {
+            return None;
+        };
+        Some(match (self.outcome?, black) {
+            (GameOutcome::Draw, _) => 1,
+            (GameOutcome::BlackWins, true) | (GameOutcome::WhiteWins, false) => 2,
+            _ => 0,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Round {
+    pairings: Vec<Pairing>,
+    /// the players without opponent, who score a win
+    byes: Vec<usize>,
+}
+
+pub struct Tournament {
+    pub name: String,
+    /// the nickname of the player who runs the tournament
+    pub organizer: String,
+    organizer_identity: String,
+    pub format: TournamentFormat,
+    pub time_control: Option<TimeControl>,
+    pub rated: bool,
+    /// the nicknames of the players, the best seeded first
+    players: Vec<String>,
+    /// who the players are, `None` until a session of the player is seen
+    identities: Vec<Option<String>>,
+    withdrawn: Vec<bool>,
+    rounds: Vec<Round>,
+}
+
+impl Tournament {
+    /// A tournament waiting to start, or why the settings are refused
+    pub fn new(
+        settings: WsTournamentSettings,
+        organizer: String,
+        organizer_identity: String,
+    ) -> Result<Tournament, String> {
+        let name = settings.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(format!(
+                "The name must have between 1 and {} characters",
+                MAX_NAME_LEN
+            ));
+        }
+        let players = settings.players;
+        if players.len() < 2 || players.len() > MAX_PLAYERS {
+            return Err(format!(
+                "A tournament has between 2 and {} players",
+                MAX_PLAYERS
+            ));
+        }
+        for (i, player) in players.iter().enumerate() {
+            if player.is_empty() {
+                return Err("A player has no nickname".to_string());
+            }
+            if players[..i].contains(player) {
+                return Err(format!("{} is listed twice", player));
+            }
+        }
+        if let TournamentFormat::Swiss { rounds } = settings.format {
+            // more rounds would pair players twice
+            if rounds == 0 || rounds >= players.len() {
+                return Err(format!(
+                    "A Swiss tournament of {} players has between 1 and {} rounds",
+                    players.len(),
+                    players.len() - 1
+                ));
+            }
+        }
+        Ok(Tournament {
+            name,
+            organizer,
+            organizer_identity,
+            format: settings.format,
+            time_control: settings.time_control,
+            rated: settings.rated,
+            identities: vec![None; players.len()],
+            withdrawn: vec![false; players.len()],
+            players,
+            rounds: Vec::new(),
+        })
+    }
+
+    /// The number of rounds of the tournament
+    pub fn total_rounds(&self) -> usize {
+        match self.format {
+            TournamentFormat::RoundRobin if self.players.len() % 2 == 1 => self.players.len(),
+            TournamentFormat::RoundRobin => self.players.len() - 1,
+            TournamentFormat::Swiss { rounds } => rounds,
+        }
+    }
+
+    pub fn is_organizer(&self, identity: &str) -> bool {
+        self.organizer_identity == identity
+    }
+
+    fn position(&self, nickname: &str) -> Option<usize> {
+        self.players.iter().position(|player| player == nickname)
+    }
+
+    /// Who the player is, `None` if no session of theirs has been seen
+    pub fn identity(&self, nickname: &str) -> Option<String> {
+        self.identities[self.position(nickname)?].clone()
+    }
+
+    /// Whether the player of the nickname may be `identity`
+    pub fn is_player(&self, nickname: &str, identity: &str) -> bool {
+        match self.position(nickname) {
+            Some(player) => match self.identities[player] {
+                Some(ref known) => known == identity,
+                None => true,
+            },
+            None => false,
+        }
+    }
+
+    /// The player of the nickname is `identity` from now on, refused if they
+    /// are someone else
+    pub fn claim(&mut self, nickname: &str, identity: String) -> bool {
+        if !self.is_player(nickname, &identity) {
+            return false;
+        }
+        if let Some(player) = self.position(nickname) {
+            self.identities[player] = Some(identity);
+        }
+        true
+    }
+
+    fn active_players(&self) -> Vec<usize> {
+        (0..self.players.len())
+            .filter(|&player| !self.withdrawn[player])
+            .collect()
+    }
+
+    fn current_round(&self) -> Option<&Round> {
+        self.rounds.last()
+    }
+
+    /// All the games of the current round are over
+    pub fn round_complete(&self) -> bool {
+        match self.current_round() {
+            Some(round) => round.pairings.iter().all(|pairing| pairing.outcome.is_some()),
+            None => true,
+        }
+    }
+
+    pub fn status(&self) -> TournamentStatus {
+        if self.rounds.is_empty() {
+            TournamentStatus::Pending
+        } else if self.round_complete()
+            && (self.rounds.len() >= self.total_rounds() || self.active_players().len() < 2)
+        {
+            TournamentStatus::Finished
+        } else {
+            TournamentStatus::Running
+        }
+    }
+
+    /// Pair the players of the next round, once the current one is over.
+    /// Returns `false` when the tournament is over.
+    pub fn pair_next_round(&mut self) -> bool {
+        if !self.round_complete() || self.rounds.len() >= self.total_rounds()
+            || self.active_players().len() < 2
+        {
+            return false;
+        }
+        let round = match self.format {
+            TournamentFormat::RoundRobin => self.round_robin(self.rounds.len()),
+            TournamentFormat::Swiss { .. } => self.swiss(),
+        };
+        self.rounds.push(round);
+        true
+    }
+
+    /// The games of the current round to start: the index of the pairing and
+    /// the nicknames of the black and the white players
+    pub fn games_to_start(&self) -> Vec<(usize, String, String)> {
+        match self.current_round() {
+            Some(round) => round
+                .pairings
+                .iter()
+                .enumerate()
+                .filter(|&(_, pairing)| pairing.board_id.is_none() && pairing.outcome.is_none())
+                .map(|(index, pairing)| {
+                    (
+                        index,
+                        self.players[pairing.black].clone(),
+                        self.players[pairing.white].clone(),
+                    )
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn pairing_mut(&mut self, index: usize) -> Option<&mut Pairing> {
+        self.rounds.last_mut()?.pairings.get_mut(index)
+    }
+
+    /// The game of the pairing is played on the board
+    pub fn set_board(&mut self, index: usize, board_id: &str) {
+        if let Some(pairing) = self.pairing_mut(index) {
+            pairing.board_id = Some(board_id.to_string());
+        }
+    }
+
+    /// The game of the pairing is not played, the absent players lose it
+    pub fn forfeit(&mut self, index: usize, black_present: bool, white_present: bool) {
+        if let Some(pairing) = self.pairing_mut(index) {
+            pairing.outcome = Some(match (black_present, white_present) {
+                (true, false) => GameOutcome::BlackWins,
+                (false, true) => GameOutcome::WhiteWins,
+                _ => GameOutcome::DoubleForfeit,
+            });
+        }
+    }
+
+    /// Record the result of the game played on the board, in the current
+    /// round
+    pub fn record(&mut self, board_id: &str, outcome: GameOutcome) -> Result<(), &'static str> {
+        let pairing = self.rounds
+            .last_mut()
+            .and_then(|round| {
+                round
+                    .pairings
+                    .iter_mut()
+                    .find(|pairing| pairing.board_id.iter().any(|id| id == board_id))
+            })
+            .ok_or("the board is not a game of the current round")?;
+        if pairing.outcome.is_some() {
+            return Err("the result of the game is already recorded");
+        }
+        pairing.outcome = Some(outcome);
+        Ok(())
+    }
+
+    /// The player is not paired anymore. Returns the board of their game in
+    /// progress, if any, lost by the player.
+    pub fn withdraw(&mut self, nickname: &str) -> Result<Option<String>, &'static str> {
+        let player = self.position(nickname).ok_or("unknown player")?;
+        if self.withdrawn[player] {
+            return Err("the player has already withdrawn");
+        }
+        if self.status() == TournamentStatus::Finished {
+            return Err("the tournament is over");
+        }
+        self.withdrawn[player] = true;
+        Ok(self.current_round().and_then(|round| {
+            round
+                .pairings
+                .iter()
+                .find(|pairing| pairing.opponent(player).is_some() && pairing.outcome.is_none())
+                .and_then(|pairing| pairing.board_id.clone())
+        }))
+    }
+
+    /// The round `round` of the circle method: the first player stays in
+    /// place while the others turn around. A player facing the empty seat of
+    /// an odd number of players, or a withdrawn player, gets a bye.
+    fn round_robin(&self, round: usize) -> Round {
+        let mut seats: Vec<Option<usize>> = (0..self.players.len()).map(Some).collect();
+        if seats.len() % 2 == 1 {
+            seats.push(None);
+        }
+        let turning = seats.len() - 1;
+        let table: Vec<Option<usize>> = (0..seats.len())
+            .map(|i| {
+                if i == 0 {
+                    seats[0]
+                } else {
+                    seats[1 + (i - 1 + round) % turning]
+                }
+            })
+            .collect();
+        let mut next = Round::default();
+        for i in 0..table.len() / 2 {
+            let (first, second) = (table[i], table[table.len() - 1 - i]);
+            let active = |seat: Option<usize>| seat.filter(|&player| !self.withdrawn[player]);
+            match (active(first), active(second)) {
+                (Some(first), Some(second)) => {
+                    // the colors alternate from a round to the next
+                    let pairing = if (i + round) % 2 == 1 {
+                        Pairing::new(second, first)
+                    } else {
+                        Pairing::new(first, second)
+                    };
+                    next.pairings.push(pairing);
+                }
+                (Some(player), None) | (None, Some(player)) => next.byes.push(player),
+                (None, None) => {}
+            }
+        }
+        next
+    }
+
+    /// Pair the players by points, each with the best ranked player they
+    /// have not met yet
+    fn swiss(&self) -> Round {
+        let mut ranking = self.active_players();
+        ranking.sort_by_key(|&player| (Reverse(self.half_points(player)), player));
+        let mut next = Round::default();
+        if ranking.len() % 2 == 1 {
+            // the lowest ranked player without a bye yet
+            let index = ranking
+                .iter()
+                .rposition(|&player| !self.had_bye(player))
+                .unwrap_or(ranking.len() - 1);
+            next.byes.push(ranking.remove(index));
+        }
+        let pairs = pair_swiss(&ranking, &|first, second| self.have_met(first, second))
+            .unwrap_or_else(|| {
+                // every pairing meets players again, the ranking decides
+                ranking
+                    .chunks(2)
+                    .map(|pair| (pair[0], pair[1]))
+                    .collect()
+            });
+        next.pairings = pairs
+            .into_iter()
+            .map(|(first, second)| {
+                // black to the player who had it the least
+                if self.black_games(second) < self.black_games(first) {
+                    Pairing::new(second, first)
+                } else {
+                    Pairing::new(first, second)
+                }
+            })
+            .collect();
+        next
+    }
+
+    fn pairings_of(&self, player: usize) -> impl Iterator<Item = &Pairing> {
+        self.rounds
+            .iter()
+            .flat_map(|round| round.pairings.iter())
+            .filter(move |pairing| pairing.opponent(player).is_some())
+    }
+
+    fn have_met(&self, first: usize, second: usize) -> bool {
+        self.pairings_of(first)
+            .any(|pairing| pairing.opponent(first) == Some(second))
+    }
+
+    fn had_bye(&self, player: usize) -> bool {
+        self.rounds.iter().any(|round| round.byes.contains(&player))
+    }
+
+    fn black_games(&self, player: usize) -> usize {
+        self.pairings_of(player)
+            .filter(|pairing| pairing.black == player)
+            .count()
+    }
+
+    /// The points of the player, in half points
+    fn half_points(&self, player: usize) -> u32 {
+        let byes = self.rounds
+            .iter()
+            .filter(|round| round.byes.contains(&player))
+            .count() as u32;
+        let games: u32 = self.pairings_of(player)
+            .filter_map(|pairing| pairing.half_points(player))
+            .sum();
+        2 * byes + games
+    }
+
+    /// The players, the best ranked first
+    pub fn standings(&self) -> Vec<WsStanding> {
+        let points: Vec<f64> = (0..self.players.len())
+            .map(|player| f64::from(self.half_points(player)) / 2.)
+            .collect();
+        let mut standings: Vec<(usize, WsStanding)> = (0..self.players.len())
+            .map(|player| {
+                let mut buchholz = 0.;
+                let mut sonneborn_berger = 0.;
+                let mut games = 0;
+                for pairing in self.pairings_of(player) {
+                    let half_points = match pairing.half_points(player) {
+                        Some(half_points) => half_points,
+                        None => continue,
+                    };
+                    let opponent = points[pairing.opponent(player).unwrap_or(player)];
+                    buchholz += opponent;
+                    sonneborn_berger += opponent * f64::from(half_points) / 2.;
+                    games += 1;
+                }
+                let standing = WsStanding {
+                    rank: 0,
+                    player: self.players[player].clone(),
+                    points: points[player],
+                    buchholz,
+                    sonneborn_berger,
+                    games,
+                    withdrawn: self.withdrawn[player],
+                };
+                (player, standing)
+            })
+            .collect();
+        standings.sort_by(|&(first_seed, ref first), &(second_seed, ref second)| {
+            let score = |standing: &WsStanding| {
+                (standing.points, standing.buchholz, standing.sonneborn_berger)
+            };
+            score(second)
+                .partial_cmp(&score(first))
+                .unwrap_or(Ordering::Equal)
+                .then(first_seed.cmp(&second_seed))
+        });
+        standings
+            .into_iter()
+            .enumerate()
+            .map(|(index, (_, standing))| WsStanding {
+                rank: index + 1,
+                ..standing
+            })
+            .collect()
+    }
+
+    /// The tournament as published to its players
+    pub fn state(&self, tournament_id: &str) -> WsTournamentState {
+        let (pairings, byes) = match self.current_round() {
+            Some(round) => (
+                round
+                    .pairings
+                    .iter()
+                    .map(|pairing| WsPairing {
+                        black: self.players[pairing.black].clone(),
+                        white: self.players[pairing.white].clone(),
+                        board_id: pairing.board_id.clone(),
+                        outcome: pairing.outcome,
+                    })
+                    .collect(),
+                round
+                    .byes
+                    .iter()
+                    .map(|&player| self.players[player].clone())
+                    .collect(),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        WsTournamentState {
+            tournament_id: tournament_id.to_string(),
+            name: self.name.clone(),
+            organizer: self.organizer.clone(),
+            format: self.format,
+            time_control: self.time_control,
+            rated: self.rated,
+            status: self.status(),
+            round: self.rounds.len(),
+            rounds: self.total_rounds(),
+            standings: self.standings(),
+            pairings,
+            byes,
+        }
+    }
+}
+
+/// Pair the ranked players in order, each with the best ranked player they
+/// have not met, backtracking when the last players cannot be paired.
+/// `None` if every pairing meets players again.
+fn pair_swiss<F>(ranking: &[usize], have_met: &F) -> Option<Vec<(usize, usize)>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let (&first, rest) = match ranking.split_first() {
+        Some(split) => split,
+        None => return Some(Vec::new()),
+    };
+    for (index, &second) in rest.iter().enumerate() {
+        if have_met(first, second) {
+            continue;
+        }
+        let mut others = rest.to_vec();
+        others.remove(index);
+        if let Some(mut pairs) = pair_swiss(&others, have_met) {
+            pairs.insert(0, (first, second));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn tournament(players: usize, format: TournamentFormat) -> Tournament {
+        let settings = WsTournamentSettings {
+            name: "Club evening".to_string(),
+            format,
+            time_control: None,
+            rated: false,
+            players: (0..players).map(|i| format!("player{}", i)).collect(),
+        };
+        Tournament::new(settings, "organizer".to_string(), "organizer-id".to_string()).unwrap()
+    }
+
+    /// Play the games of the current round, the best seeded player wins
+    fn play_round(tournament: &mut Tournament) {
+        for (index, black, white) in tournament.games_to_start() {
+            let board_id = format!("board-{}-{}", tournament.rounds.len(), index);
+            tournament.set_board(index, &board_id);
+            let outcome = if black < white {
+                GameOutcome::BlackWins
+            } else {
+                GameOutcome::WhiteWins
+            };
+            tournament.record(&board_id, outcome).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings = |players: usize, format| WsTournamentSettings {
+            name: " Club ".to_string(),
+            format,
+            time_control: None,
+            rated: false,
+            players: (0..players).map(|i| i.to_string()).collect(),
+        };
+        let new = |settings| Tournament::new(settings, "organizer".to_string(), "id".to_string());
+        assert_eq!(new(settings(4, TournamentFormat::RoundRobin)).unwrap().name, "Club");
+        assert!(new(settings(1, TournamentFormat::RoundRobin)).is_err());
+        assert!(new(settings(MAX_PLAYERS + 1, TournamentFormat::RoundRobin)).is_err());
+        assert!(new(settings(4, TournamentFormat::Swiss { rounds: 3 })).is_ok());
+        assert!(new(settings(4, TournamentFormat::Swiss { rounds: 4 })).is_err());
+        assert!(new(settings(4, TournamentFormat::Swiss { rounds: 0 })).is_err());
+        let mut twice = settings(3, TournamentFormat::RoundRobin);
+        twice.players.push("1".to_string());
+        assert!(new(twice).is_err());
+    }
+
+    #[test]
+    fn test_round_robin() {
+        for &players in &[4, 5, 8] {
+            let mut tournament = tournament(players, TournamentFormat::RoundRobin);
+            assert_eq!(tournament.status(), TournamentStatus::Pending);
+            let mut met = HashSet::new();
+            let mut byes = vec![0; players];
+            while tournament.pair_next_round() {
+                {
+                    let round = tournament.current_round().unwrap();
+                    for pairing in &round.pairings {
+                        let pair = (
+                            pairing.black.min(pairing.white),
+                            pairing.black.max(pairing.white),
+                        );
+                        assert!(met.insert(pair), "{:?} meet twice", pair);
+                    }
+                    for &player in &round.byes {
+                        byes[player] += 1;
+                    }
+                }
+                play_round(&mut tournament);
+            }
+            assert_eq!(tournament.status(), TournamentStatus::Finished);
+            assert_eq!(tournament.rounds.len(), tournament.total_rounds());
+            // everybody met everybody
+            assert_eq!(met.len(), players * (players - 1) / 2);
+            let expected_byes = if players % 2 == 1 { 1 } else { 0 };
+            assert!(byes.iter().all(|&count| count == expected_byes));
+        }
+    }
+
+    #[test]
+    fn test_swiss() {
+        let mut tournament = tournament(7, TournamentFormat::Swiss { rounds: 4 });
+        let mut met = HashSet::new();
+        let mut byes = HashSet::new();
+        while tournament.pair_next_round() {
+            {
+                let round = tournament.current_round().unwrap();
+                assert_eq!(round.pairings.len(), 3);
+                assert_eq!(round.byes.len(), 1);
+                assert!(byes.insert(round.byes[0]), "two byes for {}", round.byes[0]);
+                for pairing in &round.pairings {
+                    let pair = (
+                        pairing.black.min(pairing.white),
+                        pairing.black.max(pairing.white),
+                    );
+                    assert!(met.insert(pair), "{:?} meet twice", pair);
+                }
+            }
+            play_round(&mut tournament);
+        }
+        assert_eq!(tournament.rounds.len(), 4);
+        assert_eq!(tournament.status(), TournamentStatus::Finished);
+        // the first seed won all their games
+        let standings = tournament.standings();
+        assert_eq!(standings[0].player, "player0");
+        assert_eq!(standings[0].points, 4.);
+        assert_eq!(standings[0].rank, 1);
+    }
+
+    #[test]
+    fn test_swiss_pairs_by_points() {
+        let mut tournament = tournament(4, TournamentFormat::Swiss { rounds: 2 });
+        assert!(tournament.pair_next_round());
+        // the round is not over
+        assert!(!tournament.pair_next_round());
+        play_round(&mut tournament);
+        assert!(tournament.pair_next_round());
+        let round = tournament.current_round().unwrap();
+        // the winners meet, and so do the losers
+        let mut pairs: Vec<(usize, usize)> = round
+            .pairings
+            .iter()
+            .map(|pairing| (pairing.black.min(pairing.white), pairing.black.max(pairing.white)))
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn test_pair_swiss_backtracks() {
+        // 0-1 and 2-3 have met, so 0 cannot take 1 and 2 cannot take 3
+        let met = |a: usize, b: usize| {
+            let pair = (a.min(b), a.max(b));
+            pair == (0, 1) || pair == (2, 3) || pair == (0, 2)
+        };
+        assert_eq!(pair_swiss(&[0, 1, 2, 3], &met), Some(vec![(0, 3), (1, 2)]));
+        let all = |_: usize, _: usize| true;
+        assert_eq!(pair_swiss(&[0, 1], &all), None);
+    }
+
+    #[test]
+    fn test_identities() {
+        let mut tournament = tournament(2, TournamentFormat::RoundRobin);
+        assert!(tournament.is_organizer("organizer-id"));
+        assert!(!tournament.is_organizer("organizer"));
+
+        // a player is whoever is seen first with the nickname
+        assert_eq!(tournament.identity("player0"), None);
+        assert!(tournament.is_player("player0", "guest-a"));
+        assert!(tournament.claim("player0", "guest-a".to_string()));
+        assert_eq!(tournament.identity("player0"), Some("guest-a".to_string()));
+        assert!(tournament.claim("player0", "guest-a".to_string()));
+        assert!(!tournament.is_player("player0", "guest-b"));
+        assert!(!tournament.claim("player0", "guest-b".to_string()));
+        assert_eq!(tournament.identity("player0"), Some("guest-a".to_string()));
+
+        assert!(!tournament.is_player("stranger", "guest-b"));
+        assert!(!tournament.claim("stranger", "guest-b".to_string()));
+        assert_eq!(tournament.identity("stranger"), None);
+    }
+
+    #[test]
+    fn test_forfeit_and_withdrawal() {
+        let mut tournament = tournament(4, TournamentFormat::RoundRobin);
+        assert!(tournament.pair_next_round());
+        let games = tournament.games_to_start();
+        assert_eq!(games.len(), 2);
+        let (first, ref black, _) = games[0];
+        let (second, _, _) = games[1];
+        tournament.forfeit(first, true, false);
+        tournament.set_board(second, "board");
+        assert!(tournament.record("other", GameOutcome::Draw).is_err());
+        assert!(!tournament.round_complete());
+
+        // the player of the game in progress loses it
+        let white = tournament.state("t").pairings[second].white.clone();
+        assert_eq!(tournament.withdraw(&white), Ok(Some("board".to_string())));
+        assert!(tournament.withdraw(&white).is_err());
+        tournament.record("board", GameOutcome::BlackWins).unwrap();
+        assert!(tournament.record("board", GameOutcome::BlackWins).is_err());
+
+        let standings = tournament.standings();
+        let forfeit_winner = standings.iter().find(|standing| standing.player == *black).unwrap();
+        assert_eq!(forfeit_winner.points, 1.);
+        let withdrawn = standings.iter().find(|standing| standing.player == white).unwrap();
+        assert!(withdrawn.withdrawn);
+
+        // the opponents of the withdrawn player get a bye
+        assert!(tournament.pair_next_round());
+        let state = tournament.state("t");
+        assert_eq!(state.round, 2);
+        assert_eq!(state.pairings.len(), 1);
+        assert_eq!(state.byes.len(), 1);
+        assert!(!state.pairings.iter().any(|pairing| pairing.black == white || pairing.white == white));
+    }
+
+    #[test]
+    fn test_tiebreaks() {
+        let mut tournament = tournament(3, TournamentFormat::RoundRobin);
+        while tournament.pair_next_round() {
+            for (index, black, white) in tournament.games_to_start() {
+                let board_id = format!("{}-{}", black, white);
+                tournament.set_board(index, &board_id);
+                let pair = (black.as_str().min(white.as_str()), black.as_str().max(white.as_str()));
+                // 0 beats 1, 1 beats 2, 0 and 2 draw
+                let winner = match pair {
+                    ("player0", "player1") => Some("player0"),
+                    ("player1", "player2") => Some("player1"),
+                    _ => None,
+                };
+                let outcome = match winner {
+                    Some(winner) if winner == black => GameOutcome::BlackWins,
+                    Some(_) => GameOutcome::WhiteWins,
+                    None => GameOutcome::Draw,
+                };
+                tournament.record(&board_id, outcome).unwrap();
+            }
+        }
+        let standings = tournament.standings();
+        // one bye each
+        let points: Vec<(&str, f64)> = standings
+            .iter()
+            .map(|standing| (standing.player.as_str(), standing.points))
+            .collect();
+        assert_eq!(points, vec![("player0", 2.5), ("player1", 2.), ("player2", 1.5)]);
+        assert_eq!(standings[0].buchholz, 2. + 1.5);
+        assert_eq!(standings[0].sonneborn_berger, 2. + 1.5 / 2.);
+        assert_eq!(standings[0].games, 2);
+    }
+}
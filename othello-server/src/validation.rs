@@ -0,0 +1,333 @@
+//! Validation of the requests of the clients.
+//!
+//! The websocket session checks each request before forwarding it to the
+//! server: the ids, the nicknames and the positions must be well formed, and
+//! the session id of the payload must be the one of the websocket. A refused
+//! request is answered by an `Error`, and the websocket is closed when its
+//! client keeps sending too many requests or too many invalid ones.
+//!
+//! The registrations of the accounts are limited by remote address, each one
+//! hashes a password.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{self, Value};
+
+use account;
+use engine::model::BOARD_SIZE;
+use wscommand::{WsError, WsErrorCode, WsRequest};
+
+/// The ids of the sessions, the boards and the tournaments are alphanumeric
+pub const MAX_ID_LEN: usize = 64;
+/// A session sends at most `REQUEST_RATE` requests per `REQUEST_WINDOW`
+/// seconds, the next ones are refused
+pub const REQUEST_RATE: usize = 40;
+pub const REQUEST_WINDOW: u64 = 10;
+/// The websocket is closed after `ERROR_RATE` errors per `ERROR_WINDOW`
+/// seconds
+pub const ERROR_RATE: usize = 10;
+pub const ERROR_WINDOW: u64 = 60;
+/// An address registers at most `REGISTER_RATE` accounts per
+/// `REGISTER_WINDOW` seconds
+pub const REGISTER_RATE: usize = 5;
+pub const REGISTER_WINDOW: u64 = 600;
+
+/// The events recently counted for a session
+pub struct RateLimit {
+    sent: VecDeque<Instant>,
+    max: usize,
+    window: Duration,
+}
+
+impl RateLimit {
+    /// At most `max` events per `window`
+    pub fn new(max: usize, window: Duration) -> Self {
+        RateLimit {
+            sent: VecDeque::new(),
+            max,
+            window,
+        }
+    }
+
+    /// The requests of a websocket
+    pub fn requests() -> Self {
+        RateLimit::new(REQUEST_RATE, Duration::from_secs(REQUEST_WINDOW))
+    }
+
+    /// The refused requests of a websocket
+    pub fn errors() -> Self {
+        RateLimit::new(ERROR_RATE, Duration::from_secs(ERROR_WINDOW))
+    }
+
+    /// The registrations of an address
+    pub fn registrations() -> Self {
+        RateLimit::new(REGISTER_RATE, Duration::from_secs(REGISTER_WINDOW))
+    }
+
+    /// Whether all the counted events are out of the window
+    fn is_idle(&self, now: Instant) -> bool {
+        match self.sent.back() {
+            Some(&sent) => now.duration_since(sent) >= self.window,
+            None => true,
+        }
+    }
+
+    /// Whether the event can happen `now`, counting it if so
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while let Some(&sent) = self.sent.front() {
+            if now.duration_since(sent) < self.window {
+                break;
+            }
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.max {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// The rate limits of the remote addresses, shared by the workers of the
+/// http server. The unknown addresses share the limit of `None`.
+pub struct AddrRateLimits {
+    limits: Mutex<HashMap<Option<IpAddr>, RateLimit>>,
+    new_limit: fn() -> RateLimit,
+}
+
+impl AddrRateLimits {
+    pub fn new(new_limit: fn() -> RateLimit) -> Self {
+        AddrRateLimits {
+            limits: Mutex::new(HashMap::new()),
+            new_limit,
+        }
+    }
+
+    /// Whether the address can do it `now`, counting it if so
+    pub fn allow(&self, addr: Option<IpAddr>, now: Instant) -> bool {
+        let mut limits = self.limits.lock().unwrap_or_else(|err| err.into_inner());
+        // forget the addresses without recent events
+        limits.retain(|_, limit| !limit.is_idle(now));
+        limits.entry(addr).or_insert_with(self.new_limit).allow(now)
+    }
+}
+
+pub fn check_id(field: &str, id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > MAX_ID_LEN || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid {}", field));
+    }
+    Ok(())
+}
+
+fn check_optional_id(field: &str, id: &Option<String>) -> Result<(), String> {
+    match *id {
+        Some(ref id) => check_id(field, id),
+        None => Ok(()),
+    }
+}
+
+pub fn check_pos(pos: (usize, usize)) -> Result<(), String> {
+    let (x, y) = pos;
+    if x >= BOARD_SIZE || y >= BOARD_SIZE {
+        return Err(format!("The position {:?} is out of the board", pos));
+    }
+    Ok(())
+}
+
+/// The fields of the request, the chat messages and the board settings are
+/// checked by the server which tells the user what is wrong
+fn check_fields(req: &WsRequest) -> Result<(), String> {
+    match *req {
+        WsRequest::ConnectingParam(ref param) => account::check_name(&param.nickname),
+        WsRequest::JoinBoard(ref param) => {
+            check_optional_id("board_id", &param.board_id)?;
+            let code = param.invite_code.as_ref().map(|code| code.trim().to_string());
+            check_optional_id("invite_code", &code)
+        }
+        WsRequest::PlayBoard(ref param) => {
+            check_id("board_id", &param.board_id)?;
+            check_pos(param.pos)
+        }
+        WsRequest::Resume(ref param) => check_id("resume_token", &param.resume_token),
+        WsRequest::CancelBoard(ref param) => check_id("board_id", &param.board_id),
+        WsRequest::WatchBoard(ref param) | WsRequest::StopWatching(ref param) => {
+            check_id("board_id", &param.board_id)
+        }
+        WsRequest::SendChat(ref param) => check_optional_id("board_id", &param.board_id),
+        WsRequest::MuteOpponent(ref param) => check_id("board_id", &param.board_id),
+        WsRequest::Resign(ref param)
+        | WsRequest::Abort(ref param)
+        | WsRequest::OfferRematch(ref param)
+        | WsRequest::DeclineRematch(ref param) => check_id("board_id", &param.board_id),
+        WsRequest::CreateTournament(ref param) => {
+            for player in &param.settings.players {
+                account::check_name(player)?;
+            }
+            Ok(())
+        }
+        WsRequest::StartTournament(ref param) | WsRequest::GetTournament(ref param) => {
+            check_id("tournament_id", &param.tournament_id)
+        }
+        WsRequest::WithdrawTournament(ref param) => {
+            check_id("tournament_id", &param.tournament_id)?;
+            account::check_name(&param.player)
+        }
+        WsRequest::Register(ref param) | WsRequest::Login(ref param) => {
+            account::check_name(&param.name)?;
+            account::check_password(&param.password)
+        }
+        WsRequest::CreateBoard(_)
+        | WsRequest::KeepAlive
+        | WsRequest::ListOpenBoards
+        | WsRequest::LeaveLobby
+        | WsRequest::ListLiveBoards => Ok(()),
+    }
+}
+
+/// Refuse the request of the session `sess_id` if it is not well formed
+pub fn check_request(req: &WsRequest, sess_id: &str) -> Result<(), WsError> {
+    let request = Some(req.name().to_string());
+    if let Some(id) = req.session_id() {
+        if id != sess_id {
+            return Err(WsError {
+                code: WsErrorCode::InvalidSession,
+                message: "The session id is not the one of the connection".to_string(),
+                request,
+            });
+        }
+    }
+    check_fields(req).map_err(|message| WsError {
+        code: WsErrorCode::InvalidInput,
+        message,
+        request,
+    })
+}
+
+/// The name of a request that cannot be read, if it has one
+fn request_name(text: &str) -> Option<String> {
+    let name = match serde_json::from_str(text) {
+        Ok(Value::String(name)) => name,
+        Ok(Value::Object(ref fields)) if fields.len() == 1 => fields.keys().next()?.clone(),
+        _ => return None,
+    };
+    // not to send back whatever the client sent
+    if name.len() > MAX_ID_LEN || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some(name)
+}
+
+/// Read the request of a text frame
+pub fn parse_request(text: &str) -> Result<WsRequest, WsError> {
+    serde_json::from_str(text).map_err(|err| WsError {
+        code: WsErrorCode::InvalidRequest,
+        message: format!("Cannot read the request: {}", err),
+        request: request_name(text),
+    })
+}
+
+/// The answer to a request sent too soon after the previous ones
+pub fn rate_limited() -> WsError {
+    WsError {
+        code: WsErrorCode::RateLimited,
+        message: format!(
+            "Too many requests, at most {} per {} seconds",
+            REQUEST_RATE, REQUEST_WINDOW
+        ),
+        request: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wscommand::{WsCredentials, WsGameAction, WsPlayBoard};
+
+    #[test]
+    fn test_rate_limit() {
+        let mut limit = RateLimit::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        assert!(limit.allow(at(0)));
+        assert!(limit.allow(at(1)));
+        assert!(!limit.allow(at(2)));
+        // the first event is out of the window, the refused one is not counted
+        assert!(limit.allow(at(10)));
+        assert!(!limit.allow(at(10)));
+    }
+
+    #[test]
+    fn test_addr_rate_limits() {
+        let limits = AddrRateLimits::new(|| RateLimit::new(1, Duration::from_secs(10)));
+        let start = Instant::now();
+        let alice = Some("10.0.0.1".parse().unwrap());
+        let bob = Some("10.0.0.2".parse().unwrap());
+        assert!(limits.allow(alice, start));
+        assert!(!limits.allow(alice, start + Duration::from_secs(1)));
+        assert!(limits.allow(bob, start + Duration::from_secs(1)));
+        assert!(limits.allow(None, start + Duration::from_secs(1)));
+        assert!(!limits.allow(None, start + Duration::from_secs(2)));
+        assert!(limits.allow(alice, start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_check_id() {
+        assert!(check_id("board_id", "aZ09").is_ok());
+        assert!(check_id("board_id", "").is_err());
+        assert!(check_id("board_id", "a-b").is_err());
+        assert!(check_id("board_id", "é").is_err());
+        assert!(check_id("board_id", &"a".repeat(MAX_ID_LEN + 1)).is_err());
+        assert!(check_pos((BOARD_SIZE - 1, 0)).is_ok());
+        assert!(check_pos((0, BOARD_SIZE)).is_err());
+    }
+
+    #[test]
+    fn test_check_request() {
+        let play = |session_id: &str, board_id: &str, pos| {
+            WsRequest::PlayBoard(WsPlayBoard {
+                session_id: session_id.to_string(),
+                board_id: board_id.to_string(),
+                pos,
+                clocks: None,
+            })
+        };
+        assert!(check_request(&play("s1", "b1", (2, 3)), "s1").is_ok());
+        let err = check_request(&play("s2", "b1", (2, 3)), "s1").unwrap_err();
+        assert_eq!(err.code, WsErrorCode::InvalidSession);
+        assert_eq!(err.request, Some("PlayBoard".to_string()));
+        let err = check_request(&play("s1", "b1", (2, BOARD_SIZE)), "s1").unwrap_err();
+        assert_eq!(err.code, WsErrorCode::InvalidInput);
+        let err = check_request(&play("s1", "../b1", (2, 3)), "s1").unwrap_err();
+        assert_eq!(err.code, WsErrorCode::InvalidInput);
+
+        let resign = WsRequest::Resign(WsGameAction {
+            session_id: "s1".to_string(),
+            board_id: "".to_string(),
+        });
+        assert!(check_request(&resign, "s1").is_err());
+        let login = |name: &str, password: &str| {
+            WsRequest::Login(WsCredentials {
+                session_id: "s1".to_string(),
+                name: name.to_string(),
+                password: password.to_string(),
+            })
+        };
+        assert!(check_request(&login("alice", "correct horse"), "s1").is_ok());
+        assert!(check_request(&login("alice bob", "correct horse"), "s1").is_err());
+        let long = "a".repeat(account::MAX_PASSWORD_LEN + 1);
+        assert!(check_request(&login("alice", &long), "s1").is_err());
+    }
+
+    #[test]
+    fn test_parse_request() {
+        assert!(parse_request(r#""KeepAlive""#).is_ok());
+        let err = parse_request(r#"{"PlayBoard": {"pos": [2, 3]}}"#).unwrap_err();
+        assert_eq!(err.code, WsErrorCode::InvalidRequest);
+        assert_eq!(err.request, Some("PlayBoard".to_string()));
+        assert_eq!(parse_request("not json").unwrap_err().request, None);
+        assert_eq!(parse_request(r#"{"<b>": 1}"#).unwrap_err().request, None);
+    }
+}
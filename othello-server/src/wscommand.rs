@@ -1,3 +1,8 @@
+use std::fmt;
+
+use rating::Rating;
+use storage::STANDARD_VARIANT;
+
 ///
 /// Request parameters
 ///
@@ -5,19 +10,130 @@
 /// Connect parameter
 #[derive(Deserialize, Debug)]
 pub struct WsConnectingParam {
-    /// a user nickname
+    /// a user nickname, refused if it is the name of an account
     pub nickname: String,
 }
 
+/// Register an account, or log in to it
+#[derive(Deserialize)]
+pub struct WsCredentials {
+    /// a previsouly registered session id
+    pub session_id: String,
+    /// the name of the account, the nickname of the user once logged in
+    pub name: String,
+    pub password: String,
+}
+
+/// Never print the password in the logs
+impl fmt::Debug for WsCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WsCredentials")
+            .field("session_id", &self.session_id)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
 /// Connect parameter
 #[derive(Deserialize, Debug)]
 pub struct WsJoinBoard {
     /// a previsouly registered session id
     pub session_id: String,
+    /// an open board, `None` to join the first one waiting for a quick game
+    pub board_id: Option<String>,
+    /// the code of a private board, given by its creator
+    #[serde(default)]
+    pub invite_code: Option<String>,
+    /// the clock of a quick game, `None` for a game without clock
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+}
+
+/// The color the creator of a board wants to play
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ColorPreference {
+    Black,
+    White,
+    /// drawn when the board is created
+    Random,
+}
+
+/// How the clock of a player gets time back after each move
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ClockMode {
+    /// the increment is added, sudden death without increment
+    Fischer,
+    /// the time used is given back, up to the increment
+    Bronstein,
+}
+
+fn fischer() -> ClockMode {
+    ClockMode::Fischer
+}
+
+/// The clock of each player, `minutes` for the game plus `increment`
+/// seconds per move
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TimeControl {
+    pub minutes: u32,
+    pub increment: u32,
+    #[serde(default = "fischer")]
+    pub mode: ClockMode,
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            ClockMode::Fischer => write!(f, "{}+{}", self.minutes, self.increment),
+            ClockMode::Bronstein => write!(f, "{}d{}", self.minutes, self.increment),
+        }
+    }
+}
+
+fn standard_variant() -> String {
+    STANDARD_VARIANT.to_string()
+}
+
+/// How the game of a board is played
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WsBoardSettings {
+    pub color: ColorPreference,
+    /// `None` for a game without clock
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    #[serde(default = "standard_variant")]
+    pub variant: String,
+    /// the result changes the ratings of the players
+    #[serde(default)]
+    pub rated: bool,
+    /// the board is not listed, it is joined with its invite code
+    #[serde(default)]
+    pub private: bool,
+}
+
+/// The settings of the quick games
+impl Default for WsBoardSettings {
+    fn default() -> Self {
+        WsBoardSettings {
+            color: ColorPreference::Black,
+            time_control: None,
+            variant: standard_variant(),
+            rated: false,
+            private: false,
+        }
+    }
+}
+
+/// Create a board and wait for an opponent
+#[derive(Deserialize, Debug)]
+pub struct WsCreateBoard {
+    /// a previously registered session id
+    pub session_id: String,
+    pub settings: WsBoardSettings,
 }
 
 /// User is sending a move
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WsPlayBoard {
     /// a board id
     pub session_id: String,
@@ -25,17 +141,114 @@ pub struct WsPlayBoard {
     pub board_id: String,
     /// a position
     pub pos: (usize, usize),
+    /// the clocks once the move is played, sent by the server
+    #[serde(default, skip_deserializing)]
+    pub clocks: Option<WsClocks>,
 }
 
-/// User is sending a gameober
+/// Resume a session after a page reload or a network issue
 #[derive(Deserialize, Debug)]
-pub struct WsGameOver {
+pub struct WsResume {
+    /// the token received in the `ConnectedParam`
+    pub resume_token: String,
+}
+
+/// Close a board waiting for an opponent
+#[derive(Deserialize, Debug)]
+pub struct WsCancelBoard {
+    /// a previously registered session id
+    pub session_id: String,
     /// a board id
+    pub board_id: String,
+}
+
+/// Watch a game in progress, or stop watching it
+#[derive(Deserialize, Debug)]
+pub struct WsWatchBoard {
+    /// a previously registered session id
     pub session_id: String,
     /// a board id
     pub board_id: String,
-    /// a position
-    pub score: (usize, usize),
+}
+
+/// Send a message to a board, or to the lobby
+#[derive(Deserialize, Debug)]
+pub struct WsSendChat {
+    /// a previously registered session id
+    pub session_id: String,
+    /// a board where the user plays or watches, `None` for the lobby
+    pub board_id: Option<String>,
+    pub text: String,
+}
+
+/// Stop receiving the messages of the opponent, or receive them again
+#[derive(Deserialize, Debug)]
+pub struct WsMuteOpponent {
+    /// a previously registered session id
+    pub session_id: String,
+    /// a board id
+    pub board_id: String,
+    pub muted: bool,
+}
+
+/// A player ends their game, or asks to play it again
+#[derive(Deserialize, Debug)]
+pub struct WsGameAction {
+    /// a previously registered session id
+    pub session_id: String,
+    /// the board of the game, the finished one for a rematch
+    pub board_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TournamentFormat {
+    /// every player meets every other player once
+    RoundRobin,
+    /// the players with the same points meet, for a number of rounds
+    Swiss { rounds: usize },
+}
+
+/// A tournament, as defined by its organizer
+#[derive(Deserialize, Debug, Clone)]
+pub struct WsTournamentSettings {
+    pub name: String,
+    pub format: TournamentFormat,
+    /// `None` for games without clock
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    /// the results change the ratings of the players
+    #[serde(default)]
+    pub rated: bool,
+    /// the nicknames of the players, the best seeded first
+    pub players: Vec<String>,
+}
+
+/// Create a tournament, run by the session
+#[derive(Deserialize, Debug)]
+pub struct WsCreateTournament {
+    /// a previously registered session id
+    pub session_id: String,
+    pub settings: WsTournamentSettings,
+}
+
+/// Start a tournament, or ask for its standings
+#[derive(Deserialize, Debug)]
+pub struct WsTournamentAction {
+    /// a previously registered session id
+    pub session_id: String,
+    /// a tournament id
+    pub tournament_id: String,
+}
+
+/// A player leaves a tournament, by themselves or by the organizer
+#[derive(Deserialize, Debug)]
+pub struct WsWithdraw {
+    /// a previously registered session id
+    pub session_id: String,
+    /// a tournament id
+    pub tournament_id: String,
+    /// the nickname of the player
+    pub player: String,
 }
 
 /// This type handle type per command
@@ -45,7 +258,109 @@ pub enum WsRequest {
     ConnectingParam(WsConnectingParam),
     JoinBoard(WsJoinBoard),
     PlayBoard(WsPlayBoard),
-    GameOver(WsGameOver),
+    /// sent periodically by the client, answered by a `KeepAlive`
+    KeepAlive,
+    /// rebind the websocket to a previous session, see `server::Resume`
+    Resume(WsResume),
+    CreateBoard(WsCreateBoard),
+    /// the creator leaves the board before an opponent joins it
+    CancelBoard(WsCancelBoard),
+    /// the boards waiting for an opponent, answered by `OpenBoards` and
+    /// again each time they change until `LeaveLobby`
+    ListOpenBoards,
+    LeaveLobby,
+    /// the games that can be watched, answered by `LiveBoards`
+    ListLiveBoards,
+    WatchBoard(WsWatchBoard),
+    StopWatching(WsWatchBoard),
+    /// forwarded as a `Chat` to the users of the board or of the lobby
+    SendChat(WsSendChat),
+    MuteOpponent(WsMuteOpponent),
+    /// the opponent wins the game
+    Resign(WsGameAction),
+    /// end the game without result, before both players have moved
+    Abort(WsGameAction),
+    /// offer the opponent of a finished game to play again with the colors
+    /// swapped, or accept their offer
+    OfferRematch(WsGameAction),
+    DeclineRematch(WsGameAction),
+    /// answered by a `Tournament`, sent again to the organizer and the
+    /// players each time the tournament changes
+    CreateTournament(WsCreateTournament),
+    /// the organizer starts the first round, the next ones start when the
+    /// games of the previous one are over
+    StartTournament(WsTournamentAction),
+    GetTournament(WsTournamentAction),
+    WithdrawTournament(WsWithdraw),
+    /// create an account and log in to it, answered by a `LoggedIn`
+    Register(WsCredentials),
+    /// the nickname of the session becomes the name of the account
+    Login(WsCredentials),
+}
+
+impl WsRequest {
+    /// The name of the request, as sent by the client
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WsRequest::ConnectingParam(_) => "ConnectingParam",
+            WsRequest::JoinBoard(_) => "JoinBoard",
+            WsRequest::PlayBoard(_) => "PlayBoard",
+            WsRequest::KeepAlive => "KeepAlive",
+            WsRequest::Resume(_) => "Resume",
+            WsRequest::CreateBoard(_) => "CreateBoard",
+            WsRequest::CancelBoard(_) => "CancelBoard",
+            WsRequest::ListOpenBoards => "ListOpenBoards",
+            WsRequest::LeaveLobby => "LeaveLobby",
+            WsRequest::ListLiveBoards => "ListLiveBoards",
+            WsRequest::WatchBoard(_) => "WatchBoard",
+            WsRequest::StopWatching(_) => "StopWatching",
+            WsRequest::SendChat(_) => "SendChat",
+            WsRequest::MuteOpponent(_) => "MuteOpponent",
+            WsRequest::Resign(_) => "Resign",
+            WsRequest::Abort(_) => "Abort",
+            WsRequest::OfferRematch(_) => "OfferRematch",
+            WsRequest::DeclineRematch(_) => "DeclineRematch",
+            WsRequest::CreateTournament(_) => "CreateTournament",
+            WsRequest::StartTournament(_) => "StartTournament",
+            WsRequest::GetTournament(_) => "GetTournament",
+            WsRequest::WithdrawTournament(_) => "WithdrawTournament",
+            WsRequest::Register(_) => "Register",
+            WsRequest::Login(_) => "Login",
+        }
+    }
+
+    /// The session id of the payload, `None` for the requests without one
+    pub fn session_id(&self) -> Option<&str> {
+        match *self {
+            WsRequest::JoinBoard(ref param) => Some(&param.session_id),
+            WsRequest::PlayBoard(ref param) => Some(&param.session_id),
+            WsRequest::CreateBoard(ref param) => Some(&param.session_id),
+            WsRequest::CancelBoard(ref param) => Some(&param.session_id),
+            WsRequest::WatchBoard(ref param) | WsRequest::StopWatching(ref param) => {
+                Some(&param.session_id)
+            }
+            WsRequest::SendChat(ref param) => Some(&param.session_id),
+            WsRequest::MuteOpponent(ref param) => Some(&param.session_id),
+            WsRequest::Resign(ref param)
+            | WsRequest::Abort(ref param)
+            | WsRequest::OfferRematch(ref param)
+            | WsRequest::DeclineRematch(ref param) => Some(&param.session_id),
+            WsRequest::CreateTournament(ref param) => Some(&param.session_id),
+            WsRequest::StartTournament(ref param) | WsRequest::GetTournament(ref param) => {
+                Some(&param.session_id)
+            }
+            WsRequest::WithdrawTournament(ref param) => Some(&param.session_id),
+            WsRequest::Register(ref param) | WsRequest::Login(ref param) => {
+                Some(&param.session_id)
+            }
+            WsRequest::ConnectingParam(_)
+            | WsRequest::KeepAlive
+            | WsRequest::Resume(_)
+            | WsRequest::ListOpenBoards
+            | WsRequest::LeaveLobby
+            | WsRequest::ListLiveBoards => None,
+        }
+    }
 }
 
 ///
@@ -58,14 +373,57 @@ pub struct WsConnectedParam {
     /// a session id
     pub session_id: String,
     pub users_count: usize,
+    /// a secret to resume the session from another websocket
+    pub resume_token: String,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum Color {
     Black,
     White,
 }
 
+/// The Glicko-2 rating of a player
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct WsRating {
+    pub rating: f64,
+    pub deviation: f64,
+    /// the player has not played enough rated games for a reliable rating
+    pub provisional: bool,
+}
+
+impl<'a> From<&'a Rating> for WsRating {
+    fn from(rating: &'a Rating) -> Self {
+        WsRating {
+            rating: rating.rating.round(),
+            deviation: rating.deviation.round(),
+            provisional: rating.is_provisional(),
+        }
+    }
+}
+
+/// The time left to the players, kept by the server
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WsClocks {
+    /// milliseconds
+    pub black: u64,
+    /// milliseconds
+    pub white: u64,
+    /// the player whose clock runs
+    pub running: Option<Color>,
+}
+
+/// The clocks of a board, when the game starts and after the moves of the
+/// user
+#[derive(Serialize, Debug)]
+pub struct WsBoardClocks {
+    /// registered session id
+    pub session_id: String,
+    /// a board id
+    pub board_id: String,
+    pub clocks: WsClocks,
+}
+
 /// Connected parameter
 #[derive(Serialize, Debug)]
 pub struct WsJoinedBoard {
@@ -77,6 +435,33 @@ pub struct WsJoinedBoard {
     pub color: Color,
     /// the nick of the opponnent user
     pub opponent: Option<String>,
+    /// the opponent has no account
+    pub opponent_guest: bool,
+    /// the code to share to join a private board, sent to its creator
+    pub invite_code: Option<String>,
+    /// the rating of the user, `None` if the server does not rate players
+    pub rating: Option<WsRating>,
+    /// the rating of the opponent, if any
+    pub opponent_rating: Option<WsRating>,
+    /// the game is rated, the engine does not help the players
+    pub rated: bool,
+}
+
+/// The board has been closed before an opponent joined it
+#[derive(Serialize, Debug)]
+pub struct WsBoardCancelled {
+    /// registered session id
+    pub session_id: String,
+    /// a board id
+    pub board_id: String,
+}
+
+/// No board can be joined
+#[derive(Serialize, Debug)]
+pub struct WsJoinFailed {
+    /// registered session id
+    pub session_id: String,
+    pub reason: String,
 }
 
 /// Connected parameter
@@ -88,15 +473,367 @@ pub struct WsOpponentJoinedBoard {
     pub board_id: String,
     /// the nick of the opponnent user
     pub opponent: String,
+    /// the opponent has no account
+    pub opponent_guest: bool,
+    /// the rating of the opponent, `None` if the server does not rate players
+    pub opponent_rating: Option<WsRating>,
 }
 
-/// Connected parameter
+/// Why a game is over
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum GameOverReason {
+    /// no player can move anymore
+    Normal,
+    /// the loser resigned
+    Resignation,
+    /// the loser ran out of time
+    Timeout,
+    /// the loser left the board
+    Disconnect,
+    /// a player left before both had moved, without winner
+    Aborted,
+}
+
+/// The final result of a game, decided by the server
 #[derive(Serialize, Debug)]
-pub struct WsOpponentDisconnected {
-    /// a session id of the user that will be ejected
+pub struct WsGameResult {
+    /// registered session id
+    pub session_id: String,
+    /// the board id, retired after the result
+    pub board_id: String,
+    /// the winner, `None` for a draw
+    pub winner: Option<Color>,
+    /// (black discs, white discs)
+    pub score: (usize, usize),
+    pub reason: GameOverReason,
+}
+
+/// A board waiting for an opponent
+#[derive(Serialize, Debug, Clone)]
+pub struct WsOpenBoard {
+    /// a board id
+    pub board_id: String,
+    /// the nick of the user waiting on the board
+    pub creator: String,
+    /// the creator has no account
+    pub creator_guest: bool,
+    /// the color of the free seat
+    pub color: Color,
+    pub settings: WsBoardSettings,
+}
+
+/// The position of a board, to draw it again
+#[derive(Serialize, Debug, Clone)]
+pub struct WsBoardState {
+    /// 64 squares, `B` and `W` for discs, `.` for empty squares
+    pub position: String,
+    /// the player who has to move
+    pub current_player: Color,
+}
+
+/// The server refused a move
+#[derive(Serialize, Debug)]
+pub struct WsRejectedMove {
+    /// a session id of the user who sent the move
     pub session_id: String,
     /// a board id
     pub board_id: String,
+    /// the refused position
+    pub pos: (usize, usize),
+    /// why the move is refused
+    pub reason: String,
+    /// the state of the board, to replace the one of the client
+    pub board: Option<WsBoardState>,
+}
+
+/// A board where a resumed session is seated
+#[derive(Serialize, Debug)]
+pub struct WsResumedBoard {
+    /// a board id
+    pub board_id: String,
+    /// the user color
+    pub color: Color,
+    /// the nick of the opponnent user
+    pub opponent: Option<String>,
+    /// the state of the board
+    pub state: WsBoardState,
+    /// the moves played since the beginning, `x + y * 8`
+    pub moves: Vec<usize>,
+    /// `None` for a game without clock
+    pub clocks: Option<WsClocks>,
+    /// the game is rated, the engine does not help the players
+    pub rated: bool,
+}
+
+/// The session has been resumed
+#[derive(Serialize, Debug)]
+pub struct WsResumed {
+    /// the resumed session id
+    pub session_id: String,
+    /// the nickname of the resumed session
+    pub nickname: Option<String>,
+    pub users_count: usize,
+    /// the game in progress, if any
+    pub board: Option<WsResumedBoard>,
+}
+
+/// The session cannot be resumed, a new one has to be created
+#[derive(Serialize, Debug)]
+pub struct WsResumeFailed {
+    pub reason: String,
+}
+
+/// A player lost the connection or came back
+#[derive(Serialize, Debug)]
+pub struct WsOpponentPresence {
+    /// registered session id
+    pub session_id: String,
+    /// a board id
+    pub board_id: String,
+    /// the color of the player
+    pub color: Color,
+}
+
+/// A game in progress, that can be watched
+#[derive(Serialize, Debug)]
+pub struct WsLiveBoard {
+    /// a board id
+    pub board_id: String,
+    /// the nick of the black player
+    pub black: String,
+    /// the nick of the white player
+    pub white: String,
+    /// (black discs, white discs)
+    pub score: (usize, usize),
+    pub spectators: usize,
+}
+
+/// The game the session is watching
+#[derive(Serialize, Debug)]
+pub struct WsWatchedBoard {
+    /// registered session id
+    pub session_id: String,
+    /// a board id
+    pub board_id: String,
+    /// the nick of the black player
+    pub black: String,
+    /// the nick of the white player
+    pub white: String,
+    /// the state of the board
+    pub state: WsBoardState,
+    /// the moves played since the beginning, `x + y * 8`
+    pub moves: Vec<usize>,
+    pub spectators: usize,
+    /// `None` for a game without clock
+    pub clocks: Option<WsClocks>,
+}
+
+/// The game cannot be watched
+#[derive(Serialize, Debug)]
+pub struct WsWatchFailed {
+    /// registered session id
+    pub session_id: String,
+    /// a board id
+    pub board_id: String,
+    pub reason: String,
+}
+
+/// The number of sessions watching a board
+#[derive(Serialize, Debug)]
+pub struct WsSpectators {
+    /// registered session id
+    pub session_id: String,
+    /// a board id
+    pub board_id: String,
+    pub count: usize,
+}
+
+/// A message of the chat
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WsChatMessage {
+    /// the nick of the author
+    pub author: String,
+    pub text: String,
+    /// milliseconds since the unix epoch
+    pub sent_at: i64,
+}
+
+/// A message sent to a board or to the lobby
+#[derive(Serialize, Debug)]
+pub struct WsChat {
+    /// registered session id
+    pub session_id: String,
+    /// a board id, `None` for the lobby
+    pub board_id: Option<String>,
+    pub message: WsChatMessage,
+}
+
+/// The last messages of a board or of the lobby
+#[derive(Serialize, Debug)]
+pub struct WsChatHistory {
+    /// registered session id
+    pub session_id: String,
+    /// a board id, `None` for the lobby
+    pub board_id: Option<String>,
+    /// the oldest first
+    pub messages: Vec<WsChatMessage>,
+}
+
+/// The message has not been sent
+#[derive(Serialize, Debug)]
+pub struct WsChatFailed {
+    /// registered session id
+    pub session_id: String,
+    pub reason: String,
+}
+
+/// The opponent of a finished game offers a rematch, or declined it
+#[derive(Serialize, Debug)]
+pub struct WsRematch {
+    /// registered session id
+    pub session_id: String,
+    /// the board of the finished game
+    pub board_id: String,
+}
+
+/// The game cannot be resigned or aborted, or the rematch cannot start
+#[derive(Serialize, Debug)]
+pub struct WsGameActionFailed {
+    /// registered session id
+    pub session_id: String,
+    /// a board id
+    pub board_id: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum TournamentStatus {
+    /// waiting for the organizer to start it
+    Pending,
+    Running,
+    Finished,
+}
+
+/// How a tournament game ended, forfeits included
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum GameOutcome {
+    BlackWins,
+    WhiteWins,
+    Draw,
+    /// neither player came to the board
+    DoubleForfeit,
+}
+
+/// The rank of a player in a tournament
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WsStanding {
+    /// from 1
+    pub rank: usize,
+    /// the nickname of the player
+    pub player: String,
+    /// a win is worth 1, a draw 0.5, a bye 1
+    pub points: f64,
+    /// the points of the opponents
+    pub buchholz: f64,
+    /// the points of the beaten opponents, and half those of the drawn ones
+    pub sonneborn_berger: f64,
+    /// the games played, byes excluded
+    pub games: usize,
+    pub withdrawn: bool,
+}
+
+/// A game of the current round of a tournament
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WsPairing {
+    /// the nickname of the black player
+    pub black: String,
+    /// the nickname of the white player
+    pub white: String,
+    /// the board of the game, `None` for a forfeit
+    pub board_id: Option<String>,
+    /// `None` while the game is played
+    pub outcome: Option<GameOutcome>,
+}
+
+/// A tournament, as published to its players and by the API
+#[derive(Serialize, Debug, Clone)]
+pub struct WsTournamentState {
+    /// a tournament id
+    pub tournament_id: String,
+    pub name: String,
+    /// the nickname of the organizer
+    pub organizer: String,
+    pub format: TournamentFormat,
+    pub time_control: Option<TimeControl>,
+    pub rated: bool,
+    pub status: TournamentStatus,
+    /// the current round, from 1, 0 before the tournament starts
+    pub round: usize,
+    /// the number of rounds
+    pub rounds: usize,
+    /// the best ranked first
+    pub standings: Vec<WsStanding>,
+    /// the games of the current round
+    pub pairings: Vec<WsPairing>,
+    /// the nicknames of the players without opponent in the current round
+    pub byes: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WsTournament {
+    /// registered session id
+    pub session_id: String,
+    #[serde(flatten)]
+    pub tournament: WsTournamentState,
+}
+
+/// The tournament cannot be created, started, read or left
+#[derive(Serialize, Debug)]
+pub struct WsTournamentFailed {
+    /// registered session id
+    pub session_id: String,
+    pub reason: String,
+}
+
+/// The session is logged in to an account
+#[derive(Serialize, Debug)]
+pub struct WsLoggedIn {
+    /// registered session id
+    pub session_id: String,
+    /// the name of the account, as registered
+    pub name: String,
+    /// a secret to resume the session from another websocket
+    pub resume_token: String,
+}
+
+/// The registration, the login or the nickname is refused
+#[derive(Serialize, Debug)]
+pub struct WsLoginFailed {
+    /// registered session id
+    pub session_id: String,
+    pub reason: String,
+}
+
+/// Why a request has been refused
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum WsErrorCode {
+    /// the request cannot be read
+    InvalidRequest,
+    /// a field of the request is refused, an id, a nickname or a position
+    InvalidInput,
+    /// the session id of the payload is not the one of the websocket
+    InvalidSession,
+    /// the session sends too many requests
+    RateLimited,
+}
+
+/// A request is refused, the websocket is closed after too many errors
+#[derive(Serialize, Debug)]
+pub struct WsError {
+    pub code: WsErrorCode,
+    pub message: String,
+    /// the name of the refused request, if it could be read
+    pub request: Option<String>,
 }
 
 /// This type handle type per command
@@ -105,7 +842,41 @@ pub enum WsResponse {
     /// `connect` command parameters
     ConnectedParam(WsConnectedParam),
     JoinedBoard(WsJoinedBoard),
+    JoinFailed(WsJoinFailed),
+    BoardCancelled(WsBoardCancelled),
     OpponentJoinedBoard(WsOpponentJoinedBoard),
     PlayedBoard(WsPlayBoard),
-    OpponentDisconnected(WsOpponentDisconnected),
+    Clocks(WsBoardClocks),
+    GameResult(WsGameResult),
+    RejectedMove(WsRejectedMove),
+    /// the answer to a `KeepAlive` of the client
+    KeepAlive,
+    Resumed(WsResumed),
+    ResumeFailed(WsResumeFailed),
+    /// the seat of the opponent is held until it comes back
+    OpponentReconnecting(WsOpponentPresence),
+    OpponentReconnected(WsOpponentPresence),
+    /// the boards of the lobby, the oldest first
+    OpenBoards(Vec<WsOpenBoard>),
+    LiveBoards(Vec<WsLiveBoard>),
+    /// the snapshot of the watched game, followed by its moves and its result
+    WatchedBoard(WsWatchedBoard),
+    WatchFailed(WsWatchFailed),
+    /// sent to the players and the spectators when someone watches or leaves
+    Spectators(WsSpectators),
+    Chat(WsChat),
+    /// sent when the user joins or watches a board, or enters the lobby
+    ChatHistory(WsChatHistory),
+    ChatFailed(WsChatFailed),
+    /// the rematch starts with a `JoinedBoard` once accepted
+    RematchOffered(WsRematch),
+    RematchDeclined(WsRematch),
+    GameActionFailed(WsGameActionFailed),
+    /// the standings and the current round of a tournament
+    Tournament(WsTournament),
+    TournamentFailed(WsTournamentFailed),
+    LoggedIn(WsLoggedIn),
+    LoginFailed(WsLoginFailed),
+    /// the request has not been handled
+    Error(WsError),
 }
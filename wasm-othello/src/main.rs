@@ -2,6 +2,7 @@
 extern crate log;
 extern crate web_logger;
 
+extern crate othello_engine as engine;
 extern crate stdweb;
 
 use std::rc::Rc;
@@ -11,23 +12,46 @@ use stdweb::traits::*;
 use std::f64::consts::PI;
 
 use stdweb::unstable::TryInto;
-use stdweb::web::html_element::CanvasElement;
-use stdweb::web::{document, CanvasRenderingContext2d, EventListenerHandle, FillRule};
+use stdweb::web::html_element::{CanvasElement, SelectElement};
+use stdweb::web::{document, set_timeout, CanvasRenderingContext2d, Element,
+                  EventListenerHandle, FillRule};
 
 use stdweb::web::event::{ClickEvent, ConcreteEvent};
 
-mod board;
+pub use engine::model::{BoardModel, Cell, BOARD_SIZE};
 
-pub use board::{Board, Cell, BOARD_SIZE};
+/// Depth of the midgame search for each level of the computer player
+const LEVELS: [usize; 4] = [1, 2, 4, 5];
+
+/// Let the previous move be painted before the computer plays
+const COMPUTER_DELAY_MS: u32 = 300;
+
+/// Who plays against whom
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    /// two players sharing the mouse
+    HotSeat,
+    /// the computer plays against `human`, searching at `depth`
+    Computer { human: Cell, depth: usize },
+}
+
+impl Mode {
+    fn is_computer(&self, player: Cell) -> bool {
+        match *self {
+            Mode::HotSeat => false,
+            Mode::Computer { human, .. } => human != player,
+        }
+    }
+}
 
 pub struct BoardUI {
-    board: Board,
+    board: BoardModel,
     cell_width: f64,
     margin_width: f64,
 }
 
 impl BoardUI {
-    pub fn new(board: Board, cell_width: u32, margin_width: u32) -> Self {
+    pub fn new(board: BoardModel, cell_width: u32, margin_width: u32) -> Self {
         BoardUI {
             board,
             cell_width: cell_width as f64,
@@ -93,7 +117,9 @@ impl BoardUI {
                 }
             }
         }
-
+        if player == Cell::Empty {
+            return;
+        }
         for pos in self.board.get_possibilities(player) {
             let width = self.cell_width - self.margin_width * 2.;
             let posx = (pos as f64 % BOARD_SIZE as f64).floor() * self.cell_width;
@@ -120,17 +146,19 @@ pub struct Store {
     player: Cell,
     game_over: bool,
     cell_width: u32,
+    mode: Mode,
 }
 
 impl Store {
-    fn new(cell_width: u32) -> Self {
-        let board = Board::new();
+    fn new(cell_width: u32, mode: Mode) -> Self {
+        let board = BoardModel::new();
         let board = BoardUI::new(board, cell_width, 1);
         Store {
             board,
             cell_width,
             game_over: false,
             player: Cell::Black,
+            mode,
         }
     }
 
@@ -139,27 +167,139 @@ impl Store {
     }
 
     fn paint(&self, context: &CanvasRenderingContext2d) {
-        self.board.paint(self.player, context);
+        let player = if self.game_over || self.mode.is_computer(self.player) {
+            Cell::Empty
+        } else {
+            self.player
+        };
+        self.board.paint(player, context);
         let score = self.board.score();
         info!("Black: {} - White: {}", score.0, score.1);
     }
 
     fn play(&mut self, x: usize, y: usize) -> Result<(), ()> {
-        if x > BOARD_SIZE && y > BOARD_SIZE {
+        if self.game_over {
+            return Err(());
+        }
+        if x >= BOARD_SIZE || y >= BOARD_SIZE {
             // prevent outside of the grid click
             return Err(());
         }
-        if let Ok(_) = self.board.board.set_cell(x, y, self.player) {
-            if self.board.can_play(self.player.opposite()) {
-                self.player = self.player.opposite();
-                info!("Player {:?} play", self.player);
-            } else if !self.board.can_play(self.player) {
-                info!("Game Over");
-                self.game_over = true;
-            }
+        self.board.board.set_cell(x, y, self.player)?;
+        if self.board.can_play(self.player.opposite()) {
+            self.player = self.player.opposite();
+            info!("Player {:?} play", self.player);
+        } else if !self.board.can_play(self.player) {
+            info!("Game Over");
+            self.game_over = true;
         }
         Ok(())
     }
+
+    /// The human player clicked on the board
+    fn human_play(&mut self, x: usize, y: usize) -> Result<(), ()> {
+        if self.mode.is_computer(self.player) {
+            return Err(());
+        }
+        self.play(x, y)
+    }
+
+    fn computer_to_play(&self) -> bool {
+        !self.game_over && self.mode.is_computer(self.player)
+    }
+
+    fn computer_play(&mut self) -> Result<(), ()> {
+        if !self.computer_to_play() {
+            return Err(());
+        }
+        let depth = match self.mode {
+            Mode::Computer { depth, .. } => depth,
+            Mode::HotSeat => return Err(()),
+        };
+        let (pos, score) = engine::best_move(&self.board.board, self.player, depth).ok_or(())?;
+        info!("Computer play {} ({:+})", pos, score);
+        self.play(pos % BOARD_SIZE, pos / BOARD_SIZE)
+    }
+
+    fn player_name(&self, player: Cell) -> &'static str {
+        match (self.mode.is_computer(player), player) {
+            (true, _) => "Computer",
+            (false, Cell::Black) => "Black",
+            (false, _) => "White",
+        }
+    }
+
+    /// A line telling who plays, or the result of the game
+    fn status(&self) -> String {
+        let score = self.board.score();
+        if self.game_over {
+            let result = if score.0 == score.1 {
+                "Draw".to_string()
+            } else if score.0 > score.1 {
+                format!("{} wins", self.player_name(Cell::Black))
+            } else {
+                format!("{} wins", self.player_name(Cell::White))
+            };
+            format!(
+                "Game Over: {} - Black {} / White {}",
+                result, score.0, score.1
+            )
+        } else if self.mode.is_computer(self.player) {
+            format!("Computer is thinking - Black {} / White {}", score.0, score.1)
+        } else {
+            let player = if self.player == Cell::Black { "Black" } else { "White" };
+            format!("{} to play - Black {} / White {}", player, score.0, score.1)
+        }
+    }
+}
+
+/// Controls around the board to choose the kind of game
+struct Controls {
+    mode: SelectElement,
+    color: SelectElement,
+    level: SelectElement,
+    new_game: Element,
+    status: Element,
+}
+
+impl Controls {
+    fn new() -> Controls {
+        let select = |selector: &str| -> SelectElement {
+            document()
+                .query_selector(selector)
+                .unwrap()
+                .unwrap()
+                .try_into()
+                .unwrap()
+        };
+        Controls {
+            mode: select("#mode"),
+            color: select("#color"),
+            level: select("#level"),
+            new_game: document().query_selector("#new-game").unwrap().unwrap(),
+            status: document().query_selector("#status").unwrap().unwrap(),
+        }
+    }
+
+    /// The game selected in the controls
+    fn mode(&self) -> Mode {
+        if self.mode.value() != Some("computer".to_string()) {
+            return Mode::HotSeat;
+        }
+        let human = match self.color.value() {
+            Some(ref color) if color == "white" => Cell::White,
+            _ => Cell::Black,
+        };
+        let level = self.level
+            .value()
+            .and_then(|level| level.parse::<usize>().ok())
+            .unwrap_or(1);
+        let level = ::std::cmp::min(::std::cmp::max(level, 1), LEVELS.len());
+        Mode::Computer {
+            human,
+            depth: LEVELS[level - 1],
+        }
+    }
 }
 
 struct Canvas {
@@ -198,34 +338,84 @@ impl Canvas {
 
 struct AnimatedCanvas {
     canvas: Canvas,
+    controls: Rc<Controls>,
     store: Rc<RefCell<Store>>,
 }
 
+/// Paint the board and display the status line
+fn refresh(store: &Store, context: &CanvasRenderingContext2d, controls: &Controls) {
+    store.paint(context);
+    controls.status.set_text_content(&store.status());
+}
+
+/// Let the computer play its moves, one at a time
+fn schedule_computer(
+    store: Rc<RefCell<Store>>,
+    context: CanvasRenderingContext2d,
+    controls: Rc<Controls>,
+) {
+    if !store.borrow().computer_to_play() {
+        return;
+    }
+    set_timeout(
+        move || {
+            let played = store.borrow_mut().computer_play();
+            if played.is_ok() {
+                refresh(&store.borrow(), &context, &controls);
+                // the human may have to pass
+                schedule_computer(store, context, controls);
+            }
+        },
+        COMPUTER_DELAY_MS,
+    );
+}
+
 impl AnimatedCanvas {
-    fn new(store: Store, canvas: Canvas) -> AnimatedCanvas {
+    fn new(store: Store, canvas: Canvas, controls: Controls) -> AnimatedCanvas {
         let store_rc = Rc::new(RefCell::new(store));
         AnimatedCanvas {
             canvas,
+            controls: Rc::new(controls),
             store: store_rc,
         }
     }
     fn attach_event(&mut self) {
         let context = self.canvas.context();
         let store = self.store.clone();
+        let controls = self.controls.clone();
         self.canvas.add_event_listener(move |event: ClickEvent| {
-            let mut store = store.borrow_mut();
-            let x = (event.offset_x() / store.cell_width() as f64) as usize;
-            let y = (event.offset_y() / store.cell_width() as f64) as usize;
-            if let Ok(_) = store.play(x, y) {
-                store.paint(&context);
+            let played = {
+                let mut store = store.borrow_mut();
+                let x = (event.offset_x() / store.cell_width() as f64) as usize;
+                let y = (event.offset_y() / store.cell_width() as f64) as usize;
+                store.human_play(x, y)
+            };
+            if played.is_ok() {
+                refresh(&store.borrow(), &context, &controls);
+                schedule_computer(store.clone(), context.clone(), controls.clone());
+            }
+        });
+
+        let context = self.canvas.context();
+        let store = self.store.clone();
+        let controls = self.controls.clone();
+        self.controls.new_game.add_event_listener(move |_: ClickEvent| {
+            let mode = controls.mode();
+            info!("New game {:?}", mode);
+            {
+                let mut store = store.borrow_mut();
+                let cell_width = store.cell_width();
+                *store = Store::new(cell_width, mode);
             }
+            refresh(&store.borrow(), &context, &controls);
+            schedule_computer(store.clone(), context.clone(), controls.clone());
         });
     }
 
     fn paint(&mut self) {
         let context = self.canvas.context();
         let store = self.store.clone();
-        store.borrow_mut().paint(&context);
+        refresh(&store.borrow(), &context, &self.controls);
     }
 }
 
@@ -233,9 +423,10 @@ fn main() {
     web_logger::init();
     info!("Welcome aboard");
 
-    let store = Store::new(60);
+    let controls = Controls::new();
+    let store = Store::new(60, controls.mode());
     let canvas = Canvas::new("#game", &store);
-    let mut ac = AnimatedCanvas::new(store, canvas);
+    let mut ac = AnimatedCanvas::new(store, canvas, controls);
     ac.attach_event();
     ac.paint();
 }